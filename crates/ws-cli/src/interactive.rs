@@ -1,13 +1,14 @@
 use anyhow::{Context, Result, bail};
-use inquire::{Confirm, Select, Text};
+use inquire::{Confirm, MultiSelect, Select, Text};
 use rust_i18n::t;
 
 use ws_core::cli::WsCommand;
 use ws_core::config::load_config;
+use ws_core::exec::Executor;
 use ws_core::git::{find_bare_dir, is_inside_git_worktree};
 use ws_core::store::{Strategy, read_manifest, require_store};
 
-pub(crate) fn interactive_mode() -> Result<()> {
+pub(crate) fn interactive_mode(exec: &Executor) -> Result<()> {
     let top_items: Vec<String> = vec![
         format!("new       {}", t!("interactive.menu.new")),
         format!("rm        {}", t!("interactive.menu.rm")),
@@ -33,21 +34,21 @@ pub(crate) fn interactive_mode() -> Result<()> {
     let cmd = selected.split_whitespace().next().unwrap_or("");
 
     match cmd {
-        "new" => interactive_new(),
-        "rm" => interactive_rm(),
+        "new" => interactive_new(exec),
+        "rm" => interactive_rm(exec),
         "status" => {
             eprintln!("> ws status");
             let ctx = ws_core::context::AppContext::build()?;
             ws_core::commands::status::cmd_status(&ctx)
         }
-        "store" => interactive_store(),
-        "repos" => interactive_repos(),
+        "store" => interactive_store(exec),
+        "repos" => interactive_repos(exec),
         "open" => interactive_open(),
         _ => bail!("{}", t!("interactive.unknown_command", cmd = cmd)),
     }
 }
 
-fn interactive_clone() -> Result<()> {
+fn interactive_clone(exec: &Executor) -> Result<()> {
     let url_input = Text::new(&t!("interactive.clone.url_prompt"))
         .with_help_message(&t!("interactive.clone.url_help"))
         .prompt_skippable()
@@ -69,10 +70,10 @@ fn interactive_clone() -> Result<()> {
             format!(" {}", url_input)
         }
     );
-    ws_core::commands::worktree::cmd_clone(&cmd)
+    ws_core::commands::worktree::cmd_clone(&cmd, exec)
 }
 
-fn interactive_new() -> Result<()> {
+fn interactive_new(exec: &Executor) -> Result<()> {
     let name = Text::new(&t!("interactive.new.name_prompt"))
         .prompt()
         .context(t!("interactive.input_failed").to_string())?;
@@ -129,6 +130,8 @@ fn interactive_new() -> Result<()> {
         directory,
         branch,
         from,
+        profile: None,
+        sparse: Vec::new(),
     };
 
     let mut cmd_str = format!("ws new {}", name);
@@ -143,10 +146,10 @@ fn interactive_new() -> Result<()> {
     }
     eprintln!("> {}", cmd_str);
 
-    ws_core::commands::worktree::cmd_new(&cmd)
+    ws_core::commands::worktree::cmd_new(&cmd, exec)
 }
 
-fn interactive_rm() -> Result<()> {
+fn interactive_rm(exec: &Executor) -> Result<()> {
     let worktree_list = ws_core::git::git_output(&["worktree", "list"])?;
     let lines: Vec<&str> = worktree_list.lines().skip(1).collect();
 
@@ -174,16 +177,22 @@ fn interactive_rm() -> Result<()> {
         force: false,
     };
     eprintln!("> ws rm {}", path);
-    ws_core::commands::worktree::cmd_rm(&cmd)
+    ws_core::commands::worktree::cmd_rm(&cmd, exec)
 }
 
-fn interactive_store() -> Result<()> {
+fn interactive_store(exec: &Executor) -> Result<()> {
     let store_items: Vec<String> = vec![
         format!("track     {}", t!("interactive.store_menu.track")),
         format!("status    {}", t!("interactive.store_menu.status")),
         format!("push      {}", t!("interactive.store_menu.push")),
         format!("pull      {}", t!("interactive.store_menu.pull")),
         format!("untrack   {}", t!("interactive.store_menu.untrack")),
+        format!("diff      {}", t!("interactive.store_menu.diff")),
+        format!("suggest   {}", t!("interactive.store_menu.suggest")),
+        format!("apply     {}", t!("interactive.store_menu.apply")),
+        format!("mv        {}", t!("interactive.store_menu.mv")),
+        format!("set-strategy {}", t!("interactive.store_menu.set_strategy")),
+        format!("verify    {}", t!("interactive.store_menu.verify")),
     ];
 
     let items_ref: Vec<&str> = store_items.iter().map(|s| s.as_str()).collect();
@@ -199,7 +208,7 @@ fn interactive_store() -> Result<()> {
     let cmd = selected.split_whitespace().next().unwrap_or("");
 
     match cmd {
-        "track" => interactive_store_track(),
+        "track" => interactive_store_track(exec),
         "status" => {
             eprintln!("> ws store status");
             ws_core::commands::store::cmd_store_status()
@@ -217,6 +226,7 @@ fn interactive_store() -> Result<()> {
                 } else {
                     Some(file_input.clone())
                 },
+                include_overlay: false,
             };
             eprintln!(
                 "> ws store push{}",
@@ -226,7 +236,7 @@ fn interactive_store() -> Result<()> {
                     format!(" {}", file_input)
                 }
             );
-            ws_core::commands::store::cmd_store_push(&cmd)
+            ws_core::commands::store::cmd_store_push(&cmd, exec)
         }
         "pull" => {
             let file_input = Text::new(&t!("interactive.store_pull.file_prompt"))
@@ -258,15 +268,53 @@ fn interactive_store() -> Result<()> {
                     format!(" {}", file_input)
                 }
             );
-            ws_core::commands::store::cmd_store_pull(&cmd)
+            ws_core::commands::store::cmd_store_pull(&cmd, exec)
+        }
+        "untrack" => interactive_store_untrack(exec),
+        "diff" => {
+            eprintln!("> ws store diff");
+            let cmd = ws_core::cli::StoreDiffCmd {
+                file: None,
+                show_values: false,
+            };
+            ws_core::commands::store::cmd_store_diff(&cmd)
+        }
+        "suggest" => interactive_store_suggest(exec),
+        "apply" => {
+            eprintln!("> ws store apply");
+            let cmd = ws_core::cli::StoreApplyCmd {
+                file: None,
+                profile: None,
+                no_profile: false,
+            };
+            ws_core::commands::store::cmd_store_apply(&cmd, exec)
+        }
+        "mv" => interactive_store_mv(exec),
+        "set-strategy" => interactive_store_set_strategy(exec),
+        "verify" => {
+            eprintln!("> ws store verify");
+            let cmd = ws_core::cli::StoreVerifyCmd {
+                file: None,
+                update: false,
+            };
+            ws_core::commands::store::cmd_store_verify(&cmd, exec)
         }
-        "untrack" => interactive_store_untrack(),
         _ => bail!("{}", t!("interactive.unknown_command", cmd = cmd)),
     }
 }
 
-fn interactive_store_track() -> Result<()> {
-    let strategy_items = vec![Strategy::Symlink, Strategy::Copy];
+fn interactive_store_track(exec: &Executor) -> Result<()> {
+    let strategy_items = vec![
+        Strategy::Symlink,
+        Strategy::Copy,
+        Strategy::Dotenv,
+        Strategy::Hardlink,
+        Strategy::Clone,
+        Strategy::Seed,
+        Strategy::Command,
+        Strategy::MergeJson,
+        Strategy::LinkTree,
+    ];
     let display_items: Vec<&str> = strategy_items.iter().map(|s| s.as_str()).collect();
     let selected = Select::new(
         &t!("interactive.store_track.select_strategy"),
@@ -291,12 +339,189 @@ fn interactive_store_track() -> Result<()> {
         bail!("{}", t!("interactive.store_track.empty_file"));
     }
 
-    eprintln!("> ws store track -s {} {}", strategy, &file);
-    let cmd = ws_core::cli::StoreTrackCmd { strategy, file };
-    ws_core::commands::store::cmd_store_track(&cmd)
+    let command = if strategy == Strategy::Command {
+        let command = Text::new(&t!("interactive.store_track.command_prompt"))
+            .prompt()
+            .context(t!("interactive.input_failed").to_string())?;
+        if command.trim().is_empty() {
+            bail!("{}", t!("store.command_required"));
+        }
+        Some(command)
+    } else {
+        None
+    };
+
+    match command {
+        Some(ref command) => eprintln!(
+            "> ws store track -s {} --command {:?} {}",
+            strategy, command, &file
+        ),
+        None => eprintln!("> ws store track -s {} {}", strategy, &file),
+    }
+    let cmd = ws_core::cli::StoreTrackCmd {
+        strategy: Some(strategy),
+        file: Some(file),
+        command,
+        include: Vec::new(),
+        exclude: Vec::new(),
+        auto: false,
+    };
+    ws_core::commands::store::cmd_store_track(&cmd, exec)
+}
+
+/// `ws store suggest -i`: 候補を複数選択し、strategy を確認しながらまとめて track する
+pub(crate) fn interactive_store_suggest(exec: &Executor) -> Result<()> {
+    let suggestions = ws_core::commands::store::collect_suggestions()?;
+    if suggestions.is_empty() {
+        eprintln!("{}", t!("store.no_suggestions"));
+        return Ok(());
+    }
+
+    let labels: Vec<String> = suggestions
+        .iter()
+        .map(|s| {
+            format!(
+                "{}{}  ({}/{}, {})",
+                s.path,
+                if s.is_dir { "/" } else { "" },
+                s.present_in.len(),
+                s.worktree_count,
+                s.strategy
+            )
+        })
+        .collect();
+    let items_ref: Vec<&str> = labels.iter().map(|s| s.as_str()).collect();
+    let selected = MultiSelect::new(&t!("interactive.store_suggest.select_files"), items_ref)
+        .prompt_skippable()
+        .context(t!("interactive.selection_failed").to_string())?;
+
+    let selected = match selected {
+        Some(s) if !s.is_empty() => s,
+        Some(_) => return Ok(()),
+        None => bail!("{}", t!("interactive.cancelled")),
+    };
+
+    let strategy_items = [
+        Strategy::Symlink,
+        Strategy::Copy,
+        Strategy::Dotenv,
+        Strategy::Hardlink,
+        Strategy::Clone,
+        Strategy::Seed,
+        Strategy::MergeJson,
+        Strategy::LinkTree,
+    ];
+    for label in selected {
+        let idx = labels
+            .iter()
+            .position(|l| l == label)
+            .expect("selected item must exist in labels");
+        let suggestion = &suggestions[idx];
+
+        let display_items: Vec<&str> = strategy_items.iter().map(|s| s.as_str()).collect();
+        let default = strategy_items
+            .iter()
+            .position(|s| *s == suggestion.strategy)
+            .unwrap_or(0);
+        let chosen = Select::new(
+            &t!(
+                "interactive.store_suggest.select_strategy",
+                file = &suggestion.path
+            ),
+            display_items,
+        )
+        .with_starting_cursor(default)
+        .prompt_skippable()
+        .context(t!("interactive.selection_failed").to_string())?;
+
+        let strategy = match chosen {
+            Some(s) => strategy_items
+                .iter()
+                .find(|item| item.as_str() == s)
+                .expect("selected item must exist in strategy_items"),
+            None => bail!("{}", t!("interactive.cancelled")),
+        };
+
+        eprintln!("> ws store track -s {} {}", strategy, suggestion.path);
+        ws_core::commands::store::track_suggestion(suggestion, strategy, exec)?;
+    }
+    Ok(())
+}
+
+/// manifest に登録されているファイルから1つ選ばせる。
+fn select_tracked_file(prompt: &str) -> Result<String> {
+    let store = require_store()?;
+    let entries = read_manifest(&store)?;
+    if entries.is_empty() {
+        bail!("{}", t!("store.no_tracked_files"));
+    }
+    let file_list: Vec<String> = entries.into_iter().map(|e| e.filepath).collect();
+    let items_ref: Vec<&str> = file_list.iter().map(|s| s.as_str()).collect();
+    let selected = Select::new(prompt, items_ref)
+        .prompt_skippable()
+        .context(t!("interactive.selection_failed").to_string())?;
+    match selected {
+        Some(s) => Ok(s.to_string()),
+        None => bail!("{}", t!("interactive.cancelled")),
+    }
+}
+
+fn interactive_store_mv(exec: &Executor) -> Result<()> {
+    let old = select_tracked_file(&t!("interactive.store_mv.select_file"))?;
+    let new = Text::new(&t!("interactive.store_mv.new_prompt"))
+        .with_initial_value(&old)
+        .prompt()
+        .context(t!("interactive.input_failed").to_string())?;
+    if new.is_empty() || new == old {
+        bail!("{}", t!("interactive.cancelled"));
+    }
+
+    eprintln!("> ws store mv {} {}", old, new);
+    let cmd = ws_core::cli::StoreMvCmd { old, new };
+    ws_core::commands::store::cmd_store_mv(&cmd, exec)
+}
+
+fn interactive_store_set_strategy(exec: &Executor) -> Result<()> {
+    let file = select_tracked_file(&t!("interactive.store_set_strategy.select_file"))?;
+    let display_items: Vec<&str> = Strategy::all().iter().map(|s| s.as_str()).collect();
+    let selected = Select::new(
+        &t!("interactive.store_track.select_strategy"),
+        display_items,
+    )
+    .prompt_skippable()
+    .context(t!("interactive.selection_failed").to_string())?;
+    let strategy: Strategy = match selected {
+        Some(s) => s.parse()?,
+        None => bail!("{}", t!("interactive.cancelled")),
+    };
+
+    let command = if strategy == Strategy::Command {
+        Some(
+            Text::new(&t!("interactive.store_track.command_prompt"))
+                .prompt()
+                .context(t!("interactive.input_failed").to_string())?,
+        )
+    } else {
+        None
+    };
+
+    match command {
+        Some(ref command) => eprintln!(
+            "> ws store set-strategy {} {} --command {:?}",
+            file, strategy, command
+        ),
+        None => eprintln!("> ws store set-strategy {} {}", file, strategy),
+    }
+    let cmd = ws_core::cli::StoreSetStrategyCmd {
+        file,
+        strategy,
+        command,
+        force: false,
+    };
+    ws_core::commands::store::cmd_store_set_strategy(&cmd, exec)
 }
 
-fn interactive_store_untrack() -> Result<()> {
+fn interactive_store_untrack(exec: &Executor) -> Result<()> {
     let store = require_store();
     if let Ok(store) = store {
         let entries = read_manifest(&store)?;
@@ -308,13 +533,7 @@ fn interactive_store_untrack() -> Result<()> {
                 .context(t!("interactive.selection_failed").to_string())?;
 
             return match selected {
-                Some(s) => {
-                    let cmd = ws_core::cli::StoreUntrackCmd {
-                        file: s.to_string(),
-                    };
-                    eprintln!("> ws store untrack {}", s);
-                    ws_core::commands::store::cmd_store_untrack(&cmd)
-                }
+                Some(s) => run_store_untrack(s.to_string(), exec),
                 None => bail!("{}", t!("interactive.cancelled")),
             };
         }
@@ -329,12 +548,30 @@ fn interactive_store_untrack() -> Result<()> {
         bail!("{}", t!("interactive.store_untrack.empty_file"));
     }
 
-    let cmd = ws_core::cli::StoreUntrackCmd { file: file.clone() };
-    eprintln!("> ws store untrack {}", file);
-    ws_core::commands::store::cmd_store_untrack(&cmd)
+    run_store_untrack(file, exec)
+}
+
+fn run_store_untrack(file: String, exec: &Executor) -> Result<()> {
+    let purge = Confirm::new(&t!("interactive.store_untrack.purge_prompt"))
+        .with_default(false)
+        .prompt_skippable()
+        .context(t!("interactive.input_failed").to_string())?
+        .unwrap_or(false);
+
+    eprintln!(
+        "> ws store untrack{} {}",
+        if purge { " --purge" } else { "" },
+        file
+    );
+    let cmd = ws_core::cli::StoreUntrackCmd {
+        file,
+        restore: false,
+        purge,
+    };
+    ws_core::commands::store::cmd_store_untrack(&cmd, exec)
 }
 
-fn interactive_repos() -> Result<()> {
+fn interactive_repos(exec: &Executor) -> Result<()> {
     let repos_items: Vec<String> = vec![
         format!("clone     {}", t!("interactive.repos_menu.clone")),
         format!("add       {}", t!("interactive.repos_menu.add")),
@@ -355,7 +592,7 @@ fn interactive_repos() -> Result<()> {
     let cmd = selected.split_whitespace().next().unwrap_or("");
 
     match cmd {
-        "clone" => interactive_clone(),
+        "clone" => interactive_clone(exec),
         "add" => interactive_repos_add(),
         "list" => {
             eprintln!("> ws repos list");
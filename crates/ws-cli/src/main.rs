@@ -3,26 +3,29 @@ mod interactive;
 use anyhow::Result;
 use rust_i18n::t;
 
-use ws_core::cli::{ReposCommand, StoreCommand, WsCommand};
+use ws_core::cli::{
+    ReposCommand, StoreCommand, StoreProfileCommand, StoreVariantCommand, WsCommand,
+};
 
 rust_i18n::i18n!("../../locales", fallback = "en");
 
 fn run(ws: ws_core::cli::Ws) -> Result<()> {
+    let exec = ws_core::exec::Executor::new(ws.dry_run);
     match ws.command {
-        WsCommand::New(cmd) => ws_core::commands::worktree::cmd_new(&cmd),
-        WsCommand::Rm(cmd) => ws_core::commands::worktree::cmd_rm(&cmd),
+        WsCommand::New(cmd) => ws_core::commands::worktree::cmd_new(&cmd, &exec),
+        WsCommand::Rm(cmd) => ws_core::commands::worktree::cmd_rm(&cmd, &exec),
         WsCommand::Open(cmd) => ws_core::commands::open::cmd_open(&cmd),
         WsCommand::Status(_) => {
             let ctx = ws_core::context::AppContext::build()?;
             ws_core::commands::status::cmd_status(&ctx)
         }
-        WsCommand::Interactive(_) => interactive::interactive_mode(),
+        WsCommand::Interactive(_) => interactive::interactive_mode(&exec),
         WsCommand::Completions(cmd) => {
             ws_core::commands::completions::cmd_completions(&cmd);
             Ok(())
         }
         WsCommand::Repos(cmd) => match cmd.command {
-            ReposCommand::Clone(c) => ws_core::commands::worktree::cmd_clone(&c),
+            ReposCommand::Clone(c) => ws_core::commands::worktree::cmd_clone(&c, &exec),
             ReposCommand::Add(c) => ws_core::commands::repos::cmd_repos_add(&c),
             ReposCommand::List(_) => {
                 let ctx = ws_core::context::AppContext::build()?;
@@ -31,11 +34,46 @@ fn run(ws: ws_core::cli::Ws) -> Result<()> {
             ReposCommand::Rm(c) => ws_core::commands::repos::cmd_repos_rm(&c),
         },
         WsCommand::Store(cmd) => match cmd.command {
-            StoreCommand::Track(c) => ws_core::commands::store::cmd_store_track(&c),
+            StoreCommand::Track(c) => ws_core::commands::store::cmd_store_track(&c, &exec),
             StoreCommand::Status(_) => ws_core::commands::store::cmd_store_status(),
-            StoreCommand::Push(c) => ws_core::commands::store::cmd_store_push(&c),
-            StoreCommand::Pull(c) => ws_core::commands::store::cmd_store_pull(&c),
-            StoreCommand::Untrack(c) => ws_core::commands::store::cmd_store_untrack(&c),
+            StoreCommand::Push(c) => ws_core::commands::store::cmd_store_push(&c, &exec),
+            StoreCommand::Pull(c) => ws_core::commands::store::cmd_store_pull(&c, &exec),
+            StoreCommand::Untrack(c) => ws_core::commands::store::cmd_store_untrack(&c, &exec),
+            StoreCommand::Diff(c) => ws_core::commands::store::cmd_store_diff(&c),
+            StoreCommand::Apply(c) => ws_core::commands::store::cmd_store_apply(&c, &exec),
+            StoreCommand::Mv(c) => ws_core::commands::store::cmd_store_mv(&c, &exec),
+            StoreCommand::SetStrategy(c) => {
+                ws_core::commands::store::cmd_store_set_strategy(&c, &exec)
+            }
+            StoreCommand::Verify(c) => ws_core::commands::store::cmd_store_verify(&c, &exec),
+            StoreCommand::Profile(c) => match c.command {
+                StoreProfileCommand::Add(c) => {
+                    ws_core::commands::store::cmd_store_profile_add(&c, &exec)
+                }
+                StoreProfileCommand::Rm(c) => {
+                    ws_core::commands::store::cmd_store_profile_rm(&c, &exec)
+                }
+                StoreProfileCommand::List(_) => ws_core::commands::store::cmd_store_profile_list(),
+            },
+            StoreCommand::Variant(c) => match c.command {
+                StoreVariantCommand::Add(c) => {
+                    ws_core::commands::store::cmd_store_variant_add(&c, &exec)
+                }
+                StoreVariantCommand::Rm(c) => {
+                    ws_core::commands::store::cmd_store_variant_rm(&c, &exec)
+                }
+                StoreVariantCommand::List(_) => ws_core::commands::store::cmd_store_variant_list(),
+            },
+            StoreCommand::Log(c) => ws_core::commands::store::cmd_store_log(&c),
+            StoreCommand::Recover(c) => ws_core::commands::store::cmd_store_recover(&c, &exec),
+            StoreCommand::Migrate(c) => ws_core::commands::store::cmd_store_migrate(&c, &exec),
+            StoreCommand::Suggest(c) => {
+                if c.interactive {
+                    interactive::interactive_store_suggest(&exec)
+                } else {
+                    ws_core::commands::store::cmd_store_suggest()
+                }
+            }
         },
     }
 }
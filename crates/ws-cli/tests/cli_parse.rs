@@ -27,7 +27,18 @@ fn store_help_shows_subcommands() {
             .and(predicate::str::contains("status"))
             .and(predicate::str::contains("push"))
             .and(predicate::str::contains("pull"))
-            .and(predicate::str::contains("untrack")),
+            .and(predicate::str::contains("untrack"))
+            .and(predicate::str::contains("diff"))
+            .and(predicate::str::contains("suggest"))
+            .and(predicate::str::contains("apply"))
+            .and(predicate::str::contains("mv"))
+            .and(predicate::str::contains("set-strategy"))
+            .and(predicate::str::contains("verify"))
+            .and(predicate::str::contains("profile"))
+            .and(predicate::str::contains("variant"))
+            .and(predicate::str::contains("log"))
+            .and(predicate::str::contains("recover"))
+            .and(predicate::str::contains("migrate")),
     );
 }
 
@@ -63,13 +74,18 @@ fn repos_rm_missing_name_fails() {
 
 #[test]
 fn store_track_missing_args_fails() {
-    // -s なし
-    ws().args(["store", "track", ".envrc"]).assert().failure();
-
-    // file なし
+    // file も --auto もなし
     ws().args(["store", "track", "-s", "symlink"])
         .assert()
         .failure();
+
+    // --auto はファイルや strategy と併用できない
+    ws().args(["store", "track", "--auto", ".envrc"])
+        .assert()
+        .failure();
+    ws().args(["store", "track", "--auto", "-s", "copy"])
+        .assert()
+        .failure();
 }
 
 #[test]
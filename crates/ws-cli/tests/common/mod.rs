@@ -104,17 +104,32 @@ impl TestRepo {
     pub fn init_store(&self) {
         let store = self.store_dir();
         std::fs::create_dir_all(&store).unwrap();
-        std::fs::write(store.join("manifest"), "").unwrap();
+        std::fs::write(store.join("manifest.toml"), "version = 1\n").unwrap();
     }
 
     /// manifest にエントリを追加
     pub fn add_manifest_entry(&self, strategy: &str, filepath: &str) {
-        let manifest = self.store_dir().join("manifest");
+        let manifest = self.store_dir().join("manifest.toml");
         let mut content = std::fs::read_to_string(&manifest).unwrap_or_default();
-        content.push_str(&format!("{}:{}\n", strategy, filepath));
+        content.push('\n');
+        content.push_str("[[files]]\n");
+        content.push_str(&manifest_record(strategy, filepath));
         std::fs::write(&manifest, content).unwrap();
     }
 
+    /// manifest (manifest.toml) の内容
+    pub fn manifest(&self) -> String {
+        std::fs::read_to_string(self.store_dir().join("manifest.toml")).unwrap()
+    }
+
+    /// 旧形式の manifest だけを置いた store にする
+    pub fn write_legacy_manifest(&self, content: &str) {
+        let store = self.store_dir();
+        std::fs::create_dir_all(&store).unwrap();
+        let _ = std::fs::remove_file(store.join("manifest.toml"));
+        std::fs::write(store.join("manifest"), content).unwrap();
+    }
+
     /// store にファイルを配置
     pub fn add_store_file(&self, filepath: &str, content: &str) {
         let path = self.store_dir().join(filepath);
@@ -124,6 +139,37 @@ impl TestRepo {
         std::fs::write(path, content).unwrap();
     }
 
+    /// main worktree にファイルを書き込んでコミットする
+    pub fn commit_files(&self, files: &[(&str, &str)]) {
+        let wt = self.main_worktree();
+        for (filepath, content) in files {
+            let path = wt.join(filepath);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).unwrap();
+            }
+            std::fs::write(path, content).unwrap();
+        }
+        for args in [
+            vec!["add", "."],
+            vec![
+                "-c",
+                "user.name=Test",
+                "-c",
+                "user.email=test@test.com",
+                "commit",
+                "-m",
+                "add files",
+            ],
+        ] {
+            let out = Command::new("git")
+                .args(&args)
+                .current_dir(&wt)
+                .output()
+                .expect("git failed");
+            assert!(out.status.success(), "git {:?} failed", args);
+        }
+    }
+
     /// ws バイナリを bare root の cwd で実行する Command を生成
     pub fn ws_cmd(&self) -> assert_cmd::Command {
         let mut cmd = assert_cmd::cargo_bin_cmd!("ws");
@@ -142,3 +188,8 @@ impl TestRepo {
         cmd
     }
 }
+
+/// manifest.toml でエントリのパスと strategy を表す行
+pub fn manifest_record(strategy: &str, filepath: &str) -> String {
+    format!("path = {:?}\nstrategy = {:?}\n", filepath, strategy)
+}
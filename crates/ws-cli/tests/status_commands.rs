@@ -163,3 +163,28 @@ fn status_marks_current_worktree_with_asterisk() {
         stdout
     );
 }
+
+/// 既存の worktree で branch を切り替えると、キャッシュがあっても新しい branch が表示される
+#[test]
+fn status_shows_branch_switched_inside_worktree() {
+    let repo = TestRepo::new();
+    repo.ws_cmd().args(["new", "feat"]).assert().success();
+    repo.ws_cmd_in("main")
+        .arg("status")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("feat"));
+
+    let out = std::process::Command::new("git")
+        .args(["switch", "-c", "renamed-branch"])
+        .current_dir(repo.path().join("feat"))
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+
+    repo.ws_cmd_in("main")
+        .arg("status")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("renamed-branch"));
+}
@@ -1,8 +1,9 @@
 mod common;
 
-use common::TestRepo;
+use common::{TestRepo, manifest_record};
 use predicates::prelude::*;
 use std::fs;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
 
 // ---- ws store track ----
 
@@ -20,8 +21,8 @@ fn track_symlink_registers_and_converts() {
         .success();
 
     // manifest に登録されている
-    let manifest = fs::read_to_string(repo.store_dir().join("manifest")).unwrap();
-    assert!(manifest.contains("symlink:.envrc"));
+    let manifest = repo.manifest();
+    assert!(manifest.contains(&manifest_record("symlink", ".envrc")));
 
     // store にマスターコピーが存在
     assert!(repo.store_dir().join(".envrc").is_file());
@@ -44,8 +45,8 @@ fn track_copy_registers() {
         .success();
 
     // manifest に登録されている
-    let manifest = fs::read_to_string(repo.store_dir().join("manifest")).unwrap();
-    assert!(manifest.contains("copy:.mcp.json"));
+    let manifest = repo.manifest();
+    assert!(manifest.contains(&manifest_record("copy", ".mcp.json")));
 
     // store にコピーが存在
     assert!(repo.store_dir().join(".mcp.json").is_file());
@@ -55,6 +56,75 @@ fn track_copy_registers() {
     assert!(!meta.file_type().is_symlink());
 }
 
+#[test]
+fn track_without_strategy_uses_config_rules() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    fs::write(
+        &repo.config_path,
+        "[store.rules]\n\".env*\" = \"dotenv\"\n\".claude/**/*.local.json\" = \"symlink\"\n",
+    )
+    .unwrap();
+    fs::write(wt.join(".env.local"), "A=1\n").unwrap();
+    fs::write(wt.join("notes.txt"), "x").unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "track", ".env.local"])
+        .assert()
+        .success();
+    let manifest = repo.manifest();
+    assert!(manifest.contains(&manifest_record("dotenv", ".env.local")));
+
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "notes.txt"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("-s"));
+}
+
+#[test]
+fn track_auto_tracks_matching_ignored_files() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    fs::write(
+        &repo.config_path,
+        "[store.rules]\n\".env*\" = \"copy\"\n\".claude/**/*.local.json\" = \"symlink\"\n",
+    )
+    .unwrap();
+    fs::write(
+        wt.join(".gitignore"),
+        ".env*\n.claude/\nnode_modules/\n*.log\n",
+    )
+    .unwrap();
+    fs::write(wt.join(".env"), "A=1\n").unwrap();
+    fs::create_dir_all(wt.join(".claude/nested")).unwrap();
+    fs::write(wt.join(".claude/nested/settings.local.json"), "{}").unwrap();
+    fs::write(wt.join("debug.log"), "").unwrap();
+    fs::create_dir_all(wt.join("node_modules/pkg")).unwrap();
+    fs::write(wt.join("node_modules/pkg/.env"), "").unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "--auto"])
+        .assert()
+        .success();
+
+    let manifest = repo.manifest();
+    assert!(manifest.contains(&manifest_record("copy", ".env")));
+    assert!(manifest.contains(&manifest_record(
+        "symlink",
+        ".claude/nested/settings.local.json"
+    )));
+    assert!(!manifest.contains("debug.log"));
+    assert!(!manifest.contains("node_modules"));
+
+    // 2回目は追跡済みなので何もしない
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "--auto"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No untracked ignored files"));
+}
+
 #[test]
 fn track_invalid_strategy_fails() {
     let repo = TestRepo::new();
@@ -77,6 +147,68 @@ fn track_nonexistent_file_fails() {
         .failure();
 }
 
+#[test]
+fn track_records_mode_and_new_worktree_keeps_it() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+
+    fs::write(wt.join(".env"), "SECRET=1").unwrap();
+    fs::set_permissions(wt.join(".env"), fs::Permissions::from_mode(0o600)).unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "copy", ".env"])
+        .assert()
+        .success();
+
+    let manifest = repo.manifest();
+    assert!(manifest.contains(&format!(
+        "{}mode = \"0600\"\n",
+        manifest_record("copy", ".env")
+    )));
+
+    repo.ws_cmd().args(["new", "feat"]).assert().success();
+    let mode = fs::metadata(repo.path().join("feat/.env"))
+        .unwrap()
+        .permissions()
+        .mode();
+    assert_eq!(mode & 0o7777, 0o600);
+}
+
+#[test]
+fn push_read_only_file_twice() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    let file = wt.join(".env");
+    let write_read_only = |content: &str| {
+        if file.exists() {
+            fs::set_permissions(&file, fs::Permissions::from_mode(0o600)).unwrap();
+        }
+        fs::write(&file, content).unwrap();
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o400)).unwrap();
+    };
+
+    write_read_only("A=1");
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "copy", ".env"])
+        .assert()
+        .success();
+
+    // store のコピーも 0400 になるが、push は作り直すので何度でも上書きできる
+    for content in ["A=2", "A=3"] {
+        write_read_only(content);
+        repo.ws_cmd_in("main")
+            .args(["store", "push", ".env"])
+            .assert()
+            .success();
+        let store_file = repo.store_dir().join(".env");
+        assert_eq!(fs::read_to_string(&store_file).unwrap(), content);
+        assert_eq!(
+            fs::metadata(&store_file).unwrap().permissions().mode() & 0o7777,
+            0o400
+        );
+    }
+}
+
 // ---- ws store status ----
 
 #[test]
@@ -102,6 +234,66 @@ fn status_shows_entries() {
         );
 }
 
+#[test]
+fn track_in_subdirectory_records_subproject_entry() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    fs::create_dir_all(wt.join("apps/web")).unwrap();
+    fs::write(wt.join("apps/web/.env"), "PORT=3000\n").unwrap();
+    fs::write(wt.join(".envrc"), "use flake").unwrap();
+
+    repo.ws_cmd_in("main/apps/web")
+        .args(["store", "track", "-s", "copy", ".env"])
+        .assert()
+        .success();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "copy", ".envrc"])
+        .assert()
+        .success();
+
+    let manifest = repo.manifest();
+    assert!(
+        manifest.contains("path = \".env\"\nproject = \"apps/web\"\n"),
+        "{}",
+        manifest
+    );
+    assert!(repo.store_dir().join("apps/web/.env").is_file());
+
+    // サブプロジェクトごとに分けて表示する
+    let output = repo
+        .ws_cmd_in("main")
+        .args(["store", "status"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let root = stdout.find("Repository root").expect(&stdout);
+    let web = stdout.find("apps/web/").expect(&stdout);
+    assert!(root < web, "{}", stdout);
+    assert!(stdout[root..web].contains(".envrc"), "{}", stdout);
+    assert!(stdout[web..].contains(".env"), "{}", stdout);
+}
+
+#[test]
+fn status_reports_mode_diff() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+
+    fs::write(wt.join(".env"), "SECRET=1").unwrap();
+    fs::set_permissions(wt.join(".env"), fs::Permissions::from_mode(0o600)).unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "copy", ".env"])
+        .assert()
+        .success();
+
+    fs::set_permissions(wt.join(".env"), fs::Permissions::from_mode(0o644)).unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "status"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("MODE_DIFF"));
+}
+
 #[test]
 fn status_without_store_fails() {
     let repo = TestRepo::new();
@@ -260,7 +452,7 @@ fn untrack_removes_and_restores() {
         .success();
 
     // manifest からエントリが消えている
-    let manifest = fs::read_to_string(repo.store_dir().join("manifest")).unwrap();
+    let manifest = repo.manifest();
     assert!(!manifest.contains(".envrc"));
 
     // store 内のマスターコピーが削除されている
@@ -272,6 +464,215 @@ fn untrack_removes_and_restores() {
     assert!(!meta.file_type().is_symlink());
 }
 
+#[test]
+fn untrack_replaces_symlinks_in_all_worktrees() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+
+    fs::write(wt.join(".envrc"), "use flake").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "symlink", ".envrc"])
+        .assert()
+        .success();
+
+    // 2つ目の worktree にも symlink が配布される
+    repo.ws_cmd().args(["new", "feat"]).assert().success();
+    let feat = repo.path().join("feat");
+    assert!(
+        feat.join(".envrc")
+            .symlink_metadata()
+            .unwrap()
+            .file_type()
+            .is_symlink()
+    );
+
+    repo.ws_cmd_in("main")
+        .args(["store", "untrack", "--restore", ".envrc"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("WORKTREE").and(predicate::str::contains("RESTORED")));
+
+    for dir in [&wt, &feat] {
+        let meta = dir.join(".envrc").symlink_metadata().unwrap();
+        assert!(!meta.file_type().is_symlink());
+        assert_eq!(fs::read_to_string(dir.join(".envrc")).unwrap(), "use flake");
+    }
+}
+
+#[test]
+fn untrack_purge_removes_file_from_all_worktrees() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+
+    fs::write(wt.join(".mcp.json"), "{}").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "copy", ".mcp.json"])
+        .assert()
+        .success();
+    repo.ws_cmd().args(["new", "feat"]).assert().success();
+    let feat = repo.path().join("feat");
+    assert!(feat.join(".mcp.json").is_file());
+
+    repo.ws_cmd_in("main")
+        .args(["store", "untrack", "--purge", ".mcp.json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("REMOVED"));
+
+    assert!(!wt.join(".mcp.json").exists());
+    assert!(!feat.join(".mcp.json").exists());
+    assert!(!repo.store_dir().join(".mcp.json").exists());
+}
+
+#[test]
+fn untrack_keeps_store_when_a_worktree_fails() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+
+    fs::create_dir_all(wt.join("conf")).unwrap();
+    fs::write(wt.join("conf/app.env"), "A=1").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "symlink", "conf/app.env"])
+        .assert()
+        .success();
+    repo.ws_cmd().args(["new", "feat"]).assert().success();
+
+    // feat の conf を worktree の外を指す symlink にすると、そこは更新できない
+    let feat = repo.path().join("feat");
+    let outside = repo.path().join("outside");
+    fs::create_dir_all(&outside).unwrap();
+    fs::remove_dir_all(feat.join("conf")).unwrap();
+    std::os::unix::fs::symlink(&outside, feat.join("conf")).unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "untrack", "conf/app.env"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("FAILED").and(predicate::str::contains("SKIPPED")));
+
+    assert!(
+        repo.manifest()
+            .contains(&manifest_record("symlink", "conf/app.env"))
+    );
+    assert_eq!(
+        fs::read_to_string(repo.store_dir().join("conf/app.env")).unwrap(),
+        "A=1"
+    );
+    // 失敗する worktree が見つかった時点で、どの worktree も変えない
+    assert!(wt.join("conf/app.env").is_symlink());
+}
+
+#[test]
+fn untrack_rolls_back_worktrees_when_a_later_one_fails() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+
+    fs::write(wt.join(".envrc"), "use flake").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "symlink", ".envrc"])
+        .assert()
+        .success();
+    repo.ws_cmd()
+        .args(["new", "rel", "--branch", "release/1.0"])
+        .assert()
+        .success();
+    repo.ws_cmd_in("rel")
+        .args(["store", "variant", "add", "release/*", ".envrc"])
+        .assert()
+        .success();
+
+    // variant のマスターコピーをコピーできないもの（socket）にして、rel での復元を失敗させる
+    let variant_file = repo.store_dir().join(".variants/release%2F*/.envrc");
+    fs::remove_file(&variant_file).unwrap();
+    let _socket = std::os::unix::net::UnixListener::bind(&variant_file).unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "untrack", "--restore", ".envrc"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("FAILED"));
+
+    let rel = repo.path().join("rel");
+    assert_eq!(
+        fs::read_link(wt.join(".envrc")).unwrap(),
+        repo.store_dir().join(".envrc")
+    );
+    assert_eq!(fs::read_link(rel.join(".envrc")).unwrap(), variant_file);
+    assert!(
+        repo.manifest()
+            .contains(&manifest_record("symlink", ".envrc"))
+    );
+    // 退避したファイルは残らない
+    let leftovers = |dir: &std::path::Path| {
+        fs::read_dir(dir)
+            .unwrap()
+            .filter(|e| {
+                e.as_ref()
+                    .unwrap()
+                    .file_name()
+                    .to_string_lossy()
+                    .contains("ws-untrack")
+            })
+            .count()
+    };
+    assert_eq!(leftovers(&wt), 0);
+    assert_eq!(leftovers(&rel), 0);
+}
+
+#[test]
+fn untrack_restore_and_purge_conflict() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+
+    fs::write(wt.join(".envrc"), "use flake").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "symlink", ".envrc"])
+        .assert()
+        .success();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "untrack", "--restore", "--purge", ".envrc"])
+        .assert()
+        .failure();
+
+    // 失敗時は manifest が変更されない
+    let manifest = repo.manifest();
+    assert!(manifest.contains(&manifest_record("symlink", ".envrc")));
+}
+
+#[test]
+fn copy_directory_above_link_threshold_is_not_hardlinked() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    fs::write(&repo.config_path, "[store]\nlink_threshold = \"1KB\"\n").unwrap();
+
+    fs::create_dir_all(wt.join("data")).unwrap();
+    fs::write(wt.join("data/conf.txt"), "x".repeat(4096)).unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "copy", "data"])
+        .assert()
+        .success();
+    repo.ws_cmd().args(["new", "feat"]).assert().success();
+
+    let feat = repo.path().join("feat");
+    let inode = |p: &std::path::Path| fs::metadata(p).unwrap().ino();
+    let main_file = wt.join("data/conf.txt");
+    let store_file = repo.store_dir().join("data/conf.txt");
+    let feat_file = feat.join("data/conf.txt");
+    assert_ne!(inode(&main_file), inode(&store_file));
+    assert_ne!(inode(&feat_file), inode(&store_file));
+    assert_ne!(inode(&main_file), inode(&feat_file));
+
+    // main での編集は feat にも store にも届かない
+    fs::write(&main_file, "edited").unwrap();
+    assert_eq!(fs::read_to_string(&feat_file).unwrap(), "x".repeat(4096));
+    assert_eq!(fs::read_to_string(&store_file).unwrap(), "x".repeat(4096));
+    repo.ws_cmd_in("feat")
+        .args(["store", "verify"])
+        .assert()
+        .success();
+}
+
 // ---- directory: ws store track ----
 
 #[test]
@@ -290,8 +691,8 @@ fn track_symlink_directory() {
         .success();
 
     // manifest に登録されている
-    let manifest = fs::read_to_string(repo.store_dir().join("manifest")).unwrap();
-    assert!(manifest.contains("symlink:nix/secrets"));
+    let manifest = repo.manifest();
+    assert!(manifest.contains(&manifest_record("symlink", "nix/secrets")));
 
     // store にマスターコピーが存在（ディレクトリとして）
     assert!(repo.store_dir().join("nix/secrets").is_dir());
@@ -322,8 +723,8 @@ fn track_copy_directory() {
         .success();
 
     // manifest に登録されている
-    let manifest = fs::read_to_string(repo.store_dir().join("manifest")).unwrap();
-    assert!(manifest.contains("copy:config/sub"));
+    let manifest = repo.manifest();
+    assert!(manifest.contains(&manifest_record("copy", "config/sub")));
 
     // store にコピーが存在
     assert!(repo.store_dir().join("config/sub").is_dir());
@@ -334,6 +735,104 @@ fn track_copy_directory() {
     assert!(!meta.file_type().is_symlink());
 }
 
+#[test]
+fn track_directory_with_exclude_keeps_local_files_out_of_store() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    fs::create_dir_all(wt.join(".idea/caches")).unwrap();
+    fs::write(wt.join(".idea/modules.xml"), "<modules/>").unwrap();
+    fs::write(wt.join(".idea/workspace.xml"), "<main/>").unwrap();
+    fs::write(wt.join(".idea/caches/blob"), "x").unwrap();
+
+    repo.ws_cmd_in("main")
+        .args([
+            "store",
+            "track",
+            "-s",
+            "copy",
+            ".idea",
+            "--exclude",
+            "workspace.xml",
+            "--exclude",
+            "caches/",
+        ])
+        .assert()
+        .success();
+
+    let manifest = repo.manifest();
+    assert!(
+        manifest.contains("exclude = [\"workspace.xml\", \"caches/\"]"),
+        "{}",
+        manifest
+    );
+    let stored = repo.store_dir().join(".idea");
+    assert!(stored.join("modules.xml").is_file());
+    assert!(!stored.join("workspace.xml").exists());
+    assert!(!stored.join("caches").exists());
+
+    // 除外したファイルの変更は MODIFIED にならない
+    fs::write(wt.join(".idea/workspace.xml"), "<changed/>").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "status"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("OK"))
+        .stdout(predicate::str::contains("MODIFIED").not());
+
+    // push しても除外したファイルは store に入らない
+    fs::write(wt.join(".idea/modules.xml"), "<modules v=\"2\"/>").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "push", ".idea"])
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(stored.join("modules.xml")).unwrap(),
+        "<modules v=\"2\"/>"
+    );
+    assert!(!stored.join("workspace.xml").exists());
+
+    // pull -f は共有するファイルだけを置き換え、ローカルの workspace.xml は残す
+    fs::write(wt.join(".idea/modules.xml"), "<local/>").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "pull", "-f", ".idea"])
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(wt.join(".idea/modules.xml")).unwrap(),
+        "<modules v=\"2\"/>"
+    );
+    assert_eq!(
+        fs::read_to_string(wt.join(".idea/workspace.xml")).unwrap(),
+        "<changed/>"
+    );
+    assert!(wt.join(".idea/caches/blob").is_file());
+
+    // 新しい worktree には共有するファイルだけを配置する
+    repo.ws_cmd().args(["new", "feat"]).assert().success();
+    let feat = repo.path().join("feat/.idea");
+    assert!(feat.join("modules.xml").is_file());
+    assert!(!feat.join("workspace.xml").exists());
+}
+
+#[test]
+fn track_rejects_filter_for_file_or_symlink_strategy() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    fs::write(wt.join(".env"), "A=1\n").unwrap();
+    fs::create_dir_all(wt.join(".idea")).unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "copy", ".env", "--exclude", "x"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("require a directory"));
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "symlink", ".idea", "--exclude", "x"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot have include/exclude"));
+}
+
 // ---- directory: ws store push ----
 
 #[test]
@@ -455,7 +954,7 @@ fn untrack_directory() {
         .success();
 
     // manifest からエントリが消えている
-    let manifest = fs::read_to_string(repo.store_dir().join("manifest")).unwrap();
+    let manifest = repo.manifest();
     assert!(!manifest.contains("secrets"));
 
     // store 内のマスターコピーが削除されている
@@ -470,3 +969,1780 @@ fn untrack_directory() {
         "secret"
     );
 }
+
+// ---- dotenv strategy ----
+
+#[test]
+fn push_dotenv_updates_only_changed_keys() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+
+    fs::write(wt.join(".env"), "# shared\nA=1\nB=2\n").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "dotenv", ".env"])
+        .assert()
+        .success();
+
+    // store 側にだけあるキーは push で消えない
+    repo.add_store_file(".env", "# shared\nA=1\nB=2\nSTORE_ONLY=x\n");
+    fs::write(wt.join(".env"), "A=1\nB=20\nC=3\n").unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "push", ".env"])
+        .assert()
+        .success();
+
+    assert_eq!(
+        fs::read_to_string(repo.store_dir().join(".env")).unwrap(),
+        "# shared\nA=1\nB=20\nSTORE_ONLY=x\nC=3\n"
+    );
+}
+
+#[test]
+fn push_dotenv_keeps_multiline_values_and_skips_noop() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+
+    let content = "KEY=\"-----BEGIN-----\nabc\n-----END-----\"\nA=1\n";
+    fs::write(wt.join(".env"), content).unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "dotenv", ".env"])
+        .assert()
+        .success();
+
+    // 何も変わっていなければ store も操作ログも変えない
+    repo.ws_cmd_in("main")
+        .args(["store", "push", ".env"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("(0 keys updated)"));
+    let log = fs::read_to_string(repo.store_dir().join("oplog.jsonl")).unwrap();
+    assert!(!log.contains("\"action\":\"push\""), "{}", log);
+
+    fs::write(
+        wt.join(".env"),
+        "KEY=\"-----BEGIN-----\nxyz\n-----END-----\"\nA=1\n",
+    )
+    .unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "push", ".env"])
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(repo.store_dir().join(".env")).unwrap(),
+        "KEY=\"-----BEGIN-----\nxyz\n-----END-----\"\nA=1\n"
+    );
+}
+
+#[test]
+fn pull_dotenv_adds_new_keys_without_clobbering() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+
+    fs::write(wt.join(".env"), "A=1\n").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "dotenv", ".env"])
+        .assert()
+        .success();
+
+    repo.add_store_file(".env", "A=1\nNEW=2\n");
+    fs::write(wt.join(".env"), "A=local\n").unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "pull"])
+        .assert()
+        .success();
+
+    assert_eq!(
+        fs::read_to_string(wt.join(".env")).unwrap(),
+        "A=local\nNEW=2\n"
+    );
+}
+
+#[test]
+fn diff_dotenv_masks_values_by_default() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+
+    fs::write(wt.join(".env"), "A=1\nB=2\n").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "dotenv", ".env"])
+        .assert()
+        .success();
+
+    fs::write(wt.join(".env"), "A=secret\nC=3\n").unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "diff"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("+ C=****")
+                .and(predicate::str::contains("- B=****"))
+                .and(predicate::str::contains("~ A=**** -> ****"))
+                .and(predicate::str::contains("secret").not()),
+        );
+
+    repo.ws_cmd_in("main")
+        .args(["store", "diff", "--show-values", ".env"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("~ A=1 -> secret"));
+}
+
+#[test]
+fn track_dotenv_directory_fails() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    fs::create_dir_all(wt.join("envs")).unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "dotenv", "envs"])
+        .assert()
+        .failure();
+}
+
+// ---- hardlink / clone strategy ----
+
+fn inode(path: &std::path::Path) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(path).unwrap().ino()
+}
+
+#[test]
+fn track_hardlink_shares_inode_with_store() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+
+    fs::write(wt.join(".env"), "A=1\n").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "hardlink", ".env"])
+        .assert()
+        .success();
+
+    let manifest = repo.manifest();
+    assert!(manifest.contains(&manifest_record("hardlink", ".env")));
+    assert_eq!(
+        inode(&wt.join(".env")),
+        inode(&repo.store_dir().join(".env"))
+    );
+
+    repo.ws_cmd_in("main")
+        .args(["store", "status"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("OK"));
+}
+
+#[test]
+fn push_hardlink_relinks_replaced_file() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+
+    fs::write(wt.join(".env"), "A=1\n").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "hardlink", ".env"])
+        .assert()
+        .success();
+
+    // エディタの保存などで別ファイルに置き換わるとリンクが切れる
+    fs::remove_file(wt.join(".env")).unwrap();
+    fs::write(wt.join(".env"), "A=2\n").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "status"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("NOT_LINK"));
+
+    repo.ws_cmd_in("main")
+        .args(["store", "push", ".env"])
+        .assert()
+        .success();
+
+    assert_eq!(
+        fs::read_to_string(repo.store_dir().join(".env")).unwrap(),
+        "A=2\n"
+    );
+    assert_eq!(
+        inode(&wt.join(".env")),
+        inode(&repo.store_dir().join(".env"))
+    );
+}
+
+#[test]
+fn pull_clone_creates_independent_copy() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+
+    fs::write(wt.join("data.db"), "original").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "clone", "data.db"])
+        .assert()
+        .success();
+
+    fs::remove_file(wt.join("data.db")).unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "pull", "data.db"])
+        .assert()
+        .success();
+
+    assert_ne!(
+        inode(&wt.join("data.db")),
+        inode(&repo.store_dir().join("data.db"))
+    );
+    fs::write(wt.join("data.db"), "changed").unwrap();
+    assert_eq!(
+        fs::read_to_string(repo.store_dir().join("data.db")).unwrap(),
+        "original"
+    );
+}
+
+// ---- command strategy ----
+
+#[test]
+fn track_command_generates_file_and_caches_output() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+
+    repo.ws_cmd_in("main")
+        .args([
+            "store",
+            "track",
+            "-s",
+            "command",
+            "--command",
+            "printf 'TOKEN=%s\\n' \"$WS_FILE\"",
+            ".env.local",
+        ])
+        .assert()
+        .success();
+
+    let manifest = repo.manifest();
+    assert!(manifest.contains(&format!(
+        "{}command = ",
+        manifest_record("command", ".env.local")
+    )));
+    assert!(manifest.contains("printf"));
+    assert_eq!(
+        fs::read_to_string(wt.join(".env.local")).unwrap(),
+        "TOKEN=.env.local\n"
+    );
+    assert_eq!(
+        fs::read_to_string(repo.store_dir().join(".env.local")).unwrap(),
+        "TOKEN=.env.local\n"
+    );
+}
+
+#[test]
+fn track_command_requires_command() {
+    let repo = TestRepo::new();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "command", ".env.local"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--command"));
+
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "copy", "--command", "true", ".env"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--command"));
+}
+
+#[test]
+fn apply_regenerates_command_output() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    let counter = repo.path().join("counter");
+    fs::write(&counter, "1").unwrap();
+
+    let command = format!("cat {}", counter.display());
+    repo.ws_cmd_in("main")
+        .args([
+            "store",
+            "track",
+            "-s",
+            "command",
+            "--command",
+            &command,
+            "gen.txt",
+        ])
+        .assert()
+        .success();
+
+    // 手で編集すると前回の出力と比べて MODIFIED になる
+    fs::write(wt.join("gen.txt"), "edited").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "status"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("MODIFIED"));
+
+    fs::write(&counter, "2").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "apply"])
+        .assert()
+        .success();
+
+    assert_eq!(fs::read_to_string(wt.join("gen.txt")).unwrap(), "2");
+    assert_eq!(
+        fs::read_to_string(repo.store_dir().join("gen.txt")).unwrap(),
+        "2"
+    );
+    repo.ws_cmd_in("main")
+        .args(["store", "status"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("MODIFIED").not());
+}
+
+#[test]
+fn apply_places_missing_files_without_overwriting() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    repo.init_store();
+    repo.add_manifest_entry("copy", ".mcp.json");
+    repo.add_store_file(".mcp.json", "store");
+    repo.add_manifest_entry("copy", ".env");
+    repo.add_store_file(".env", "store");
+    fs::write(wt.join(".env"), "local").unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "apply"])
+        .assert()
+        .success();
+
+    assert_eq!(fs::read_to_string(wt.join(".mcp.json")).unwrap(), "store");
+    assert_eq!(fs::read_to_string(wt.join(".env")).unwrap(), "local");
+}
+
+// ---- link-tree strategy ----
+
+#[test]
+fn link_tree_links_each_file_and_keeps_new_files_local() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    fs::create_dir_all(wt.join(".vscode")).unwrap();
+    fs::write(wt.join(".vscode/settings.json"), "{}").unwrap();
+    fs::write(wt.join(".vscode/launch.json"), "[]").unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "link-tree", ".vscode"])
+        .assert()
+        .success();
+
+    let stored = repo.store_dir().join(".vscode");
+    assert!(!wt.join(".vscode").is_symlink());
+    assert_eq!(
+        fs::read_link(wt.join(".vscode/settings.json")).unwrap(),
+        stored.join("settings.json")
+    );
+
+    // 新しいファイルは store に入らず、EXTRA として表示する
+    fs::write(wt.join(".vscode/tasks.json"), "local").unwrap();
+    assert!(!stored.join("tasks.json").exists());
+    fs::remove_file(wt.join(".vscode/launch.json")).unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "status"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("MISSING"))
+        .stdout(predicate::str::contains("launch.json"))
+        .stdout(predicate::str::contains("tasks.json"))
+        .stdout(predicate::str::contains("EXTRA"));
+
+    // pull -f は store のファイルだけを張り直す
+    repo.ws_cmd_in("main")
+        .args(["store", "pull", "-f", ".vscode"])
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_link(wt.join(".vscode/launch.json")).unwrap(),
+        stored.join("launch.json")
+    );
+    assert_eq!(
+        fs::read_to_string(wt.join(".vscode/tasks.json")).unwrap(),
+        "local"
+    );
+
+    repo.ws_cmd().args(["new", "feat"]).assert().success();
+    let feat = repo.path().join("feat/.vscode");
+    assert!(!feat.is_symlink());
+    assert_eq!(
+        fs::read_link(feat.join("settings.json")).unwrap(),
+        stored.join("settings.json")
+    );
+
+    // 別の strategy にすると、symlink をコピーに戻してローカルのファイルは残す
+    repo.ws_cmd_in("main")
+        .args(["store", "set-strategy", ".vscode", "copy"])
+        .assert()
+        .success();
+    assert!(!wt.join(".vscode/settings.json").is_symlink());
+    assert_eq!(
+        fs::read_to_string(wt.join(".vscode/settings.json")).unwrap(),
+        "{}"
+    );
+    assert!(wt.join(".vscode/tasks.json").is_file());
+}
+
+#[test]
+fn untrack_link_tree_replaces_or_removes_each_link() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    fs::create_dir_all(wt.join(".vscode/sub")).unwrap();
+    fs::write(wt.join(".vscode/settings.json"), "{}").unwrap();
+    fs::write(wt.join(".vscode/sub/launch.json"), "[]").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "link-tree", ".vscode"])
+        .assert()
+        .success();
+    repo.ws_cmd().args(["new", "feat"]).assert().success();
+    let feat = repo.path().join("feat");
+    for root in [&wt, &feat] {
+        fs::write(root.join(".vscode/tasks.json"), "local").unwrap();
+    }
+
+    repo.ws_cmd_in("main")
+        .args(["store", "untrack", ".vscode"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("RESTORED"));
+    assert!(!repo.store_dir().join(".vscode").exists());
+    for root in [&wt, &feat] {
+        for (file, content) in [("settings.json", "{}"), ("sub/launch.json", "[]")] {
+            let path = root.join(".vscode").join(file);
+            assert!(!path.is_symlink(), "{}", path.display());
+            assert_eq!(fs::read_to_string(&path).unwrap(), content);
+        }
+        assert_eq!(
+            fs::read_to_string(root.join(".vscode/tasks.json")).unwrap(),
+            "local"
+        );
+    }
+
+    // --purge は store へのリンクだけを消し、worktree 固有のファイルは残す
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "link-tree", ".vscode"])
+        .assert()
+        .success();
+    fs::write(wt.join(".vscode/notes.txt"), "local").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "untrack", "--purge", ".vscode"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("REMOVED"));
+    assert!(!wt.join(".vscode/settings.json").exists());
+    assert!(!wt.join(".vscode/tasks.json").exists());
+    assert!(!wt.join(".vscode/sub").exists());
+    assert_eq!(
+        fs::read_to_string(wt.join(".vscode/notes.txt")).unwrap(),
+        "local"
+    );
+}
+
+#[test]
+fn mv_relinks_link_tree_files() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    fs::create_dir_all(wt.join(".vscode/sub")).unwrap();
+    fs::write(wt.join(".vscode/settings.json"), "{}").unwrap();
+    fs::write(wt.join(".vscode/sub/launch.json"), "[]").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "link-tree", ".vscode"])
+        .assert()
+        .success();
+    repo.ws_cmd().args(["new", "feat"]).assert().success();
+    let feat = repo.path().join("feat");
+    fs::write(feat.join(".vscode/tasks.json"), "local").unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "mv", ".vscode", "editor/.vscode"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("RELINKED"));
+
+    let new_store = repo.store_dir().join("editor/.vscode");
+    for root in [&wt, &feat] {
+        assert!(!root.join(".vscode").exists());
+        for file in ["settings.json", "sub/launch.json"] {
+            assert_eq!(
+                fs::read_link(root.join("editor/.vscode").join(file)).unwrap(),
+                new_store.join(file)
+            );
+        }
+    }
+    assert_eq!(
+        fs::read_to_string(feat.join("editor/.vscode/tasks.json")).unwrap(),
+        "local"
+    );
+    repo.ws_cmd_in("feat")
+        .args(["store", "status"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("WRONG").not());
+}
+
+#[test]
+fn link_tree_requires_directory() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    fs::write(wt.join(".env"), "A=1\n").unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "link-tree", ".env"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("requires a directory"));
+}
+
+// ---- merge-json strategy ----
+
+#[test]
+fn pull_merge_json_keeps_worktree_overlay() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    let file = ".vscode/settings.json";
+
+    fs::create_dir_all(wt.join(".vscode")).unwrap();
+    fs::write(
+        wt.join(file),
+        r#"{"editor.tabSize": 2, "files.exclude": {"dist": true}}"#,
+    )
+    .unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "merge-json", file])
+        .assert()
+        .success();
+
+    // worktree 側で一部の設定を変える
+    fs::write(
+        wt.join(file),
+        r#"{"editor.tabSize": 4, "files.exclude": {"dist": true}, "local": 1}"#,
+    )
+    .unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "status"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("BASE_CHANGED").not());
+
+    // store の base が更新されると BASE_CHANGED になる
+    repo.add_store_file(
+        file,
+        r#"{"editor.tabSize": 2, "files.exclude": {"dist": true, "out": true}}"#,
+    );
+    repo.ws_cmd_in("main")
+        .args(["store", "status"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("BASE_CHANGED"));
+
+    repo.ws_cmd_in("main")
+        .args(["store", "pull"])
+        .assert()
+        .success();
+
+    let merged: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(wt.join(file)).unwrap()).unwrap();
+    assert_eq!(
+        merged,
+        serde_json::json!({
+            "editor.tabSize": 4,
+            "files.exclude": {"dist": true, "out": true},
+            "local": 1
+        })
+    );
+    repo.ws_cmd_in("main")
+        .args(["store", "status"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("BASE_CHANGED").not());
+}
+
+#[test]
+fn push_merge_json_keeps_overlay_keys_out_of_store() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    let file = "settings.json";
+
+    fs::write(wt.join(file), r#"{"tabSize": 2, "theme": "dark"}"#).unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "merge-json", file])
+        .assert()
+        .success();
+
+    // base のキーを変え、worktree 固有のキーを足す
+    fs::write(
+        wt.join(file),
+        r#"{"tabSize": 4, "theme": "dark", "local": true}"#,
+    )
+    .unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "push", file])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 keys updated"));
+    let stored = || -> serde_json::Value {
+        serde_json::from_str(&fs::read_to_string(repo.store_dir().join(file)).unwrap()).unwrap()
+    };
+    assert_eq!(stored(), serde_json::json!({"tabSize": 4, "theme": "dark"}));
+
+    // 新しい worktree には overlay が届かない
+    repo.ws_cmd().args(["new", "feat"]).assert().success();
+    let feat: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(repo.path().join("feat").join(file)).unwrap())
+            .unwrap();
+    assert_eq!(feat, serde_json::json!({"tabSize": 4, "theme": "dark"}));
+
+    // 変更が overlay だけなら store は変わらない
+    repo.ws_cmd_in("main")
+        .args(["store", "push", file])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("0 keys updated"));
+
+    repo.ws_cmd_in("main")
+        .args(["store", "push", file, "--include-overlay"])
+        .assert()
+        .success();
+    assert_eq!(
+        stored(),
+        serde_json::json!({"tabSize": 4, "theme": "dark", "local": true})
+    );
+}
+
+#[test]
+fn track_merge_json_rejects_invalid_json() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+
+    fs::write(wt.join("settings.json"), "[1, 2]").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "merge-json", "settings.json"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("not a JSON object"));
+}
+
+// ---- ws store mv / set-strategy ----
+
+#[test]
+fn mv_relinks_symlinks_in_every_worktree() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+
+    fs::write(wt.join(".envrc"), "use flake").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "symlink", ".envrc"])
+        .assert()
+        .success();
+    repo.ws_cmd().args(["new", "feat"]).assert().success();
+    let feat = repo.path().join("feat");
+
+    repo.ws_cmd_in("main")
+        .args(["store", "mv", ".envrc", "nix/.envrc"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("RELINKED"));
+
+    let manifest = repo.manifest();
+    assert!(manifest.contains(&manifest_record("symlink", "nix/.envrc")));
+    assert!(!manifest.contains(&manifest_record("symlink", ".envrc")));
+    assert!(!repo.store_dir().join(".envrc").exists());
+    let new_store = repo.store_dir().join("nix/.envrc");
+    assert_eq!(fs::read_to_string(&new_store).unwrap(), "use flake");
+
+    for root in [&wt, &feat] {
+        assert!(root.join(".envrc").symlink_metadata().is_err());
+        assert_eq!(fs::read_link(root.join("nix/.envrc")).unwrap(), new_store);
+    }
+}
+
+#[test]
+fn mv_moves_copies_and_rejects_tracked_target() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+
+    fs::write(wt.join(".env"), "A=1\n").unwrap();
+    fs::write(wt.join(".env.local"), "B=1\n").unwrap();
+    for file in [".env", ".env.local"] {
+        repo.ws_cmd_in("main")
+            .args(["store", "track", "-s", "copy", file])
+            .assert()
+            .success();
+    }
+
+    repo.ws_cmd_in("main")
+        .args(["store", "mv", ".env", ".env.local"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("already tracked"));
+
+    repo.ws_cmd_in("main")
+        .args(["store", "mv", ".env", "config/.env"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("MOVED"));
+    assert_eq!(fs::read_to_string(wt.join("config/.env")).unwrap(), "A=1\n");
+    assert!(!wt.join(".env").exists());
+    assert!(repo.store_dir().join("config/.env").is_file());
+}
+
+#[test]
+fn set_strategy_symlink_to_copy_materializes_files() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+
+    fs::write(wt.join(".envrc"), "use flake").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "symlink", ".envrc"])
+        .assert()
+        .success();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "set-strategy", ".envrc", "copy"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("CONVERTED"));
+
+    let meta = wt.join(".envrc").symlink_metadata().unwrap();
+    assert!(meta.file_type().is_file());
+    assert_eq!(fs::read_to_string(wt.join(".envrc")).unwrap(), "use flake");
+    let manifest = repo.manifest();
+    assert!(manifest.contains(&manifest_record("copy", ".envrc")));
+}
+
+#[test]
+fn set_strategy_to_symlink_keeps_local_changes_without_force() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+
+    fs::write(wt.join(".mcp.json"), "{}").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "copy", ".mcp.json"])
+        .assert()
+        .success();
+    fs::write(wt.join(".mcp.json"), r#"{"local":true}"#).unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "set-strategy", ".mcp.json", "symlink"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("SKIPPED(modified)"));
+    assert!(
+        !wt.join(".mcp.json")
+            .symlink_metadata()
+            .unwrap()
+            .file_type()
+            .is_symlink()
+    );
+
+    repo.ws_cmd_in("main")
+        .args(["store", "set-strategy", ".mcp.json", "symlink", "--force"])
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_link(wt.join(".mcp.json")).unwrap(),
+        repo.store_dir().join(".mcp.json")
+    );
+}
+
+#[test]
+fn set_strategy_copy_to_hardlink_links_worktree() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+
+    fs::write(wt.join(".env"), "A=1\n").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "copy", ".env"])
+        .assert()
+        .success();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "set-strategy", ".env", "hardlink"])
+        .assert()
+        .success();
+    assert_eq!(
+        inode(&wt.join(".env")),
+        inode(&repo.store_dir().join(".env"))
+    );
+
+    // hardlink から copy に戻すと store と実体を共有しなくなる
+    repo.ws_cmd_in("main")
+        .args(["store", "set-strategy", ".env", "copy"])
+        .assert()
+        .success();
+    assert_ne!(
+        inode(&wt.join(".env")),
+        inode(&repo.store_dir().join(".env"))
+    );
+}
+
+// ---- seed strategy ----
+
+#[test]
+fn seed_is_skipped_by_bulk_push_and_pull() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+
+    fs::write(wt.join("dev.db"), "seed").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "seed", "dev.db"])
+        .assert()
+        .success();
+
+    // worktree 側で変更しても MODIFIED にならない
+    fs::write(wt.join("dev.db"), "local changes").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "status"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("MODIFIED").not());
+
+    repo.ws_cmd_in("main")
+        .args(["store", "push"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No non-symlink files to push"));
+    assert_eq!(
+        fs::read_to_string(repo.store_dir().join("dev.db")).unwrap(),
+        "seed"
+    );
+
+    fs::remove_file(wt.join("dev.db")).unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "pull"])
+        .assert()
+        .success();
+    assert!(!wt.join("dev.db").exists());
+}
+
+#[test]
+fn seed_syncs_when_named_explicitly() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+
+    fs::write(wt.join("dev.db"), "seed").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "seed", "dev.db"])
+        .assert()
+        .success();
+
+    fs::write(wt.join("dev.db"), "new seed").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "push", "dev.db"])
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(repo.store_dir().join("dev.db")).unwrap(),
+        "new seed"
+    );
+
+    fs::remove_file(wt.join("dev.db")).unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "pull", "dev.db"])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(wt.join("dev.db")).unwrap(), "new seed");
+}
+
+#[test]
+fn suggest_lists_ignored_files_missing_in_other_worktrees() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    repo.ws_cmd().args(["new", "feat"]).assert().success();
+
+    fs::write(
+        repo.path().join("feat/.gitignore"),
+        ".env.local\nnode_modules/\n",
+    )
+    .unwrap();
+    fs::write(wt.join(".gitignore"), ".env.local\nnode_modules/\n").unwrap();
+    fs::write(wt.join(".env.local"), "A=1\n").unwrap();
+    fs::create_dir_all(wt.join("node_modules/pkg")).unwrap();
+    fs::write(wt.join("node_modules/pkg/index.js"), "").unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "suggest"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains(".env.local")
+                .and(predicate::str::contains("1/2"))
+                .and(predicate::str::contains("dotenv"))
+                .and(predicate::str::contains("node_modules").not()),
+        );
+}
+
+#[test]
+fn suggest_skips_tracked_files() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+
+    fs::write(wt.join(".gitignore"), ".env\n").unwrap();
+    fs::write(wt.join(".env"), "A=1\n").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "dotenv", ".env"])
+        .assert()
+        .success();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "suggest"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No candidate files found"));
+}
+
+#[test]
+fn dry_run_track_leaves_store_and_worktree_untouched() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    fs::write(wt.join(".envrc"), "export A=1\n").unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["--dry-run", "store", "track", "-s", "symlink", ".envrc"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("[dry-run] write")
+                .and(predicate::str::contains("[dry-run] ln -s")),
+        );
+
+    assert!(!repo.store_dir().exists());
+    assert!(!wt.join(".envrc").symlink_metadata().unwrap().is_symlink());
+}
+
+#[test]
+fn dry_run_untrack_purge_keeps_files() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    fs::write(wt.join(".env"), "A=1\n").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "copy", ".env"])
+        .assert()
+        .success();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "untrack", "--purge", ".env", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[dry-run] rm"));
+
+    assert!(wt.join(".env").exists());
+    assert!(repo.store_dir().join(".env").exists());
+    let manifest = repo.manifest();
+    assert!(manifest.contains(&manifest_record("copy", ".env")));
+}
+
+#[test]
+fn concurrent_track_keeps_every_entry() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    repo.init_store();
+
+    let files: Vec<String> = (0..8).map(|i| format!(".env.{i}")).collect();
+    for file in &files {
+        fs::write(wt.join(file), "A=1\n").unwrap();
+    }
+
+    let handles: Vec<_> = files
+        .iter()
+        .map(|file| {
+            let mut cmd = std::process::Command::new(assert_cmd::cargo::cargo_bin!("ws"));
+            cmd.args(["store", "track", "-s", "copy", file])
+                .stdout(std::process::Stdio::null())
+                .current_dir(&wt)
+                .env("LC_ALL", "en")
+                .env("WS_CONFIG_PATH", &repo.config_path);
+            cmd.spawn().unwrap()
+        })
+        .collect();
+    for mut handle in handles {
+        assert!(handle.wait().unwrap().success());
+    }
+
+    let manifest = repo.manifest();
+    for file in &files {
+        assert!(
+            manifest.contains(&manifest_record("copy", file)),
+            "{file} missing from manifest:\n{manifest}"
+        );
+    }
+}
+
+#[test]
+fn track_rejects_files_over_max_size() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    fs::write(&repo.config_path, "[store]\nmax_size = \"1KB\"\n").unwrap();
+    fs::write(wt.join("big.dat"), "x".repeat(2048)).unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "copy", "big.dat"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("store.max_size"));
+
+    assert!(!repo.store_dir().join("big.dat").exists());
+}
+
+#[test]
+fn track_warns_about_large_and_binary_files() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    fs::write(&repo.config_path, "[store]\nwarn_size = \"1KB\"\n").unwrap();
+    fs::create_dir_all(wt.join("data")).unwrap();
+    fs::write(wt.join("data/blob.bin"), [0u8; 2048]).unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "copy", "data"])
+        .assert()
+        .success()
+        .stderr(
+            predicate::str::contains("will be duplicated into every worktree").and(
+                predicate::str::contains("data/blob.bin looks like a binary file"),
+            ),
+        );
+}
+
+#[test]
+fn track_dotenv_rejects_binary() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    fs::write(wt.join(".env"), b"A=1\n\0").unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "dotenv", ".env"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn status_shows_store_size_summary() {
+    let repo = TestRepo::new();
+    repo.init_store();
+    repo.add_manifest_entry("copy", ".env");
+    repo.add_store_file(".env", "A=1\n");
+
+    repo.ws_cmd_in("main")
+        .args(["store", "status"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("SIZE").and(predicate::str::contains("Total: 1 entries, 4 B")),
+        );
+}
+
+// ---- ws store verify ----
+
+#[test]
+fn verify_detects_tampered_store_and_update_accepts_it() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    fs::write(wt.join(".env"), "A=1\n").unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "copy", ".env"])
+        .assert()
+        .success();
+    let manifest = repo.manifest();
+    assert!(manifest.contains("hash = \"sha256:"));
+
+    repo.ws_cmd_in("main")
+        .args(["store", "verify"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("OK"));
+
+    fs::write(repo.store_dir().join(".env"), "A=2\n").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "verify"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("CORRUPTED"));
+
+    repo.ws_cmd_in("main")
+        .args(["store", "verify", "--update"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("UPDATED"));
+    repo.ws_cmd_in("main")
+        .args(["store", "verify"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn verify_reports_entries_without_hash_as_unverified() {
+    let repo = TestRepo::new();
+    repo.init_store();
+    repo.add_manifest_entry("copy", ".env");
+    repo.add_store_file(".env", "A=1\n");
+
+    repo.ws_cmd_in("main")
+        .args(["store", "verify"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("UNVERIFIED"));
+}
+
+#[test]
+fn push_updates_hash_and_status_detects_modification() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    fs::write(wt.join(".env"), "A=1\n").unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "copy", ".env"])
+        .assert()
+        .success();
+
+    // 同じサイズで書き換えても MODIFIED になる
+    fs::write(wt.join(".env"), "A=2\n").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "status"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("MODIFIED"));
+
+    repo.ws_cmd_in("main")
+        .args(["store", "push", ".env"])
+        .assert()
+        .success();
+    repo.ws_cmd_in("main")
+        .args(["store", "status"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("OK"));
+    repo.ws_cmd_in("main")
+        .args(["store", "verify"])
+        .assert()
+        .success();
+}
+
+// ---- ws store profile ----
+
+#[test]
+fn profile_add_rm_and_list() {
+    let repo = TestRepo::new();
+    repo.init_store();
+    repo.add_manifest_entry("copy", ".env");
+    repo.add_manifest_entry("copy", ".mcp.json");
+    repo.add_store_file(".env", "A=1\n");
+    repo.add_store_file(".mcp.json", "{}");
+
+    repo.ws_cmd_in("main")
+        .args(["store", "profile", "add", "review", ".env", ".mcp.json"])
+        .assert()
+        .success();
+    repo.ws_cmd_in("main")
+        .args(["store", "profile", "add", "ci", ".env"])
+        .assert()
+        .success();
+    let manifest = repo.manifest();
+    assert!(manifest.contains(&format!(
+        "{}profiles = [\"review\", \"ci\"]\n",
+        manifest_record("copy", ".env")
+    )));
+
+    repo.ws_cmd_in("main")
+        .args(["store", "profile", "rm", "review", ".mcp.json"])
+        .assert()
+        .success();
+    repo.ws_cmd_in("main")
+        .args(["store", "profile", "list"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("review")
+                .and(predicate::str::contains("ci"))
+                .and(predicate::str::contains(".mcp.json").not()),
+        );
+}
+
+#[test]
+fn profile_add_rejects_untracked_file_and_invalid_name() {
+    let repo = TestRepo::new();
+    repo.init_store();
+    repo.add_manifest_entry("copy", ".env");
+
+    repo.ws_cmd_in("main")
+        .args(["store", "profile", "add", "review", ".missing"])
+        .assert()
+        .failure();
+    repo.ws_cmd_in("main")
+        .args(["store", "profile", "add", "a b", ".env"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn apply_profile_is_remembered_until_no_profile() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    repo.init_store();
+    repo.add_manifest_entry("copy", ".env");
+    repo.add_manifest_entry("copy", ".mcp.json");
+    repo.add_store_file(".env", "A=1\n");
+    repo.add_store_file(".mcp.json", "{}");
+    repo.ws_cmd_in("main")
+        .args(["store", "profile", "add", "review", ".env"])
+        .assert()
+        .success();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "apply", "--profile", "review"])
+        .assert()
+        .success();
+    assert!(wt.join(".env").is_file());
+    assert!(!wt.join(".mcp.json").exists());
+
+    // 記録した profile は次の apply でも使われる
+    repo.ws_cmd_in("main")
+        .args(["store", "apply"])
+        .assert()
+        .success();
+    assert!(!wt.join(".mcp.json").exists());
+
+    repo.ws_cmd_in("main")
+        .args(["store", "apply", "--no-profile"])
+        .assert()
+        .success();
+    assert!(wt.join(".mcp.json").is_file());
+    repo.ws_cmd_in("main")
+        .args(["store", "status"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Profile:").not());
+}
+
+// ---- ws store variant ----
+
+#[test]
+fn variant_is_applied_to_matching_branch_and_push_writes_back() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    fs::write(wt.join(".env"), "ENV=prod\n").unwrap();
+    repo.init_store();
+    repo.add_manifest_entry("copy", ".env");
+    repo.add_store_file(".env", "ENV=dev\n");
+
+    repo.ws_cmd_in("main")
+        .args(["store", "variant", "add", "release/*", ".env"])
+        .assert()
+        .success();
+    let manifest = repo.manifest();
+    assert!(manifest.contains("[[files.variants]]\nbranch = \"release/*\"\n"));
+    let variant_file = repo.store_dir().join(".variants/release%2F*/.env");
+    assert_eq!(fs::read_to_string(&variant_file).unwrap(), "ENV=prod\n");
+
+    repo.ws_cmd()
+        .args(["new", "rel", "--branch", "release/1.0"])
+        .assert()
+        .success();
+    repo.ws_cmd()
+        .args(["new", "feat", "--branch", "feature/x"])
+        .assert()
+        .success();
+    let rel = repo.path().join("rel");
+    assert_eq!(fs::read_to_string(rel.join(".env")).unwrap(), "ENV=prod\n");
+    assert_eq!(
+        fs::read_to_string(repo.path().join("feat").join(".env")).unwrap(),
+        "ENV=dev\n"
+    );
+
+    repo.ws_cmd_in("rel")
+        .args(["store", "status"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("VARIANT").and(predicate::str::contains("release/*")));
+
+    // push は適用した variant に書き戻し、既定の内容は変えない
+    fs::write(rel.join(".env"), "ENV=prod2\n").unwrap();
+    repo.ws_cmd_in("rel")
+        .args(["store", "push", ".env"])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&variant_file).unwrap(), "ENV=prod2\n");
+    assert_eq!(
+        fs::read_to_string(repo.store_dir().join(".env")).unwrap(),
+        "ENV=dev\n"
+    );
+    repo.ws_cmd_in("rel")
+        .args(["store", "verify"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(".env [release/*]"));
+}
+
+#[test]
+fn variant_rm_relinks_symlinks_to_default() {
+    let repo = TestRepo::new();
+    repo.ws_cmd()
+        .args(["new", "rel", "--branch", "release/1.0"])
+        .assert()
+        .success();
+    let rel = repo.path().join("rel");
+    fs::write(rel.join(".envrc"), "use flake").unwrap();
+    repo.ws_cmd_in("rel")
+        .args(["store", "track", "-s", "symlink", ".envrc"])
+        .assert()
+        .success();
+
+    repo.ws_cmd_in("rel")
+        .args(["store", "variant", "add", "release/*", ".envrc"])
+        .assert()
+        .success();
+    let variant_file = repo.store_dir().join(".variants/release%2F*/.envrc");
+    assert_eq!(fs::read_link(rel.join(".envrc")).unwrap(), variant_file);
+
+    repo.ws_cmd_in("rel")
+        .args(["store", "variant", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("release/*"));
+
+    repo.ws_cmd_in("rel")
+        .args(["store", "variant", "rm", "release/*", ".envrc"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("RELINKED"));
+    assert_eq!(
+        fs::read_link(rel.join(".envrc")).unwrap(),
+        repo.store_dir().join(".envrc")
+    );
+    assert!(!variant_file.exists());
+    assert!(!repo.store_dir().join(".variants").exists());
+}
+
+#[test]
+fn variant_add_rejects_command_strategy() {
+    let repo = TestRepo::new();
+    repo.init_store();
+    repo.add_manifest_entry("command", ".kube/config");
+    fs::create_dir_all(repo.main_worktree().join(".kube")).unwrap();
+    fs::write(repo.main_worktree().join(".kube/config"), "x").unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "variant", "add", "release/*", ".kube/config"])
+        .assert()
+        .failure();
+}
+
+// ---- ws store log ----
+
+#[test]
+fn log_records_store_operations_with_worktree_and_hashes() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    fs::write(wt.join(".env"), "A=1\n").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "copy", ".env"])
+        .assert()
+        .success();
+    fs::write(wt.join(".env"), "A=2\n").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "push", ".env"])
+        .assert()
+        .success();
+    repo.ws_cmd().args(["new", "feat"]).assert().success();
+    repo.ws_cmd_in("feat")
+        .args(["store", "pull", "-f", ".env"])
+        .assert()
+        .success();
+
+    let log = fs::read_to_string(repo.store_dir().join("oplog.jsonl")).unwrap();
+    let lines: Vec<&str> = log.lines().collect();
+    assert_eq!(lines.len(), 4, "{}", log);
+    assert!(lines[0].contains("\"action\":\"track\""));
+    assert!(lines[1].contains("\"action\":\"push\"") && lines[1].contains("\"before\":\"sha256:"));
+    // ws new で配置したファイルも記録する
+    assert!(lines[2].contains("\"action\":\"apply\"") && lines[2].contains("\"branch\":\"feat\""));
+    assert!(lines[3].contains("\"action\":\"pull\"") && lines[3].contains("\"branch\":\"feat\""));
+
+    // 既定では store の内容を変えた操作だけを新しい順に表示する
+    let output = repo
+        .ws_cmd_in("main")
+        .args(["store", "log"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("WORKTREE"));
+    assert!(!stdout.contains("pull"), "{}", stdout);
+    assert!(stdout.find("push").unwrap() < stdout.find("track").unwrap());
+
+    repo.ws_cmd_in("main")
+        .args(["store", "log", "--ops", "-n", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("pull").and(predicate::str::contains("feat")))
+        .stdout(predicate::str::contains("push").not());
+    repo.ws_cmd_in("main")
+        .args(["store", "log", "--ops", ".envrc"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No store operations recorded"));
+}
+
+#[test]
+fn log_records_entry_changes_recover_and_migrate() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    fs::write(wt.join(".env"), "A=1\n").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "copy", ".env"])
+        .assert()
+        .success();
+    repo.ws_cmd().args(["new", "feat"]).assert().success();
+    for args in [
+        vec!["store", "variant", "add", "feat", ".env"],
+        vec!["store", "variant", "rm", "feat", ".env"],
+        vec!["store", "set-strategy", ".env", "clone"],
+        vec!["store", "mv", ".env", ".env.local"],
+    ] {
+        repo.ws_cmd_in("feat").args(&args).assert().success();
+    }
+
+    // ws rm で退避したファイルを戻す
+    let feat = repo.path().join("feat");
+    fs::write(feat.join(".env.local"), "A=2\n").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["rm", "--force", feat.to_str().unwrap()])
+        .assert()
+        .success();
+    let trash = repo.store_dir().join(".trash");
+    let id = fs::read_dir(&trash)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .file_name()
+        .to_string_lossy()
+        .into_owned();
+    repo.ws_cmd_in("main")
+        .args(["store", "recover", &id, "--force"])
+        .assert()
+        .success();
+
+    repo.ws_cmd_in("main")
+        .args(["repos", "add", "--name", "proj"])
+        .assert()
+        .success();
+    let dest = repo.path().join("proj-store");
+    repo.ws_cmd_in("main")
+        .args(["store", "migrate", "--to", dest.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let output = repo
+        .ws_cmd_in("main")
+        .args(["store", "log", "--ops"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for action in [
+        "apply",
+        "variant-add",
+        "variant-rm",
+        "set-strategy",
+        "mv",
+        "recover",
+        "migrate",
+    ] {
+        assert!(stdout.contains(action), "{}: {}", action, stdout);
+    }
+    assert!(stdout.contains(".env.local"), "{}", stdout);
+
+    // 既定の表示には worktree だけを変えた操作を含めない
+    repo.ws_cmd_in("main")
+        .args(["store", "log"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("migrate"))
+        .stdout(predicate::str::contains("recover").not())
+        .stdout(predicate::str::contains("apply").not());
+}
+
+#[test]
+fn log_skips_dry_run_and_records_untrack() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    fs::write(wt.join(".env"), "A=1\n").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "copy", ".env", "--dry-run"])
+        .assert()
+        .success();
+    assert!(!repo.store_dir().join("oplog.jsonl").exists());
+
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "copy", ".env"])
+        .assert()
+        .success();
+    repo.ws_cmd_in("main")
+        .args(["store", "untrack", ".env"])
+        .assert()
+        .success();
+
+    let log = fs::read_to_string(repo.store_dir().join("oplog.jsonl")).unwrap();
+    let untrack = log.lines().last().unwrap();
+    assert!(untrack.contains("\"action\":\"untrack\""));
+    assert!(untrack.contains("\"before\":\"sha256:"));
+    assert!(!untrack.contains("\"after\""));
+}
+
+// ---- ws store migrate ----
+
+#[test]
+fn migrate_moves_store_relinks_worktrees_and_updates_config() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    fs::write(wt.join(".envrc"), "use flake").unwrap();
+    fs::write(wt.join(".env"), "A=1\n").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "symlink", ".envrc"])
+        .assert()
+        .success();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "copy", ".env"])
+        .assert()
+        .success();
+    repo.ws_cmd_in("main")
+        .args(["repos", "add", "--name", "proj"])
+        .assert()
+        .success();
+    let old_store = repo.store_dir();
+    let dest = repo.path().join("dotfiles").join("proj-store");
+
+    repo.ws_cmd_in("main")
+        .args(["store", "migrate", "--to", dest.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Relinked 1 files"));
+
+    assert!(!old_store.exists());
+    let dest = fs::canonicalize(&dest).unwrap();
+    assert!(dest.join("manifest.toml").is_file());
+    assert_eq!(fs::read_to_string(dest.join(".env")).unwrap(), "A=1\n");
+    assert_eq!(
+        fs::read_link(wt.join(".envrc")).unwrap(),
+        dest.join(".envrc")
+    );
+    let config = fs::read_to_string(&repo.config_path).unwrap();
+    assert!(config.contains("store_path = "), "{}", config);
+
+    // 以降のコマンドは新しい store を使う
+    repo.ws_cmd().args(["new", "feat"]).assert().success();
+    assert_eq!(
+        fs::read_to_string(repo.path().join("feat/.env")).unwrap(),
+        "A=1\n"
+    );
+    repo.ws_cmd_in("main")
+        .args(["store", "status"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("proj-store"));
+}
+
+#[test]
+fn migrate_rejects_non_empty_target_and_unregistered_repo() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    fs::write(wt.join(".env"), "A=1\n").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "copy", ".env"])
+        .assert()
+        .success();
+    let dest = repo.path().join("elsewhere");
+
+    repo.ws_cmd_in("main")
+        .args(["store", "migrate", "--to", dest.to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("ws repos add"));
+
+    repo.ws_cmd_in("main")
+        .args(["repos", "add"])
+        .assert()
+        .success();
+    fs::create_dir_all(&dest).unwrap();
+    fs::write(dest.join("notes.txt"), "").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "migrate", "--to", dest.to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("not an empty directory"));
+
+    assert!(repo.store_dir().join(".env").is_file());
+    assert!(!dest.join("manifest.toml").exists());
+}
+
+// ---- manifest format ----
+
+#[test]
+fn legacy_manifest_is_migrated_by_writing_commands() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    repo.write_legacy_manifest("copy:.env\tmode=0600\tprofiles=ci\n");
+    repo.add_store_file(".env", "A=1\n");
+
+    // 読むだけのコマンドは旧形式をそのまま読み、書き換えない
+    repo.ws_cmd_in("main")
+        .args(["store", "status"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(".env"))
+        .stderr(predicate::str::contains("manifest.toml").not());
+    assert!(repo.store_dir().join("manifest").is_file());
+    assert!(!repo.store_dir().join("manifest.toml").exists());
+
+    fs::write(wt.join(".envrc"), "use flake\n").unwrap();
+    // dry-run でも書き換えない
+    repo.ws_cmd_in("main")
+        .args(["--dry-run", "store", "track", "-s", "symlink", ".envrc"])
+        .assert()
+        .success();
+    assert!(repo.store_dir().join("manifest").is_file());
+    assert!(!repo.store_dir().join("manifest.toml").exists());
+
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "symlink", ".envrc"])
+        .assert()
+        .success();
+
+    let manifest = repo.manifest();
+    assert!(manifest.starts_with("version = 1\n"));
+    assert!(manifest.contains(&format!(
+        "{}mode = \"0600\"\nprofiles = [\"ci\"]\n",
+        manifest_record("copy", ".env")
+    )));
+    assert!(manifest.contains(&manifest_record("symlink", ".envrc")));
+    assert!(!repo.store_dir().join("manifest").exists());
+    assert_eq!(
+        fs::read_to_string(repo.store_dir().join("manifest.legacy")).unwrap(),
+        "copy:.env\tmode=0600\tprofiles=ci\n"
+    );
+}
+
+#[test]
+fn malformed_config_falls_back_to_default_store() {
+    let repo = TestRepo::new();
+    repo.init_store();
+    repo.add_manifest_entry("copy", ".env");
+    repo.add_store_file(".env", "A=1\n");
+    fs::write(&repo.config_path, "[repos\nbroken").unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "status"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(".env"))
+        .stderr(predicate::str::contains("default store location"));
+}
+
+#[test]
+fn malformed_manifest_reports_line_number() {
+    let repo = TestRepo::new();
+    repo.init_store();
+    fs::write(
+        repo.store_dir().join("manifest.toml"),
+        "version = 1\n\n[[files]]\npath = \".env\"\nstratgy = \"copy\"\n",
+    )
+    .unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "status"])
+        .assert()
+        .failure()
+        .stderr(
+            predicate::str::contains("manifest.toml:5:").and(predicate::str::contains("stratgy")),
+        );
+
+    repo.write_legacy_manifest("symlink:.envrc\nnot a manifest line\n");
+    repo.ws_cmd_in("main")
+        .args(["store", "status"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("manifest:2:"));
+}
+
+// ---- path safety ----
+
+#[test]
+fn crafted_manifest_with_escaping_path_is_rejected() {
+    let repo = TestRepo::new();
+    repo.init_store();
+    repo.add_manifest_entry("copy", "../../escaped");
+    fs::write(repo.store_dir().join("escaped"), "x").unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "pull"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("manifest.toml:4:").and(predicate::str::contains("..")));
+    repo.ws_cmd().args(["new", "feat"]).assert().failure();
+    assert!(!repo.path().parent().unwrap().join("escaped").exists());
+}
+
+#[test]
+fn crafted_manifest_with_absolute_path_is_rejected() {
+    let repo = TestRepo::new();
+    repo.init_store();
+    let outside = repo.path().join("outside.txt");
+    repo.add_manifest_entry("copy", outside.to_str().unwrap());
+
+    repo.ws_cmd_in("main")
+        .args(["store", "apply"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("absolute"));
+    assert!(!outside.exists());
+}
+
+#[test]
+fn track_rejects_paths_outside_worktree() {
+    let repo = TestRepo::new();
+    fs::write(repo.path().join("secret"), "x").unwrap();
+
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "copy", "../secret"])
+        .assert()
+        .failure();
+    repo.ws_cmd_in("main")
+        .args([
+            "store",
+            "track",
+            "-s",
+            "copy",
+            repo.path().join("secret").to_str().unwrap(),
+        ])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn pull_does_not_follow_symlinked_directories() {
+    let repo = TestRepo::new();
+    let wt = repo.main_worktree();
+    let outside = repo.path().join("outside");
+    fs::create_dir_all(&outside).unwrap();
+    std::os::unix::fs::symlink(&outside, wt.join("cfg")).unwrap();
+    repo.init_store();
+    repo.add_manifest_entry("copy", "cfg/.env");
+    repo.add_store_file("cfg/.env", "A=1\n");
+
+    repo.ws_cmd_in("main")
+        .args(["store", "pull"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cfg"));
+    assert!(!outside.join(".env").exists());
+
+    // symlink の先にあるファイルを store に取り込むこともしない
+    fs::write(outside.join("leak"), "x").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "track", "-s", "copy", "cfg/leak"])
+        .assert()
+        .failure();
+}
@@ -1,6 +1,7 @@
 mod common;
 
 use common::TestRepo;
+use predicates::prelude::*;
 use std::fs;
 use tempfile::TempDir;
 
@@ -66,6 +67,167 @@ fn new_applies_store_files() {
     assert_eq!(fs::read_to_string(&wt_file).unwrap(), r#"{"key":"value"}"#);
 }
 
+#[test]
+fn new_with_profile_applies_only_profile_entries() {
+    let repo = TestRepo::new();
+    repo.init_store();
+    repo.add_manifest_entry("copy", ".env");
+    repo.add_manifest_entry("copy", ".mcp.json");
+    repo.add_store_file(".env", "A=1\n");
+    repo.add_store_file(".mcp.json", "{}");
+    repo.ws_cmd_in("main")
+        .args(["store", "profile", "add", "review", ".env"])
+        .assert()
+        .success();
+
+    repo.ws_cmd()
+        .args(["new", "review-1", "--profile", "review"])
+        .assert()
+        .success();
+    let wt = repo.path().join("review-1");
+    assert!(wt.join(".env").is_file());
+    assert!(!wt.join(".mcp.json").exists());
+
+    // 未適用のファイルは MISSING ではなく profile 外として表示される
+    repo.ws_cmd_in("review-1")
+        .args(["store", "status"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("Profile: review")
+                .and(predicate::str::contains("-(profile)"))
+                .and(predicate::str::contains("MISSING").not()),
+        );
+}
+
+#[test]
+fn new_with_sparse_checks_out_and_applies_only_subproject() {
+    let repo = TestRepo::new();
+    repo.commit_files(&[("apps/web/index.js", ""), ("apps/api/main.go", "")]);
+    repo.init_store();
+    let manifest = repo.store_dir().join("manifest.toml");
+    let mut content = fs::read_to_string(&manifest).unwrap();
+    for project in ["apps/web", "apps/api"] {
+        content.push_str(&format!(
+            "\n[[files]]\npath = \".env\"\nproject = \"{}\"\nstrategy = \"copy\"\n",
+            project
+        ));
+        repo.add_store_file(&format!("{}/.env", project), project);
+    }
+    fs::write(&manifest, content).unwrap();
+    repo.add_manifest_entry("copy", ".envrc");
+    repo.add_store_file(".envrc", "use flake");
+
+    repo.ws_cmd()
+        .args(["new", "feat-web", "--from", "main", "--sparse", "apps/web"])
+        .assert()
+        .success();
+
+    let wt = repo.path().join("feat-web");
+    assert!(wt.join("README.md").is_file());
+    assert!(wt.join("apps/web/index.js").is_file());
+    assert!(!wt.join("apps/api").exists());
+    assert_eq!(
+        fs::read_to_string(wt.join("apps/web/.env")).unwrap(),
+        "apps/web"
+    );
+    assert!(wt.join(".envrc").is_file());
+}
+
+#[test]
+fn new_rejects_sparse_directory_outside_worktree() {
+    let repo = TestRepo::new();
+
+    repo.ws_cmd()
+        .args(["new", "feat-x", "--sparse", "../other"])
+        .assert()
+        .failure();
+
+    assert!(!repo.path().join("feat-x").exists());
+}
+
+#[test]
+fn new_rejects_unknown_profile_before_creating_worktree() {
+    let repo = TestRepo::new();
+    repo.init_store();
+    repo.add_manifest_entry("copy", ".env");
+    repo.add_store_file(".env", "A=1\n");
+
+    repo.ws_cmd()
+        .args(["new", "review-1", "--profile", "reveiw"])
+        .assert()
+        .failure();
+    assert!(!repo.path().join("review-1").exists());
+}
+
+#[test]
+fn new_runs_command_entries_in_new_worktree() {
+    let repo = TestRepo::new();
+
+    repo.init_store();
+    fs::write(
+        repo.store_dir().join("manifest.toml"),
+        "version = 1\n\n[[files]]\npath = \".env.local\"\nstrategy = \"command\"\ncommand = 'echo \"NAME=$(basename \"$PWD\")\"'\n",
+    )
+    .unwrap();
+
+    repo.ws_cmd().args(["new", "feat-y"]).assert().success();
+
+    // コマンドは新しい worktree をカレントディレクトリとして実行される
+    let wt_file = repo.path().join("feat-y").join(".env.local");
+    assert_eq!(fs::read_to_string(&wt_file).unwrap(), "NAME=feat-y\n");
+    // 出力は store にキャッシュされる
+    assert_eq!(
+        fs::read_to_string(repo.store_dir().join(".env.local")).unwrap(),
+        "NAME=feat-y\n"
+    );
+}
+
+#[test]
+fn new_uses_cached_output_when_command_fails() {
+    let repo = TestRepo::new();
+
+    repo.init_store();
+    repo.add_store_file(".kube/config", "cached");
+    fs::write(
+        repo.store_dir().join("manifest.toml"),
+        "version = 1\n\n[[files]]\npath = \".kube/config\"\nstrategy = \"command\"\ncommand = \"exit 1\"\n",
+    )
+    .unwrap();
+
+    repo.ws_cmd()
+        .args(["new", "feat-y"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("failed"));
+
+    let wt_file = repo.path().join("feat-y").join(".kube/config");
+    assert_eq!(fs::read_to_string(&wt_file).unwrap(), "cached");
+}
+
+#[test]
+fn new_records_merge_json_base() {
+    let repo = TestRepo::new();
+
+    repo.init_store();
+    repo.add_manifest_entry("merge-json", "settings.json");
+    repo.add_store_file("settings.json", r#"{"a": 1}"#);
+
+    repo.ws_cmd().args(["new", "feat-y"]).assert().success();
+
+    assert_eq!(
+        fs::read_to_string(repo.path().join("feat-y/settings.json")).unwrap(),
+        r#"{"a": 1}"#
+    );
+    // 配布した base が記録されているので BASE_CHANGED にならない
+    repo.ws_cmd_in("feat-y")
+        .args(["store", "status"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("OK"))
+        .stdout(predicate::str::contains("BASE_CHANGED").not());
+}
+
 #[test]
 fn new_requires_name() {
     let repo = TestRepo::new();
@@ -94,6 +256,104 @@ fn rm_removes_worktree() {
     assert!(!repo.path().join("to-remove").is_dir());
 }
 
+#[test]
+fn rm_stashes_modified_store_copies_and_recover_restores_them() {
+    let repo = TestRepo::new();
+    repo.init_store();
+    repo.add_manifest_entry("copy", ".mcp.json");
+    repo.add_store_file(".mcp.json", "{}");
+    repo.ws_cmd().args(["new", "feat-x"]).assert().success();
+    let wt_file = repo.path().join("feat-x").join(".mcp.json");
+    fs::write(&wt_file, r#"{"local":true}"#).unwrap();
+
+    // untracked ファイルがあるので --force で削除する
+    let abs_path = repo.path().join("feat-x").to_string_lossy().to_string();
+    repo.ws_cmd_in("main")
+        .args(["rm", "--force", &abs_path])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ws store recover"));
+    assert!(!repo.path().join("feat-x").is_dir());
+
+    let trash = repo.store_dir().join(".trash");
+    let ids: Vec<String> = fs::read_dir(&trash)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(ids.len(), 1);
+    let id = &ids[0];
+
+    repo.ws_cmd_in("main")
+        .args(["store", "recover"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(id.as_str()))
+        .stdout(predicate::str::contains(".mcp.json"));
+
+    // 既存のファイルは --force なしでは上書きしない
+    fs::write(repo.main_worktree().join(".mcp.json"), "{}").unwrap();
+    repo.ws_cmd_in("main")
+        .args(["store", "recover", id])
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(repo.main_worktree().join(".mcp.json")).unwrap(),
+        "{}"
+    );
+    assert!(trash.join(id).is_dir());
+
+    repo.ws_cmd_in("main")
+        .args(["store", "recover", id, ".mcp.json", "--force"])
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(repo.main_worktree().join(".mcp.json")).unwrap(),
+        r#"{"local":true}"#
+    );
+    assert!(!trash.join(id).exists());
+}
+
+#[test]
+fn rm_stashes_modified_copies_even_with_malformed_config() {
+    let repo = TestRepo::new();
+    repo.init_store();
+    repo.add_manifest_entry("copy", ".mcp.json");
+    repo.add_store_file(".mcp.json", "{}");
+    repo.ws_cmd().args(["new", "feat-x"]).assert().success();
+    fs::write(repo.path().join("feat-x").join(".mcp.json"), "[]").unwrap();
+    fs::write(&repo.config_path, "[repos\nbroken").unwrap();
+
+    let abs_path = repo.path().join("feat-x").to_string_lossy().to_string();
+    repo.ws_cmd_in("main")
+        .args(["rm", "--force", &abs_path])
+        .assert()
+        .success();
+
+    let trash = repo.store_dir().join(".trash");
+    assert_eq!(fs::read_dir(&trash).unwrap().count(), 1);
+}
+
+#[test]
+fn rm_discards_stash_when_git_refuses_to_remove() {
+    let repo = TestRepo::new();
+    repo.init_store();
+    repo.add_manifest_entry("copy", ".mcp.json");
+    repo.add_store_file(".mcp.json", "{}");
+    repo.ws_cmd().args(["new", "feat-x"]).assert().success();
+    fs::write(repo.path().join("feat-x").join(".mcp.json"), "[]").unwrap();
+
+    // untracked ファイルがあるので --force なしでは git が削除を拒否する
+    let abs_path = repo.path().join("feat-x").to_string_lossy().to_string();
+    repo.ws_cmd_in("main")
+        .args(["rm", &abs_path])
+        .assert()
+        .failure();
+
+    assert!(repo.path().join("feat-x").is_dir());
+    let trash = repo.store_dir().join(".trash");
+    assert_eq!(fs::read_dir(&trash).map(|d| d.count()).unwrap_or(0), 0);
+}
+
 // ---- config registration ----
 
 #[test]
@@ -132,3 +392,42 @@ fn clone_registers_in_config() {
         path_str
     );
 }
+
+// ---- --dry-run ----
+
+#[test]
+fn dry_run_new_prints_plan_without_creating_worktree() {
+    let repo = TestRepo::new();
+    repo.init_store();
+    repo.add_manifest_entry("copy", ".mcp.json");
+    repo.add_store_file(".mcp.json", "{}");
+
+    repo.ws_cmd()
+        .args(["--dry-run", "new", "feat-x"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("[dry-run] git --git-dir .bare worktree add")
+                .and(predicate::str::contains(".mcp.json")),
+        );
+
+    assert!(!repo.path().join("feat-x").exists());
+}
+
+#[test]
+fn dry_run_clone_does_not_create_bare_repo() {
+    let tmp = TempDir::new().unwrap();
+    let config_path = tmp.path().join("ws-config.toml");
+
+    let mut cmd = assert_cmd::cargo_bin_cmd!("ws");
+    cmd.args(["repos", "clone", "--dry-run"])
+        .current_dir(tmp.path())
+        .env("LC_ALL", "en")
+        .env("WS_CONFIG_PATH", &config_path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[dry-run] git init --bare .bare"));
+
+    assert!(!tmp.path().join(".bare").exists());
+    assert!(!config_path.exists());
+}
@@ -7,6 +7,9 @@ use crate::store::Strategy;
 #[derive(Parser)]
 #[command(name = "ws", version)]
 pub struct Ws {
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
     #[command(subcommand)]
     pub command: WsCommand,
 }
@@ -41,6 +44,12 @@ pub struct NewCmd {
 
     #[arg(long = "from")]
     pub from: Option<String>,
+
+    #[arg(long)]
+    pub profile: Option<String>,
+
+    #[arg(long, value_name = "DIR")]
+    pub sparse: Vec<String>,
 }
 
 #[derive(Parser)]
@@ -86,14 +95,38 @@ pub enum StoreCommand {
     Push(StorePushCmd),
     Pull(StorePullCmd),
     Untrack(StoreUntrackCmd),
+    Diff(StoreDiffCmd),
+    Suggest(StoreSuggestCmd),
+    Apply(StoreApplyCmd),
+    Mv(StoreMvCmd),
+    SetStrategy(StoreSetStrategyCmd),
+    Verify(StoreVerifyCmd),
+    Profile(StoreProfileCmd),
+    Variant(StoreVariantCmd),
+    Log(StoreLogCmd),
+    Recover(StoreRecoverCmd),
+    Migrate(StoreMigrateCmd),
 }
 
 #[derive(Parser)]
 pub struct StoreTrackCmd {
     #[arg(short = 's', long, value_enum)]
-    pub strategy: Strategy,
+    pub strategy: Option<Strategy>,
 
-    pub file: String,
+    #[arg(required_unless_present = "auto")]
+    pub file: Option<String>,
+
+    #[arg(long)]
+    pub command: Option<String>,
+
+    #[arg(long, value_name = "PATTERN")]
+    pub include: Vec<String>,
+
+    #[arg(long, value_name = "PATTERN")]
+    pub exclude: Vec<String>,
+
+    #[arg(long, conflicts_with_all = ["strategy", "file", "command", "include", "exclude"])]
+    pub auto: bool,
 }
 
 #[derive(Parser)]
@@ -102,6 +135,9 @@ pub struct StoreStatusCmd {}
 #[derive(Parser)]
 pub struct StorePushCmd {
     pub file: Option<String>,
+
+    #[arg(long)]
+    pub include_overlay: bool,
 }
 
 #[derive(Parser)]
@@ -115,6 +151,142 @@ pub struct StorePullCmd {
 #[derive(Parser)]
 pub struct StoreUntrackCmd {
     pub file: String,
+
+    /// 既定の動作と同じ。スクリプトで明示できるよう残している
+    #[arg(long, conflicts_with = "purge")]
+    pub restore: bool,
+
+    #[arg(long)]
+    pub purge: bool,
+}
+
+#[derive(Parser)]
+pub struct StoreDiffCmd {
+    pub file: Option<String>,
+
+    #[arg(long)]
+    pub show_values: bool,
+}
+
+#[derive(Parser)]
+pub struct StoreSuggestCmd {
+    #[arg(short = 'i', long)]
+    pub interactive: bool,
+}
+
+#[derive(Parser)]
+pub struct StoreApplyCmd {
+    pub file: Option<String>,
+
+    #[arg(long, conflicts_with = "no_profile")]
+    pub profile: Option<String>,
+
+    #[arg(long)]
+    pub no_profile: bool,
+}
+
+#[derive(Parser)]
+pub struct StoreMvCmd {
+    pub old: String,
+
+    pub new: String,
+}
+
+#[derive(Parser)]
+pub struct StoreSetStrategyCmd {
+    pub file: String,
+
+    #[arg(value_enum)]
+    pub strategy: Strategy,
+
+    #[arg(long)]
+    pub command: Option<String>,
+
+    #[arg(short = 'f', long)]
+    pub force: bool,
+}
+
+#[derive(Parser)]
+pub struct StoreVerifyCmd {
+    pub file: Option<String>,
+
+    #[arg(long)]
+    pub update: bool,
+}
+
+#[derive(Parser)]
+pub struct StoreProfileCmd {
+    #[command(subcommand)]
+    pub command: StoreProfileCommand,
+}
+
+#[derive(Subcommand)]
+pub enum StoreProfileCommand {
+    Add(StoreProfileEditCmd),
+    Rm(StoreProfileEditCmd),
+    List(StoreProfileListCmd),
+}
+
+#[derive(Parser)]
+pub struct StoreProfileEditCmd {
+    pub profile: String,
+
+    #[arg(required = true)]
+    pub files: Vec<String>,
+}
+
+#[derive(Parser)]
+pub struct StoreProfileListCmd {}
+
+#[derive(Parser)]
+pub struct StoreVariantCmd {
+    #[command(subcommand)]
+    pub command: StoreVariantCommand,
+}
+
+#[derive(Subcommand)]
+pub enum StoreVariantCommand {
+    Add(StoreVariantEditCmd),
+    Rm(StoreVariantEditCmd),
+    List(StoreVariantListCmd),
+}
+
+#[derive(Parser)]
+pub struct StoreVariantEditCmd {
+    pub branch: String,
+
+    pub file: String,
+}
+
+#[derive(Parser)]
+pub struct StoreVariantListCmd {}
+
+#[derive(Parser)]
+pub struct StoreLogCmd {
+    pub file: Option<String>,
+
+    #[arg(long)]
+    pub ops: bool,
+
+    #[arg(short = 'n', long)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Parser)]
+pub struct StoreRecoverCmd {
+    pub id: Option<String>,
+
+    #[arg(requires = "id")]
+    pub files: Vec<String>,
+
+    #[arg(short = 'f', long, requires = "id")]
+    pub force: bool,
+}
+
+#[derive(Parser)]
+pub struct StoreMigrateCmd {
+    #[arg(long)]
+    pub to: String,
 }
 
 #[derive(Parser)]
@@ -151,12 +323,15 @@ pub struct ReposRmCmd {
 pub fn parse_with_i18n() -> Ws {
     let cmd = Ws::command()
         .about(t!("cli.about").to_string())
+        .mut_arg("dry_run", |a| a.help(t!("cli.dry_run").to_string()))
         .mut_subcommand("new", |s| {
             s.about(t!("cli.new.about").to_string())
                 .mut_arg("name", |a| a.help(t!("cli.new.name").to_string()))
                 .mut_arg("directory", |a| a.help(t!("cli.new.directory").to_string()))
                 .mut_arg("branch", |a| a.help(t!("cli.new.branch").to_string()))
                 .mut_arg("from", |a| a.help(t!("cli.new.from").to_string()))
+                .mut_arg("profile", |a| a.help(t!("cli.new.profile").to_string()))
+                .mut_arg("sparse", |a| a.help(t!("cli.new.sparse").to_string()))
         })
         .mut_subcommand("rm", |s| {
             s.about(t!("cli.rm.about").to_string())
@@ -187,6 +362,16 @@ pub fn parse_with_i18n() -> Ws {
                             a.help(t!("cli.store.track.strategy").to_string())
                         })
                         .mut_arg("file", |a| a.help(t!("cli.store.track.file").to_string()))
+                        .mut_arg("command", |a| {
+                            a.help(t!("cli.store.track.command").to_string())
+                        })
+                        .mut_arg("include", |a| {
+                            a.help(t!("cli.store.track.include").to_string())
+                        })
+                        .mut_arg("exclude", |a| {
+                            a.help(t!("cli.store.track.exclude").to_string())
+                        })
+                        .mut_arg("auto", |a| a.help(t!("cli.store.track.auto").to_string()))
                 })
                 .mut_subcommand("status", |ss| {
                     ss.about(t!("cli.store.status.about").to_string())
@@ -194,6 +379,9 @@ pub fn parse_with_i18n() -> Ws {
                 .mut_subcommand("push", |ss| {
                     ss.about(t!("cli.store.push.about").to_string())
                         .mut_arg("file", |a| a.help(t!("cli.store.push.file").to_string()))
+                        .mut_arg("include_overlay", |a| {
+                            a.help(t!("cli.store.push.include_overlay").to_string())
+                        })
                 })
                 .mut_subcommand("pull", |ss| {
                     ss.about(t!("cli.store.pull.about").to_string())
@@ -203,6 +391,130 @@ pub fn parse_with_i18n() -> Ws {
                 .mut_subcommand("untrack", |ss| {
                     ss.about(t!("cli.store.untrack.about").to_string())
                         .mut_arg("file", |a| a.help(t!("cli.store.untrack.file").to_string()))
+                        .mut_arg("restore", |a| {
+                            a.help(t!("cli.store.untrack.restore").to_string())
+                        })
+                        .mut_arg("purge", |a| {
+                            a.help(t!("cli.store.untrack.purge").to_string())
+                        })
+                })
+                .mut_subcommand("diff", |ss| {
+                    ss.about(t!("cli.store.diff.about").to_string())
+                        .mut_arg("file", |a| a.help(t!("cli.store.diff.file").to_string()))
+                        .mut_arg("show_values", |a| {
+                            a.help(t!("cli.store.diff.show_values").to_string())
+                        })
+                })
+                .mut_subcommand("suggest", |ss| {
+                    ss.about(t!("cli.store.suggest.about").to_string())
+                        .mut_arg("interactive", |a| {
+                            a.help(t!("cli.store.suggest.interactive").to_string())
+                        })
+                })
+                .mut_subcommand("apply", |ss| {
+                    ss.about(t!("cli.store.apply.about").to_string())
+                        .mut_arg("file", |a| a.help(t!("cli.store.apply.file").to_string()))
+                        .mut_arg("profile", |a| {
+                            a.help(t!("cli.store.apply.profile").to_string())
+                        })
+                        .mut_arg("no_profile", |a| {
+                            a.help(t!("cli.store.apply.no_profile").to_string())
+                        })
+                })
+                .mut_subcommand("mv", |ss| {
+                    ss.about(t!("cli.store.mv.about").to_string())
+                        .mut_arg("old", |a| a.help(t!("cli.store.mv.old").to_string()))
+                        .mut_arg("new", |a| a.help(t!("cli.store.mv.new").to_string()))
+                })
+                .mut_subcommand("set-strategy", |ss| {
+                    ss.about(t!("cli.store.set_strategy.about").to_string())
+                        .mut_arg("file", |a| {
+                            a.help(t!("cli.store.set_strategy.file").to_string())
+                        })
+                        .mut_arg("strategy", |a| {
+                            a.help(t!("cli.store.set_strategy.strategy").to_string())
+                        })
+                        .mut_arg("command", |a| {
+                            a.help(t!("cli.store.set_strategy.command").to_string())
+                        })
+                        .mut_arg("force", |a| {
+                            a.help(t!("cli.store.set_strategy.force").to_string())
+                        })
+                })
+                .mut_subcommand("verify", |ss| {
+                    ss.about(t!("cli.store.verify.about").to_string())
+                        .mut_arg("file", |a| a.help(t!("cli.store.verify.file").to_string()))
+                        .mut_arg("update", |a| {
+                            a.help(t!("cli.store.verify.update").to_string())
+                        })
+                })
+                .mut_subcommand("profile", |ss| {
+                    ss.about(t!("cli.store.profile.about").to_string())
+                        .mut_subcommand("add", |sss| {
+                            sss.about(t!("cli.store.profile.add.about").to_string())
+                                .mut_arg("profile", |a| {
+                                    a.help(t!("cli.store.profile.name").to_string())
+                                })
+                                .mut_arg("files", |a| {
+                                    a.help(t!("cli.store.profile.files").to_string())
+                                })
+                        })
+                        .mut_subcommand("rm", |sss| {
+                            sss.about(t!("cli.store.profile.rm.about").to_string())
+                                .mut_arg("profile", |a| {
+                                    a.help(t!("cli.store.profile.name").to_string())
+                                })
+                                .mut_arg("files", |a| {
+                                    a.help(t!("cli.store.profile.files").to_string())
+                                })
+                        })
+                        .mut_subcommand("list", |sss| {
+                            sss.about(t!("cli.store.profile.list.about").to_string())
+                        })
+                })
+                .mut_subcommand("variant", |ss| {
+                    ss.about(t!("cli.store.variant.about").to_string())
+                        .mut_subcommand("add", |sss| {
+                            sss.about(t!("cli.store.variant.add.about").to_string())
+                                .mut_arg("branch", |a| {
+                                    a.help(t!("cli.store.variant.branch").to_string())
+                                })
+                                .mut_arg("file", |a| {
+                                    a.help(t!("cli.store.variant.file").to_string())
+                                })
+                        })
+                        .mut_subcommand("rm", |sss| {
+                            sss.about(t!("cli.store.variant.rm.about").to_string())
+                                .mut_arg("branch", |a| {
+                                    a.help(t!("cli.store.variant.branch").to_string())
+                                })
+                                .mut_arg("file", |a| {
+                                    a.help(t!("cli.store.variant.file").to_string())
+                                })
+                        })
+                        .mut_subcommand("list", |sss| {
+                            sss.about(t!("cli.store.variant.list.about").to_string())
+                        })
+                })
+                .mut_subcommand("log", |ss| {
+                    ss.about(t!("cli.store.log.about").to_string())
+                        .mut_arg("file", |a| a.help(t!("cli.store.log.file").to_string()))
+                        .mut_arg("ops", |a| a.help(t!("cli.store.log.ops").to_string()))
+                        .mut_arg("limit", |a| a.help(t!("cli.store.log.limit").to_string()))
+                })
+                .mut_subcommand("recover", |ss| {
+                    ss.about(t!("cli.store.recover.about").to_string())
+                        .mut_arg("id", |a| a.help(t!("cli.store.recover.id").to_string()))
+                        .mut_arg("files", |a| {
+                            a.help(t!("cli.store.recover.files").to_string())
+                        })
+                        .mut_arg("force", |a| {
+                            a.help(t!("cli.store.recover.force").to_string())
+                        })
+                })
+                .mut_subcommand("migrate", |ss| {
+                    ss.about(t!("cli.store.migrate.about").to_string())
+                        .mut_arg("to", |a| a.help(t!("cli.store.migrate.to").to_string()))
                 })
         })
         .mut_subcommand("repos", |s| {
@@ -4,7 +4,7 @@ use std::path::PathBuf;
 use std::process::{Command, Stdio};
 
 use crate::cli::{ReposAddCmd, ReposRmCmd};
-use crate::config::{RepoEntry, load_config, save_config};
+use crate::config::{RepoEntry, load_config, lock_config, save_config};
 use crate::ui::{self, StyledCell};
 
 pub fn cmd_repos_add(cmd: &ReposAddCmd) -> Result<()> {
@@ -51,6 +51,7 @@ pub fn cmd_repos_add(cmd: &ReposAddCmd) -> Result<()> {
             .unwrap_or_else(|| "unknown".to_string()),
     };
 
+    let _lock = lock_config()?;
     let mut config = load_config()?;
 
     // 重複チェック
@@ -78,6 +79,7 @@ pub fn cmd_repos_add(cmd: &ReposAddCmd) -> Result<()> {
         RepoEntry {
             path: path.clone(),
             url,
+            ..Default::default()
         },
     );
     save_config(&config)?;
@@ -190,6 +192,7 @@ pub fn parse_worktree_list(output: &str, repo_root: &std::path::Path) -> Vec<Wor
 }
 
 pub fn cmd_repos_rm(cmd: &ReposRmCmd) -> Result<()> {
+    let _lock = lock_config()?;
     let mut config = load_config()?;
 
     if config.repos.remove(&cmd.name).is_none() {
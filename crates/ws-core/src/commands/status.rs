@@ -3,7 +3,8 @@ use rust_i18n::t;
 
 use crate::commands::repos::WorktreeEntry;
 use crate::context::{AppContext, abbreviate_home, print_table};
-use crate::store::file_status;
+use crate::git::worktree_branch;
+use crate::store::{load_hash_cache, read_profile, worktree_status};
 use crate::ui::{self, StyledCell};
 
 pub fn cmd_status(ctx: &AppContext) -> Result<()> {
@@ -16,25 +17,18 @@ pub fn cmd_status(ctx: &AppContext) -> Result<()> {
 
         let mut rows = Vec::new();
         let mut markers = Vec::new();
-        for (name, entry) in &ctx.config.repos {
+        for probe in &ctx.repos {
             let is_current = ctx
                 .current_repo
                 .as_ref()
-                .and_then(|r| entry.path.canonicalize().ok().map(|p| p == r.root))
-                .unwrap_or(false);
-
-            let display_path = abbreviate_home(&entry.path);
-            let repo_type = if !entry.path.exists() {
-                "NOT_FOUND"
-            } else if entry.path.join(".bare").is_dir() {
-                "bare"
-            } else {
-                "git"
-            };
+                .is_some_and(|r| probe.canonical.as_ref() == Some(&r.root));
+
+            let display_path = abbreviate_home(&probe.path);
+            let repo_type = probe.kind.as_str();
 
             markers.push(is_current);
             rows.push(vec![
-                StyledCell::plain(name.clone()),
+                StyledCell::plain(probe.name.clone()),
                 StyledCell::plain(display_path),
                 StyledCell::new(repo_type, ui::repo_type_style(repo_type)),
             ]);
@@ -132,19 +126,39 @@ pub fn cmd_status(ctx: &AppContext) -> Result<()> {
 
         let store = ws.store_dir.as_ref().unwrap();
         let wt_root = Some(ws.root.clone());
+        let mut cache = load_hash_cache(&wt_root);
+        let profile = read_profile(&ws.root);
+        let branch = worktree_branch(&ws.root);
+        let has_variants = ws.manifest.iter().any(|e| !e.variants.is_empty());
 
         let mut rows = Vec::new();
         for entry in &ws.manifest {
-            let store_file = store.join(&entry.filepath);
-            let status = file_status(entry, &store_file, &wt_root);
-            rows.push(vec![
+            let status = worktree_status(
+                entry,
+                store,
+                &wt_root,
+                branch.as_deref(),
+                profile.as_deref(),
+                &mut cache,
+            );
+            let mut row = vec![
                 StyledCell::plain(entry.strategy.to_string()),
                 StyledCell::plain(entry.filepath.clone()),
                 StyledCell::new(status.to_string(), ui::status_style(&status)),
-            ]);
+            ];
+            if has_variants {
+                row.push(ui::variant_cell(entry.for_branch(branch.as_deref())));
+            }
+            rows.push(row);
         }
 
-        print_table(&["STRATEGY", "FILE", "STATUS"], &rows, 2, None);
+        let headers: &[&str] = if has_variants {
+            &["STRATEGY", "FILE", "STATUS", "VARIANT"]
+        } else {
+            &["STRATEGY", "FILE", "STATUS"]
+        };
+        print_table(headers, &rows, 2, None);
+        let _ = cache.save();
     }
 
     if !has_output {
@@ -1,50 +1,343 @@
 use anyhow::{Context, Result, bail};
 use rust_i18n::t;
+use std::collections::BTreeMap;
 use std::fs;
-use std::os::unix::fs as unix_fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use crate::cli::{StorePullCmd, StorePushCmd, StoreTrackCmd, StoreUntrackCmd};
-use crate::git::{git_output, worktree_root};
+use crate::cli::{
+    StoreApplyCmd, StoreDiffCmd, StoreLogCmd, StoreMigrateCmd, StoreMvCmd, StoreProfileEditCmd,
+    StorePullCmd, StorePushCmd, StoreRecoverCmd, StoreSetStrategyCmd, StoreTrackCmd,
+    StoreUntrackCmd, StoreVariantEditCmd, StoreVerifyCmd,
+};
+use crate::config::Config;
+use crate::context::abbreviate_home;
+use crate::dotenv::{self, KeyChange};
+use crate::exec::Executor;
+use crate::git::{
+    git_output_in, resolve_repo_root, worktree_branch, worktree_paths, worktree_root,
+};
+use crate::hash;
+use crate::oplog::{Action, OpLog, OpRecord, read_oplog};
+use crate::pattern::PathFilter;
 use crate::store::{
-    ManifestEntry, Strategy, copy_dir_recursive, ensure_store, file_status, path_or_symlink_exists,
-    read_manifest, require_store, store_entry_exists, write_manifest,
+    FileStatus, ManifestEntry, Strategy, apply_file, current_project, default_store_dir,
+    ensure_store, file_mode, file_status, find_binary, format_size, generate_file, is_hardlinked,
+    is_real_dir, link_tree_status, load_hash_cache, lock_store, lock_store_shared, manifest_exists,
+    manifest_path, merge_base_path, merge_json_file, merge_json_push, migrate_legacy_manifest,
+    open_store, path_or_symlink_exists, path_size, project_file, read_manifest, read_profile,
+    require_profile, require_store, retire_legacy_manifest, save_merge_base, save_profile,
+    store_dir, store_entry_exists, tree_links_into, validate_branch_pattern, validate_entry_path,
+    validate_profile, variant_dir, worktree_path, worktree_status, write_manifest,
 };
+use crate::trash;
 use crate::ui::{self, StyledCell};
 
-pub fn cmd_store_track(cmd: &StoreTrackCmd) -> Result<()> {
-    let store = ensure_store()?;
+pub fn cmd_store_track(cmd: &StoreTrackCmd, exec: &Executor) -> Result<()> {
+    let store = ensure_store(exec)?;
+    let _lock = lock_store(&store)?;
     let wt_root = worktree_root()?;
+    if cmd.auto {
+        return track_auto(&store, &wt_root, exec);
+    }
+
+    // サブディレクトリで実行したら、そのディレクトリをサブプロジェクトとしてパスを解決する
+    let project = current_project(&wt_root);
+    let file = project_file(project.as_deref(), cmd.file.as_deref().unwrap_or_default());
+    let project = project.filter(|p| file.starts_with(&format!("{}/", p)));
+    let file = file.as_str();
+    let strategy = match cmd.strategy {
+        Some(ref strategy) => strategy.clone(),
+        None => rule_strategy(&crate::config::load_config()?, &wt_root, file)?
+            .ok_or_else(|| anyhow::anyhow!("{}", t!("store.no_matching_rule", file = file)))?,
+    };
+    if strategy == Strategy::Command && (!cmd.include.is_empty() || !cmd.exclude.is_empty()) {
+        bail!(
+            "{}",
+            t!(
+                "store.filter_unsupported",
+                file = file,
+                strategy = strategy.as_str()
+            )
+        );
+    }
+    match (&strategy, cmd.command.as_deref()) {
+        (Strategy::Command, Some(command)) => {
+            track_command(&store, &wt_root, file, project.as_deref(), command, exec)
+        }
+        (Strategy::Command, None) => bail!("{}", t!("store.command_required")),
+        (_, Some(_)) => bail!("{}", t!("store.command_requires_strategy")),
+        (strategy, None) => {
+            let filter = PathFilter {
+                include: cmd.include.clone(),
+                exclude: cmd.exclude.clone(),
+            };
+            track_file(
+                &store,
+                &wt_root,
+                file,
+                project.as_deref(),
+                strategy,
+                &filter,
+                exec,
+            )
+        }
+    }
+}
+
+/// config の自動 track のルールから `file` の strategy を決める。一致するルールがなければ `None`。
+fn rule_strategy(config: &Config, wt_root: &Path, file: &str) -> Result<Option<Strategy>> {
+    let repo_root = resolve_repo_root(Some(wt_root));
+    config
+        .store_rule_for(repo_root.as_deref(), file)
+        .map(|name| {
+            name.parse::<Strategy>()
+                .with_context(|| t!("store.invalid_rule_strategy", strategy = name).to_string())
+        })
+        .transpose()
+}
+
+/// `ws store track --auto`: worktree の ignored ファイルのうち、ルールに一致するものをまとめて track する。
+///
+/// 既に追跡中のパスとビルド成果物は対象にしない。command はコマンドを指定できないので飛ばす。
+fn track_auto(store: &Path, wt_root: &Path, exec: &Executor) -> Result<()> {
+    let config = crate::config::load_config()?;
+    let tracked: Vec<String> = if manifest_exists(store) {
+        read_manifest(store)?
+            .into_iter()
+            .map(|e| e.filepath)
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let output = git_output_in(
+        wt_root,
+        &["ls-files", "--others", "--ignored", "--exclude-standard"],
+    )?;
+    let mut count = 0u32;
+    for path in output.lines() {
+        if path.is_empty()
+            || is_build_artifact(path)
+            || tracked
+                .iter()
+                .any(|t| path == t || Path::new(path).starts_with(t))
+        {
+            continue;
+        }
+        let Some(strategy) = rule_strategy(&config, wt_root, path)? else {
+            continue;
+        };
+        if strategy == Strategy::Command {
+            anstream::eprintln!(
+                "{}",
+                ui::styled(
+                    ui::STYLE_WARN,
+                    &t!("store.auto_command_skipped", file = path)
+                )
+            );
+            continue;
+        }
+        track_file(
+            store,
+            wt_root,
+            path,
+            None,
+            &strategy,
+            &PathFilter::default(),
+            exec,
+        )?;
+        count += 1;
+    }
+
+    if count == 0 {
+        anstream::println!("{}", t!("store.no_auto_matches"));
+    }
+    Ok(())
+}
+
+/// 空のコマンドは何も出力しないので登録させない。
+fn validate_command(command: &str) -> Result<()> {
+    if command.trim().is_empty() {
+        bail!("{}", t!("store.command_invalid"));
+    }
+    Ok(())
+}
+
+/// command strategy で登録する。ファイルは worktree になくてよく、
+/// 登録時にコマンドを一度実行して worktree と store のキャッシュに書き込む。
+fn track_command(
+    store: &Path,
+    wt_root: &Path,
+    file: &str,
+    project: Option<&str>,
+    command: &str,
+    exec: &Executor,
+) -> Result<()> {
+    validate_command(command)?;
+    let target = worktree_path(wt_root, file)?;
+
+    let mut entries = if manifest_exists(store) {
+        read_manifest(store)?
+    } else {
+        Vec::new()
+    };
+    let pos = match entries.iter().position(|e| e.filepath == file) {
+        Some(pos) => pos,
+        None => {
+            entries.push(ManifestEntry::new(Strategy::Command, file));
+            entries.len() - 1
+        }
+    };
+    let entry = &mut entries[pos];
+    entry.strategy = Strategy::Command;
+    entry.command = Some(command.to_string());
+    entry.mode = file_mode(&target).or(entry.mode);
+    entry.hash = None;
+    if project.is_some() {
+        entry.project = project.map(str::to_string);
+    }
+
+    let log = OpLog::new(store, Some(wt_root), exec);
+    let before = log.hash(&store.join(file));
+    generate_file(entry, store, wt_root, exec)?;
+    save_manifest(store, &entries, exec)?;
+    log.record(Action::Track, file, before, log.hash(&store.join(file)));
+
+    anstream::println!(
+        "{}",
+        ui::styled(
+            ui::STYLE_OK,
+            &t!(
+                "store.tracking_started",
+                strategy = Strategy::Command.as_str(),
+                file = file
+            )
+        )
+    );
+    Ok(())
+}
 
-    let strategy = &cmd.strategy;
+/// manifest を書き込む（dry-run では書き込む旨を表示するだけ）。
+fn save_manifest(store: &Path, entries: &[ManifestEntry], exec: &Executor) -> Result<()> {
+    exec.run(format!("write {}", manifest_path(store).display()), || {
+        write_manifest(store, entries)
+    })?;
+    // 書き込んだ manifest.toml が旧形式の manifest を置き換える
+    retire_legacy_manifest(store, exec)
+}
 
-    let source = wt_root.join(&cmd.file);
+/// `wt_root` 内のファイルを store に登録する。`ws store track` と `ws store suggest` で共用。
+///
+/// `project` を指定すると、エントリをそのサブプロジェクトに属するものとして記録する。
+/// `filter` が空でなければディレクトリのエントリの include / exclude を置き換え、
+/// 空なら既存のエントリの指定を引き継ぐ。
+pub fn track_file(
+    store: &Path,
+    wt_root: &Path,
+    file: &str,
+    project: Option<&str>,
+    strategy: &Strategy,
+    filter: &PathFilter,
+    exec: &Executor,
+) -> Result<()> {
+    let source = worktree_path(wt_root, file)?;
     if !path_or_symlink_exists(&source) {
-        bail!("{}", t!("store.file_not_found", file = &cmd.file));
+        bail!("{}", t!("store.file_not_found", file = file));
+    }
+
+    // manifest を読む（dry-run では store が未作成のことがある）
+    let mut entries = if manifest_exists(store) {
+        read_manifest(store)?
+    } else {
+        Vec::new()
+    };
+    let filter = if filter.is_empty() {
+        entries
+            .iter()
+            .find(|e| e.filepath == file)
+            .map(|e| e.filter.clone())
+            .unwrap_or_default()
+    } else {
+        filter.clone()
+    };
+    if !filter.is_empty() {
+        if !strategy.supports_filter() {
+            bail!(
+                "{}",
+                t!(
+                    "store.filter_unsupported",
+                    file = file,
+                    strategy = strategy.as_str()
+                )
+            );
+        }
+        if !source.is_dir() {
+            bail!("{}", t!("store.filter_requires_dir", file = file));
+        }
+    }
+
+    if *strategy == Strategy::Dotenv && !source.is_file() {
+        bail!("{}", t!("store.dotenv_requires_file", file = file));
     }
 
+    if *strategy == Strategy::LinkTree && (!source.is_dir() || source.is_symlink()) {
+        bail!("{}", t!("store.link_tree_requires_dir", file = file));
+    }
+
+    if *strategy == Strategy::MergeJson
+        && fs::read_to_string(&source)
+            .ok()
+            .and_then(|content| crate::json::parse_object(&content))
+            .is_none()
+    {
+        bail!("{}", t!("store.merge_json_invalid", file = file));
+    }
+
+    check_track_limits(&source, file, strategy)?;
+
+    let mode = file_mode(&source);
+    // store には source と同じ内容を置くので、source のハッシュを記録する
+    let hash = if strategy.records_hash() {
+        hash::hash_path_filtered(&source, &filter).ok()
+    } else {
+        None
+    };
+
     // manifest を更新
-    let mut entries = read_manifest(&store)?;
     let mut found = false;
     for entry in entries.iter_mut() {
-        if entry.filepath == cmd.file {
+        if entry.filepath == file {
             entry.strategy = strategy.clone();
+            entry.mode = mode;
+            entry.command = None;
+            entry.hash = hash.clone();
+            entry.filter = filter.clone();
+            for variant in &mut entry.variants {
+                variant.filter = filter.clone();
+            }
+            if project.is_some() {
+                entry.project = project.map(str::to_string);
+            }
             found = true;
             break;
         }
     }
     if !found {
-        entries.push(ManifestEntry {
-            strategy: strategy.clone(),
-            filepath: cmd.file.clone(),
-        });
+        let mut entry = ManifestEntry::new(strategy.clone(), file);
+        entry.mode = mode;
+        entry.hash = hash;
+        entry.project = project.map(str::to_string);
+        entry.filter = filter.clone();
+        entries.push(entry);
     }
-    write_manifest(&store, &entries)?;
+    save_manifest(store, &entries, exec)?;
 
     // store にコピー
-    let store_file = store.join(&cmd.file);
+    let store_file = store.join(file);
+    let log = OpLog::new(store, Some(wt_root), exec);
+    let before = log.hash(&store_file);
     if let Some(parent) = store_file.parent() {
-        fs::create_dir_all(parent)?;
+        exec.create_dir_all(parent)?;
     }
 
     let is_symlink = source
@@ -52,36 +345,44 @@ pub fn cmd_store_track(cmd: &StoreTrackCmd) -> Result<()> {
         .map(|m| m.file_type().is_symlink())
         .unwrap_or(false);
 
-    let is_dir = source.is_dir();
-
-    if *strategy == Strategy::Symlink {
-        if is_dir {
-            copy_dir_recursive(&source, &store_file)
+    match strategy {
+        // link-tree は worktree の各ファイルを store に移して symlink にする
+        Strategy::LinkTree => {
+            exec.adopt_tree(&source, &store_file, &filter)
                 .context(t!("store.copy_to_store_failed").to_string())?;
-        } else {
-            fs::copy(&source, &store_file).context(t!("store.copy_to_store_failed").to_string())?;
         }
-
-        if !is_symlink {
-            if is_dir {
-                fs::remove_dir_all(&source)?;
-            } else {
-                fs::remove_file(&source)?;
+        // hardlink は worktree のファイルと store が同じ inode を共有する
+        Strategy::Hardlink if is_hardlinked(&store_file, &source) => {}
+        Strategy::Hardlink | Strategy::Clone => {
+            if path_or_symlink_exists(&store_file) {
+                exec.remove(&store_file)?;
             }
-            unix_fs::symlink(&store_file, &source)?;
-            anstream::println!(
-                "{}",
-                ui::styled(
-                    ui::STYLE_OK,
-                    &t!("store.converted_to_symlink", file = &cmd.file)
-                )
-            );
+            exec.place(strategy, &source, &store_file, &filter)
+                .context(t!("store.copy_to_store_failed").to_string())?;
         }
-    } else if is_dir {
-        copy_dir_recursive(&source, &store_file)
-            .context(t!("store.copy_to_store_failed").to_string())?;
-    } else {
-        fs::copy(&source, &store_file).context(t!("store.copy_to_store_failed").to_string())?;
+        _ => {
+            // 除外したファイルが store に残らないよう、ディレクトリは作り直す
+            if !filter.is_empty() && path_or_symlink_exists(&store_file) {
+                exec.remove(&store_file)?;
+            }
+            exec.copy_filtered(&source, &store_file, &filter)
+                .context(t!("store.copy_to_store_failed").to_string())?;
+        }
+    }
+    exec.apply_mode(&store_file, mode)?;
+
+    if *strategy == Strategy::MergeJson {
+        save_merge_base(store, wt_root, file, exec)?;
+    }
+    log.record(Action::Track, file, before, log.hash(&store_file));
+
+    if *strategy == Strategy::Symlink && !is_symlink {
+        exec.remove(&source)?;
+        exec.symlink(&store_file, &source)?;
+        anstream::println!(
+            "{}",
+            ui::styled(ui::STYLE_OK, &t!("store.converted_to_symlink", file = file))
+        );
     }
 
     anstream::println!(
@@ -91,18 +392,80 @@ pub fn cmd_store_track(cmd: &StoreTrackCmd) -> Result<()> {
             &t!(
                 "store.tracking_started",
                 strategy = strategy.as_str(),
-                file = &cmd.file
+                file = file
             )
         )
     );
     Ok(())
 }
 
+/// track 前にサイズとバイナリを確認する。
+/// `store.max_size` を超えるものは拒否し、`store.warn_size` を超える copy 系とバイナリは警告する。
+fn check_track_limits(source: &Path, file: &str, strategy: &Strategy) -> Result<()> {
+    let settings = crate::config::load_config()?.store;
+    let size = path_size(source);
+
+    if let Some(max) = settings.max_size()?
+        && size > max
+    {
+        bail!(
+            "{}",
+            t!(
+                "store.too_large",
+                file = file,
+                size = format_size(size),
+                limit = format_size(max)
+            )
+        );
+    }
+
+    let warn = settings.warn_size()?;
+    if size > warn && !matches!(strategy, Strategy::Symlink | Strategy::LinkTree) {
+        anstream::eprintln!(
+            "{}",
+            ui::styled(
+                ui::STYLE_WARN,
+                &t!(
+                    "store.large_file_warning",
+                    file = file,
+                    size = format_size(size),
+                    limit = format_size(warn)
+                )
+            )
+        );
+    }
+
+    if let Some(binary) = find_binary(source) {
+        let binary = binary
+            .strip_prefix(source.parent().unwrap_or(source))
+            .unwrap_or(&binary)
+            .display()
+            .to_string();
+        if *strategy == Strategy::Dotenv {
+            bail!("{}", t!("store.dotenv_binary", file = binary));
+        }
+        anstream::eprintln!(
+            "{}",
+            ui::styled(
+                ui::STYLE_WARN,
+                &t!("store.binary_file_warning", file = binary)
+            )
+        );
+    }
+    Ok(())
+}
+
 pub fn cmd_store_status() -> Result<()> {
     let store = require_store()?;
     let wt_root = worktree_root().ok();
+    let mut cache = load_hash_cache(&wt_root);
+    let profile = wt_root.as_deref().and_then(read_profile);
+    let branch = wt_root.as_deref().and_then(worktree_branch);
 
     anstream::println!("Store: {}", crate::context::abbreviate_home(&store));
+    if let Some(ref profile) = profile {
+        anstream::println!("Profile: {}", profile);
+    }
     anstream::println!();
 
     let entries = read_manifest(&store)?;
@@ -111,41 +474,251 @@ pub fn cmd_store_status() -> Result<()> {
         return Ok(());
     }
 
-    let mut rows = Vec::new();
+    let has_variants = entries.iter().any(|e| !e.variants.is_empty());
+    // サブプロジェクトがあれば、サブプロジェクトごとに表を分ける（root のエントリが先）
+    let grouped = entries.iter().any(|e| e.project.is_some());
+    let mut groups: BTreeMap<Option<&str>, Vec<Vec<StyledCell>>> = BTreeMap::new();
+    let mut total = 0u64;
     for entry in &entries {
-        let store_file = store.join(&entry.filepath);
-        let status = file_status(entry, &store_file, &wt_root);
-        rows.push(vec![
+        let variant = entry.for_branch(branch.as_deref());
+        let status = worktree_status(
+            entry,
+            &store,
+            &wt_root,
+            branch.as_deref(),
+            profile.as_deref(),
+            &mut cache,
+        );
+        let size = path_size(&variant.store_path(&store));
+        // 合計にはこの worktree で使わない variant も含める
+        total += std::iter::once(entry)
+            .chain(&entry.variants)
+            .map(|e| path_size(&e.store_path(&store)))
+            .sum::<u64>();
+        let file = if grouped {
+            entry.project_path()
+        } else {
+            &entry.filepath
+        };
+        let mut row = vec![
             StyledCell::plain(entry.strategy.to_string()),
-            StyledCell::plain(entry.filepath.clone()),
+            StyledCell::plain(file.to_string()),
             StyledCell::new(status.to_string(), ui::status_style(&status)),
+        ];
+        if has_variants {
+            row.push(ui::variant_cell(variant));
+        }
+        row.push(StyledCell::new(format_size(size), ui::STYLE_DIM));
+        let project = if grouped {
+            entry.project.as_deref()
+        } else {
+            None
+        };
+        let rows = groups.entry(project).or_default();
+        rows.push(row);
+        // link-tree はディレクトリ内のファイルごとの食い違いを続けて表示する
+        if variant.strategy == Strategy::LinkTree
+            && matches!(
+                status,
+                FileStatus::Missing | FileStatus::WrongLink | FileStatus::Ok
+            )
+            && let Some(ref root) = wt_root
+            && let Ok(wt_dir) = worktree_path(root, &variant.filepath)
+            && wt_dir.is_dir()
+        {
+            let store_dir = variant.store_path(&store);
+            for (rel, file_status) in link_tree_status(&store_dir, &wt_dir, &variant.filter) {
+                let mut row = vec![
+                    StyledCell::plain(""),
+                    StyledCell::new(format!("  {}/{}", file, rel), ui::STYLE_DIM),
+                    StyledCell::new(file_status.to_string(), ui::status_style(&file_status)),
+                ];
+                if has_variants {
+                    row.push(StyledCell::plain(""));
+                }
+                row.push(StyledCell::plain(""));
+                rows.push(row);
+            }
+        }
+    }
+
+    let headers: &[&str] = if has_variants {
+        &["STRATEGY", "FILE", "STATUS", "VARIANT", "SIZE"]
+    } else {
+        &["STRATEGY", "FILE", "STATUS", "SIZE"]
+    };
+    for (project, rows) in &groups {
+        if !grouped {
+            crate::context::print_table(headers, rows, 0, None);
+            continue;
+        }
+        let heading = match project {
+            Some(project) => format!("{}/", project),
+            None => t!("store.project_root").to_string(),
+        };
+        anstream::println!("{}", ui::styled(ui::STYLE_INFO, &heading));
+        crate::context::print_table(headers, rows, 2, None);
+        anstream::println!();
+    }
+    if !grouped {
+        anstream::println!();
+    }
+    anstream::println!(
+        "{}",
+        t!(
+            "store.total_size",
+            count = entries.len(),
+            size = format_size(total)
+        )
+    );
+
+    // キャッシュは次回を速くするためだけのものなので、書けなくても status は成功させる
+    let _ = cache.save();
+    Ok(())
+}
+
+/// `ws store verify` で各エントリを検査した結果。
+enum VerifyOutcome {
+    Ok,
+    Corrupted,
+    MissingStore,
+    Unverified,
+    Updated,
+}
+
+impl VerifyOutcome {
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Ok => "OK",
+            Self::Corrupted => "CORRUPTED",
+            Self::MissingStore => "MISSING(store)",
+            Self::Unverified => "UNVERIFIED",
+            Self::Updated => "UPDATED",
+        }
+    }
+
+    fn style(&self) -> anstyle::Style {
+        match self {
+            Self::Ok | Self::Updated => ui::STYLE_OK,
+            Self::Corrupted | Self::MissingStore => ui::STYLE_ERROR,
+            Self::Unverified => ui::STYLE_DIM,
+        }
+    }
+}
+
+/// store のマスターコピーを manifest のハッシュと照合する。`update` なら食い違うハッシュを記録し直す。
+fn verify_entry(entry: &mut ManifestEntry, store: &Path, update: bool) -> VerifyOutcome {
+    let store_file = entry.store_path(store);
+    let actual = if store_entry_exists(&store_file) {
+        hash::hash_path_filtered(&store_file, &entry.filter).ok()
+    } else {
+        None
+    };
+    match (actual, entry.hash.as_ref()) {
+        (None, _) => VerifyOutcome::MissingStore,
+        (Some(actual), Some(expected)) if actual == *expected => VerifyOutcome::Ok,
+        (Some(actual), _) if update => {
+            entry.hash = Some(actual);
+            VerifyOutcome::Updated
+        }
+        (Some(_), Some(_)) => VerifyOutcome::Corrupted,
+        (Some(_), None) => VerifyOutcome::Unverified,
+    }
+}
+
+/// store の内容を manifest に記録したハッシュと照合する。
+///
+/// `--update` を付けると、現在の store の内容を正しいものとしてハッシュを記録し直す
+/// （ハッシュのない旧形式の manifest の移行や、store を直接編集したとき用）。
+/// worktree から store を直接書き換えられる strategy（symlink / hardlink）と command は検査しない。
+pub fn cmd_store_verify(cmd: &StoreVerifyCmd, exec: &Executor) -> Result<()> {
+    let store = require_store()?;
+    let _lock = if cmd.update {
+        lock_store(&store)?
+    } else {
+        lock_store_shared(&store)?
+    };
+    let mut entries = read_manifest(&store)?;
+    if let Some(ref file) = cmd.file
+        && !entries.iter().any(|e| e.filepath == *file)
+    {
+        bail!("{}", t!("store.not_tracked", file = file));
+    }
+
+    let mut rows = Vec::new();
+    let mut failed = 0usize;
+    let mut updated = 0usize;
+    let mut record = |entry: &ManifestEntry, outcome: VerifyOutcome| {
+        match outcome {
+            VerifyOutcome::Corrupted | VerifyOutcome::MissingStore => failed += 1,
+            VerifyOutcome::Updated => updated += 1,
+            _ => {}
+        }
+        rows.push(vec![
+            StyledCell::plain(entry.strategy.to_string()),
+            StyledCell::plain(entry.display_path()),
+            StyledCell::new(outcome.label(), outcome.style()),
         ]);
+    };
+    for entry in entries.iter_mut() {
+        if cmd.file.as_ref().is_some_and(|f| entry.filepath != *f) || !entry.strategy.records_hash()
+        {
+            continue;
+        }
+        let outcome = verify_entry(entry, &store, cmd.update);
+        record(entry, outcome);
+        // variant も store の内容なので、元のエントリと同じように検査する
+        for variant in entry.variants.iter_mut() {
+            let outcome = verify_entry(variant, &store, cmd.update);
+            record(variant, outcome);
+        }
     }
 
-    crate::context::print_table(&["STRATEGY", "FILE", "STATUS"], &rows, 0, None);
+    if rows.is_empty() {
+        anstream::println!("{}", t!("store.no_files_to_verify"));
+        return Ok(());
+    }
+    crate::context::print_table(&["STRATEGY", "FILE", "RESULT"], &rows, 0, None);
+    if updated > 0 {
+        save_manifest(&store, &entries, exec)?;
+    }
 
+    anstream::println!();
+    if failed > 0 {
+        bail!("{}", t!("store.verify_failed", count = failed));
+    }
+    anstream::println!(
+        "{}",
+        ui::styled(ui::STYLE_OK, &t!("store.verify_ok", count = rows.len()))
+    );
     Ok(())
 }
 
-pub fn cmd_store_push(cmd: &StorePushCmd) -> Result<()> {
+pub fn cmd_store_push(cmd: &StorePushCmd, exec: &Executor) -> Result<()> {
     let store = require_store()?;
+    let _lock = lock_store(&store)?;
     let wt_root = worktree_root()?;
-    let entries = read_manifest(&store)?;
+    let branch = worktree_branch(&wt_root);
+    let mut entries = read_manifest(&store)?;
+    let log = OpLog::new(&store, Some(wt_root.as_path()), exec);
 
     let mut pushed = 0u32;
+    let mut unchanged = 0u32;
 
-    for entry in &entries {
-        if entry.strategy != Strategy::Copy {
+    for entry in entries.iter_mut() {
+        if !entry.strategy.is_pushable() {
             continue;
         }
 
-        if let Some(ref target_file) = cmd.file
-            && entry.filepath != *target_file
-        {
-            continue;
+        match cmd.file {
+            Some(ref target_file) if entry.filepath != *target_file => continue,
+            None if !entry.strategy.syncs_by_default() => continue,
+            _ => {}
         }
 
-        let wt_file = wt_root.join(&entry.filepath);
+        // worktree に配置した variant に書き戻す
+        let entry = entry.for_branch_mut(branch.as_deref());
+        let wt_file = worktree_path(&wt_root, &entry.filepath)?;
         if !wt_file.is_file() && !wt_file.is_dir() {
             anstream::eprintln!(
                 "{}",
@@ -157,48 +730,130 @@ pub fn cmd_store_push(cmd: &StorePushCmd) -> Result<()> {
             continue;
         }
 
-        let store_file = store.join(&entry.filepath);
-        if wt_file.is_dir() {
-            if store_file.is_dir() {
-                fs::remove_dir_all(&store_file)?;
+        let store_file = entry.store_path(&store);
+        let before = log.hash(&store_file);
+        if entry.strategy == Strategy::Dotenv {
+            // 値が変わったキーと新しいキーだけを store に反映する
+            let store_content = fs::read_to_string(&store_file).unwrap_or_default();
+            let wt_content = fs::read_to_string(&wt_file)?;
+            let (merged, changed) = dotenv::merge(&store_content, &wt_content, true);
+            if changed == 0 {
+                // 反映するキーがなければ store も履歴も変えない
+                anstream::println!(
+                    "{}",
+                    t!("store.pushed_keys", file = entry.display_path(), count = 0)
+                );
+                unchanged += 1;
+                continue;
+            }
+            entry.hash = Some(hash::hash_bytes(&merged));
+            exec.write(&store_file, merged)?;
+            anstream::println!(
+                "{}",
+                ui::styled(
+                    ui::STYLE_OK,
+                    &t!(
+                        "store.pushed_keys",
+                        file = entry.display_path(),
+                        count = changed
+                    )
+                )
+            );
+        } else if entry.strategy == Strategy::MergeJson {
+            // worktree 固有のキー（overlay）は store に入れず、base のキーの変更だけを反映する
+            let (merged, changed) =
+                merge_json_push(&store_file, &wt_root, &entry.filepath, cmd.include_overlay)?;
+            if changed == 0 {
+                anstream::println!(
+                    "{}",
+                    t!("store.pushed_keys", file = entry.display_path(), count = 0)
+                );
+                unchanged += 1;
+                continue;
+            }
+            entry.hash = Some(hash::hash_bytes(&merged));
+            exec.write(&store_file, merged)?;
+            // 反映した内容が新しい base になる
+            save_merge_base(&store, &wt_root, &entry.filepath, exec)?;
+            anstream::println!(
+                "{}",
+                ui::styled(
+                    ui::STYLE_OK,
+                    &t!(
+                        "store.pushed_keys",
+                        file = entry.display_path(),
+                        count = changed
+                    )
+                )
+            );
+        } else if entry.strategy == Strategy::Hardlink {
+            // リンクが切れている（エディタが別ファイルとして保存したなど）ときだけ反映する。
+            // store の inode に上書きするので、リンクを共有している他の worktree にも届く
+            if !is_hardlinked(&store_file, &wt_file) {
+                exec.copy_filtered(&wt_file, &store_file, &entry.filter)?;
+                exec.remove_entry(&wt_file, &entry.filter)?;
+                exec.place(&entry.strategy, &store_file, &wt_file, &entry.filter)?;
             }
-            copy_dir_recursive(&wt_file, &store_file)?;
+            anstream::println!(
+                "{}",
+                ui::styled(ui::STYLE_OK, &format!("push: {}", entry.display_path()))
+            );
         } else {
-            fs::copy(&wt_file, &store_file)?;
+            // reflink は既存ファイルに上書きできないので clone は作り直す
+            if (wt_file.is_dir() && store_file.is_dir())
+                || (entry.strategy == Strategy::Clone && path_or_symlink_exists(&store_file))
+            {
+                exec.remove(&store_file)?;
+            }
+            exec.place(&entry.strategy, &wt_file, &store_file, &entry.filter)?;
+            entry.hash = hash::hash_path_filtered(&wt_file, &entry.filter).ok();
+            anstream::println!(
+                "{}",
+                ui::styled(ui::STYLE_OK, &format!("push: {}", entry.display_path()))
+            );
         }
-        anstream::println!(
-            "{}",
-            ui::styled(ui::STYLE_OK, &format!("push: {}", entry.filepath))
-        );
+        // worktree 側でのパーミッション変更も store に反映する
+        entry.mode = file_mode(&wt_file);
+        log.record(Action::Push, &entry.filepath, before, log.hash(&store_file));
         pushed += 1;
     }
 
-    if pushed == 0 {
+    if pushed > 0 {
+        save_manifest(&store, &entries, exec)?;
+    }
+
+    if pushed == 0 && unchanged == 0 {
         if let Some(ref target_file) = cmd.file {
-            bail!("{}", t!("store.not_copy_tracked", file = target_file));
+            bail!("{}", t!("store.not_push_tracked", file = target_file));
         } else {
-            anstream::println!("{}", t!("store.no_copy_files_to_push"));
+            anstream::println!("{}", t!("store.no_files_to_push"));
         }
     }
 
     Ok(())
 }
 
-pub fn cmd_store_pull(cmd: &StorePullCmd) -> Result<()> {
+pub fn cmd_store_pull(cmd: &StorePullCmd, exec: &Executor) -> Result<()> {
     let store = require_store()?;
+    let _lock = lock_store_shared(&store)?;
     let wt_root = worktree_root()?;
+    let branch = worktree_branch(&wt_root);
     let entries = read_manifest(&store)?;
+    let profile = read_profile(&wt_root);
+    let log = OpLog::new(&store, Some(wt_root.as_path()), exec);
 
     let mut pulled = 0u32;
 
     for entry in &entries {
-        if let Some(ref target_file) = cmd.file
-            && entry.filepath != *target_file
-        {
-            continue;
+        match cmd.file {
+            Some(ref target_file) if entry.filepath != *target_file => continue,
+            None if !entry.strategy.syncs_by_default() => continue,
+            None if !entry.in_profile(profile.as_deref()) => continue,
+            _ => {}
         }
 
-        let store_file = store.join(&entry.filepath);
+        let entry = entry.for_branch(branch.as_deref());
+        let store_file = entry.store_path(&store);
         if !store_entry_exists(&store_file) {
             anstream::eprintln!(
                 "{}",
@@ -210,8 +865,44 @@ pub fn cmd_store_pull(cmd: &StorePullCmd) -> Result<()> {
             continue;
         }
 
-        let wt_file = wt_root.join(&entry.filepath);
+        let wt_file = worktree_path(&wt_root, &entry.filepath)?;
         let wt_exists = path_or_symlink_exists(&wt_file);
+        let before = log.hash(&wt_file);
+
+        // dotenv はローカルの値を残したまま、store にだけあるキーを追加する
+        if entry.strategy == Strategy::Dotenv && wt_file.is_file() && !cmd.force {
+            let local = fs::read_to_string(&wt_file)?;
+            let shared = fs::read_to_string(&store_file)?;
+            let (merged, added) = dotenv::merge(&local, &shared, false);
+            if added > 0 {
+                exec.write(&wt_file, merged)?;
+                log.record(Action::Pull, &entry.filepath, before, log.hash(&wt_file));
+            }
+            anstream::println!(
+                "{}",
+                ui::styled(
+                    ui::STYLE_OK,
+                    &t!("store.pulled_keys", file = &entry.filepath, count = added)
+                )
+            );
+            pulled += 1;
+            continue;
+        }
+
+        // merge-json は新しい base に worktree 側の変更分を重ね直す
+        if entry.strategy == Strategy::MergeJson && wt_file.is_file() && !cmd.force {
+            merge_json_file(&store, &wt_root, &entry.filepath, exec)?;
+            log.record(Action::Pull, &entry.filepath, before, log.hash(&wt_file));
+            anstream::println!(
+                "{}",
+                ui::styled(
+                    ui::STYLE_OK,
+                    &format!("pull ({}): {}", entry.strategy, entry.filepath)
+                )
+            );
+            pulled += 1;
+            continue;
+        }
 
         if wt_exists && !cmd.force {
             anstream::eprintln!(
@@ -224,38 +915,31 @@ pub fn cmd_store_pull(cmd: &StorePullCmd) -> Result<()> {
             continue;
         }
 
-        if wt_exists {
-            if wt_file.is_dir() {
-                let _ = fs::remove_dir_all(&wt_file);
-            } else {
-                let _ = fs::remove_file(&wt_file);
-            }
+        // 除外したファイルは worktree 固有のものとして残す。
+        // link-tree は store のファイルだけを張り直すので、worktree 固有のファイルは消さない
+        if wt_exists && entry.strategy != Strategy::LinkTree {
+            let _ = exec.remove_entry(&wt_file, &entry.filter);
         }
 
         if let Some(parent) = wt_file.parent() {
-            fs::create_dir_all(parent)?;
+            exec.create_dir_all(parent)?;
         }
 
-        match entry.strategy {
-            Strategy::Symlink => {
-                unix_fs::symlink(&store_file, &wt_file)?;
-                anstream::println!(
-                    "{}",
-                    ui::styled(ui::STYLE_OK, &format!("pull (symlink): {}", entry.filepath))
-                );
-            }
-            Strategy::Copy => {
-                if store_file.is_dir() {
-                    copy_dir_recursive(&store_file, &wt_file)?;
-                } else {
-                    fs::copy(&store_file, &wt_file)?;
-                }
-                anstream::println!(
-                    "{}",
-                    ui::styled(ui::STYLE_OK, &format!("pull (copy): {}", entry.filepath))
-                );
-            }
+        exec.place(&entry.strategy, &store_file, &wt_file, &entry.filter)?;
+        if !entry.strategy.shares_store() {
+            exec.apply_mode(&wt_file, entry.mode)?;
+        }
+        if entry.strategy == Strategy::MergeJson {
+            save_merge_base(&store, &wt_root, &entry.filepath, exec)?;
         }
+        log.record(Action::Pull, &entry.filepath, before, log.hash(&wt_file));
+        anstream::println!(
+            "{}",
+            ui::styled(
+                ui::STYLE_OK,
+                &format!("pull ({}): {}", entry.strategy, entry.filepath)
+            )
+        );
         pulled += 1;
     }
 
@@ -270,71 +954,333 @@ pub fn cmd_store_pull(cmd: &StorePullCmd) -> Result<()> {
     Ok(())
 }
 
-/// symlink strategy のファイルについて、全 worktree 内の symlink を実ファイルに復元する。
-fn restore_symlinks_to_files(store: &Path, entry: &ManifestEntry) -> Result<()> {
-    if entry.strategy != Strategy::Symlink {
-        return Ok(());
+/// manifest の内容を現在の worktree に適用する。
+///
+/// 存在しないファイルは `ws new` と同じように配置し、command strategy のファイルは
+/// コマンドを実行し直して常に作り直す。ファイル指定なしのときは worktree の profile に
+/// 含まれるエントリだけを対象にする。`--profile` / `--no-profile` は worktree の profile を記録し直す。
+pub fn cmd_store_apply(cmd: &StoreApplyCmd, exec: &Executor) -> Result<()> {
+    let store = require_store()?;
+    // command の出力で store のキャッシュを書き換えるので排他ロックを取る
+    let _lock = lock_store(&store)?;
+    let wt_root = worktree_root()?;
+    let branch = worktree_branch(&wt_root);
+    let entries = read_manifest(&store)?;
+
+    let profile = match cmd.profile {
+        Some(ref profile) => {
+            validate_profile(profile)?;
+            require_profile(&entries, profile)?;
+            Some(profile.clone())
+        }
+        None if cmd.no_profile => None,
+        None => read_profile(&wt_root),
+    };
+    if cmd.profile.is_some() || cmd.no_profile {
+        save_profile(&wt_root, profile.as_deref(), exec)?;
+    }
+
+    let log = OpLog::new(&store, Some(wt_root.as_path()), exec);
+    let mut applied = 0u32;
+    for entry in &entries {
+        match cmd.file {
+            Some(ref target_file) if entry.filepath != *target_file => continue,
+            None if !entry.in_profile(profile.as_deref()) => continue,
+            _ => {}
+        }
+
+        let wt_file = wt_root.join(&entry.filepath);
+        let before = log.hash(&wt_file);
+        if entry.strategy == Strategy::Command {
+            generate_file(entry, &store, &wt_root, exec)?;
+            log.record(Action::Apply, &entry.filepath, before, log.hash(&wt_file));
+            anstream::println!(
+                "{}",
+                ui::styled(
+                    ui::STYLE_OK,
+                    &format!("apply ({}): {}", entry.strategy, entry.filepath)
+                )
+            );
+            applied += 1;
+        } else if entry.strategy == Strategy::MergeJson && wt_file.is_file() {
+            merge_json_file(&store, &wt_root, &entry.filepath, exec)?;
+            log.record(Action::Apply, &entry.filepath, before, log.hash(&wt_file));
+            anstream::println!(
+                "{}",
+                ui::styled(
+                    ui::STYLE_OK,
+                    &format!("apply ({}): {}", entry.strategy, entry.filepath)
+                )
+            );
+            applied += 1;
+        } else if !path_or_symlink_exists(&wt_file) {
+            apply_file(entry, &store, &wt_root, branch.as_deref(), exec)?;
+            log.record(Action::Apply, &entry.filepath, before, log.hash(&wt_file));
+            applied += 1;
+        } else if cmd.file.is_some() {
+            anstream::eprintln!(
+                "{}",
+                ui::styled(
+                    ui::STYLE_WARN,
+                    &t!("store.skip_exists", file = &entry.filepath)
+                )
+            );
+            applied += 1;
+        }
+    }
+
+    if applied == 0 {
+        if let Some(ref target_file) = cmd.file {
+            bail!("{}", t!("store.not_tracked", file = target_file));
+        } else {
+            anstream::println!("{}", t!("store.no_files_to_apply"));
+        }
     }
 
-    let store_file = store.join(&entry.filepath);
-    if !store_entry_exists(&store_file) {
+    Ok(())
+}
+
+pub fn cmd_store_diff(cmd: &StoreDiffCmd) -> Result<()> {
+    let store = require_store()?;
+    let wt_root = worktree_root()?;
+    let branch = worktree_branch(&wt_root);
+    let entries = read_manifest(&store)?;
+
+    let targets: Vec<&ManifestEntry> = match cmd.file {
+        Some(ref file) => {
+            let entry = entries
+                .iter()
+                .find(|e| e.filepath == *file)
+                .ok_or_else(|| anyhow::anyhow!("{}", t!("store.not_tracked", file = file)))?;
+            if entry.strategy != Strategy::Dotenv {
+                bail!("{}", t!("store.diff_dotenv_only", file = file));
+            }
+            vec![entry]
+        }
+        None => entries
+            .iter()
+            .filter(|e| e.strategy == Strategy::Dotenv)
+            .collect(),
+    };
+
+    if targets.is_empty() {
+        anstream::println!("{}", t!("store.no_dotenv_files"));
         return Ok(());
     }
 
-    let wt_list = git_output(&["worktree", "list"])?;
-    for line in wt_list.lines() {
-        if line.contains("(bare)") {
+    for (i, entry) in targets.iter().enumerate() {
+        if i > 0 {
+            anstream::println!();
+        }
+        let variant = entry.for_branch(branch.as_deref());
+        anstream::println!("{}", ui::styled(ui::STYLE_HEADER, &variant.display_path()));
+
+        let store_content = fs::read_to_string(variant.store_path(&store)).unwrap_or_default();
+        let Ok(wt_content) = fs::read_to_string(wt_root.join(&entry.filepath)) else {
+            anstream::println!(
+                "  {}",
+                ui::styled(ui::STYLE_ERROR, &FileStatus::Missing.to_string())
+            );
+            continue;
+        };
+
+        let changes = dotenv::diff(&store_content, &wt_content);
+        if changes.is_empty() {
+            anstream::println!("  {}", t!("store.no_key_differences"));
             continue;
         }
-        let wt_path = match line.split_whitespace().next() {
-            Some(p) => p,
-            None => continue,
+        for change in &changes {
+            let line = match change {
+                KeyChange::Added { key, value } => ui::styled(
+                    ui::STYLE_OK,
+                    &format!("+ {}={}", key, dotenv::mask(value, cmd.show_values)),
+                ),
+                KeyChange::Removed { key, value } => ui::styled(
+                    ui::STYLE_ERROR,
+                    &format!("- {}={}", key, dotenv::mask(value, cmd.show_values)),
+                ),
+                KeyChange::Changed {
+                    key,
+                    store_value,
+                    worktree_value,
+                } => ui::styled(
+                    ui::STYLE_WARN,
+                    &format!(
+                        "~ {}={} -> {}",
+                        key,
+                        dotenv::mask(store_value, cmd.show_values),
+                        dotenv::mask(worktree_value, cmd.show_values)
+                    ),
+                ),
+            };
+            anstream::println!("  {}", line);
+        }
+    }
+
+    Ok(())
+}
+
+/// untrack 時に各 worktree で行った処理の結果。
+enum UntrackOutcome {
+    Restored,
+    Removed,
+    Kept,
+    Absent,
+    Failed,
+    /// 他の worktree が失敗したので何もしなかった
+    Skipped,
+}
+
+impl UntrackOutcome {
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Restored => "RESTORED",
+            Self::Removed => "REMOVED",
+            Self::Kept => "KEPT",
+            Self::Absent => "ABSENT",
+            Self::Failed => "FAILED",
+            Self::Skipped => "SKIPPED",
+        }
+    }
+
+    fn style(&self) -> anstyle::Style {
+        match self {
+            Self::Restored | Self::Removed => ui::STYLE_OK,
+            Self::Kept | Self::Absent | Self::Skipped => ui::STYLE_DIM,
+            Self::Failed => ui::STYLE_ERROR,
+        }
+    }
+}
+
+/// untrack で 1 つの worktree に対して行う処理。
+enum UntrackStep {
+    /// store を指す symlink を store の内容のコピーで置き換える
+    Restore {
+        target: PathBuf,
+        store_file: PathBuf,
+    },
+    /// ファイル・ディレクトリ・symlink を削除する
+    Purge { target: PathBuf },
+    /// link-tree のディレクトリで、store を指す symlink をコピーで置き換える（`purge` なら削除する）。
+    /// worktree 固有のファイルは残す
+    Detach {
+        target: PathBuf,
+        store_file: PathBuf,
+        purge: bool,
+    },
+    /// 何もしない（`Kept` / `Absent` / `Failed` のいずれか）
+    Skip(UntrackOutcome),
+}
+
+impl UntrackStep {
+    /// 変更を加える前に、この worktree で何をするかを決める。
+    ///
+    /// restore は store を指す symlink だけを置き換え、通常ファイル（copy strategy や手動で
+    /// 置き換えたもの）はそのまま残す。link-tree のディレクトリは中の symlink を 1 つずつ処理する。
+    fn plan(wt_path: &Path, file: &str, store_file: PathBuf, purge: bool) -> Self {
+        // symlink のディレクトリの先は worktree の外なので触らない
+        let Ok(target) = worktree_path(wt_path, file) else {
+            return Self::Skip(UntrackOutcome::Failed);
         };
-        let target = std::path::Path::new(wt_path).join(&entry.filepath);
-        let is_link = target
-            .symlink_metadata()
-            .map(|m| m.file_type().is_symlink())
-            .unwrap_or(false);
-        if is_link {
-            let _ = fs::remove_file(&target);
-            if let Some(parent) = target.parent() {
-                let _ = fs::create_dir_all(parent);
+        if !path_or_symlink_exists(&target) {
+            return Self::Skip(UntrackOutcome::Absent);
+        }
+        if is_real_dir(&target) && tree_links_into(&target, &store_file) {
+            if !purge && !store_entry_exists(&store_file) {
+                return Self::Skip(UntrackOutcome::Failed);
             }
-            let copy_result: Result<()> = if store_file.is_dir() {
-                copy_dir_recursive(&store_file, &target)
-            } else {
-                fs::copy(&store_file, &target)
-                    .map(|_| ())
-                    .map_err(Into::into)
+            return Self::Detach {
+                target,
+                store_file,
+                purge,
             };
-            match copy_result {
-                Ok(()) => anstream::println!(
-                    "{}",
-                    ui::styled(
-                        ui::STYLE_OK,
-                        &t!(
-                            "store.symlink_restored",
-                            file = &entry.filepath,
-                            path = wt_path
-                        )
-                    )
-                ),
-                Err(_) => anstream::eprintln!(
-                    "{}",
-                    ui::styled(
-                        ui::STYLE_ERROR,
-                        &t!(
-                            "store.restore_copy_failed",
-                            file = &entry.filepath,
-                            path = wt_path
-                        )
-                    )
-                ),
+        }
+        if purge {
+            return Self::Purge { target };
+        }
+        let points_to_store = fs::read_link(&target)
+            .map(|link| link.starts_with(&store_file))
+            .unwrap_or(false);
+        if !points_to_store {
+            return Self::Skip(UntrackOutcome::Kept);
+        }
+        if !store_entry_exists(&store_file) {
+            return Self::Skip(UntrackOutcome::Failed);
+        }
+        Self::Restore { target, store_file }
+    }
+
+    fn target(&self) -> Option<&Path> {
+        match self {
+            Self::Restore { target, .. } | Self::Purge { target } | Self::Detach { target, .. } => {
+                Some(target)
             }
+            Self::Skip(_) => None,
         }
     }
 
-    Ok(())
+    fn is_failed(&self) -> bool {
+        matches!(self, Self::Skip(UntrackOutcome::Failed))
+    }
+
+    fn outcome(&self) -> UntrackOutcome {
+        match self {
+            Self::Restore { .. } | Self::Detach { purge: false, .. } => UntrackOutcome::Restored,
+            Self::Purge { .. } | Self::Detach { purge: true, .. } => UntrackOutcome::Removed,
+            Self::Skip(UntrackOutcome::Kept) => UntrackOutcome::Kept,
+            Self::Skip(UntrackOutcome::Absent) => UntrackOutcome::Absent,
+            Self::Skip(_) => UntrackOutcome::Failed,
+        }
+    }
+
+    /// 元のファイルを退避してから処理する。退避先は全 worktree が終わってから消すので、
+    /// 途中で失敗しても `undo` で元に戻せる。
+    fn apply(&self, exec: &Executor) -> Result<()> {
+        let Some(target) = self.target() else {
+            return Ok(());
+        };
+        let backup = untrack_backup(target);
+        exec.rename(target, &backup)?;
+        match self {
+            Self::Restore { target, store_file } => exec.copy(store_file, target),
+            Self::Detach {
+                target,
+                store_file,
+                purge,
+            } => exec.detach_tree(&backup, target, store_file, *purge),
+            _ => Ok(()),
+        }
+    }
+
+    fn undo(&self, exec: &Executor) -> Result<()> {
+        let Some(target) = self.target() else {
+            return Ok(());
+        };
+        let backup = untrack_backup(target);
+        if !path_or_symlink_exists(&backup) {
+            return Ok(());
+        }
+        if path_or_symlink_exists(target) {
+            exec.remove(target)?;
+        }
+        exec.rename(&backup, target)
+    }
+
+    fn discard_backup(&self, exec: &Executor) -> Result<()> {
+        match self.target() {
+            Some(target) => exec.remove(&untrack_backup(target)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// untrack 中に元のファイルを退避するパス（同じディレクトリ内なので rename で済む）。
+fn untrack_backup(target: &Path) -> PathBuf {
+    let name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    target.with_file_name(format!(".{}.ws-untrack-{}", name, std::process::id()))
 }
 
 /// 指定パスから親方向に辿り、空ディレクトリを削除する。stop_at で停止。
@@ -356,8 +1302,9 @@ fn cleanup_empty_parents(path: &Path, stop_at: &Path) {
     }
 }
 
-pub fn cmd_store_untrack(cmd: &StoreUntrackCmd) -> Result<()> {
+pub fn cmd_store_untrack(cmd: &StoreUntrackCmd, exec: &Executor) -> Result<()> {
     let store = require_store()?;
+    let _lock = lock_store(&store)?;
     let mut entries = read_manifest(&store)?;
 
     let pos = entries
@@ -365,24 +1312,1357 @@ pub fn cmd_store_untrack(cmd: &StoreUntrackCmd) -> Result<()> {
         .position(|e| e.filepath == cmd.file)
         .ok_or_else(|| anyhow::anyhow!("{}", t!("store.not_tracked", file = &cmd.file)))?;
 
-    let entry = &entries[pos];
-    restore_symlinks_to_files(&store, entry)?;
+    // worktree 側の処理は store を削除する前に行う（restore は store の内容を使う）。
+    // 一部の worktree だけ変わって manifest と食い違わないよう、先に全 worktree を確認する
+    let steps: Vec<(PathBuf, UntrackStep)> = worktree_paths()?
+        .into_iter()
+        .map(|wt_path| {
+            let branch = worktree_branch(&wt_path);
+            let store_file = entries[pos]
+                .for_branch(branch.as_deref())
+                .store_path(&store);
+            let step = UntrackStep::plan(&wt_path, &cmd.file, store_file, cmd.purge);
+            (wt_path, step)
+        })
+        .collect();
+    // 失敗した worktree があれば、それ以外の worktree は何もしなかったことを示す
+    let rows = |failed: &dyn Fn(usize) -> bool| -> Vec<Vec<StyledCell>> {
+        let any_failed = (0..steps.len()).any(failed);
+        steps
+            .iter()
+            .enumerate()
+            .map(|(i, (wt_path, step))| {
+                let outcome = if failed(i) {
+                    UntrackOutcome::Failed
+                } else if any_failed && step.target().is_some() {
+                    UntrackOutcome::Skipped
+                } else {
+                    step.outcome()
+                };
+                vec![
+                    StyledCell::plain(crate::context::abbreviate_home(wt_path)),
+                    StyledCell::new(outcome.label(), outcome.style()),
+                ]
+            })
+            .collect()
+    };
+    let report_failure = |wt_path: &Path| {
+        anstream::eprintln!(
+            "{}",
+            ui::styled(
+                ui::STYLE_ERROR,
+                &t!(
+                    "store.untrack_worktree_failed",
+                    file = &cmd.file,
+                    path = wt_path.display().to_string()
+                )
+            )
+        );
+    };
 
-    entries.remove(pos);
-    write_manifest(&store, &entries)?;
+    // 失敗する worktree では store が唯一のコピーかもしれないので、どこも変えずに manifest も store も残す
+    if steps.iter().any(|(_, step)| step.is_failed()) {
+        for (wt_path, _) in steps.iter().filter(|(_, step)| step.is_failed()) {
+            report_failure(wt_path);
+        }
+        crate::context::print_table(
+            &["WORKTREE", "RESULT"],
+            &rows(&|i| steps[i].1.is_failed()),
+            2,
+            None,
+        );
+        bail!("{}", t!("store.untrack_incomplete", file = &cmd.file));
+    }
 
-    let store_file = store.join(&cmd.file);
-    if store_file.is_dir() {
-        fs::remove_dir_all(&store_file)?;
-    } else if store_file.exists() {
-        fs::remove_file(&store_file)?;
+    let entry = entries.remove(pos);
+    let mut applied = 0;
+    let result = steps
+        .iter()
+        .try_for_each(|(_, step)| {
+            step.apply(exec)?;
+            applied += 1;
+            Ok(())
+        })
+        .and_then(|()| save_manifest(&store, &entries, exec));
+    if let Err(e) = result {
+        // 実行中に失敗したら、処理済みの worktree を元に戻す
+        let failed_at = applied.min(steps.len().saturating_sub(1));
+        for (_, step) in steps[..=failed_at].iter().rev() {
+            let _ = step.undo(exec);
+        }
+        if let Some((wt_path, _)) = steps.get(failed_at) {
+            report_failure(wt_path);
+        }
+        crate::context::print_table(&["WORKTREE", "RESULT"], &rows(&|i| i == failed_at), 2, None);
+        return Err(e.context(t!("store.untrack_incomplete", file = &cmd.file).to_string()));
+    }
+    for (_, step) in &steps {
+        step.discard_backup(exec)?;
     }
 
-    cleanup_empty_parents(&store_file, &store);
+    let log = OpLog::new(&store, worktree_root().ok().as_deref(), exec);
+    log.record(
+        Action::Untrack,
+        &entry.filepath,
+        log.hash(&entry.store_path(&store)),
+        None,
+    );
+
+    for store_file in std::iter::once(&entry)
+        .chain(&entry.variants)
+        .map(|e| e.store_path(&store))
+    {
+        if store_file.exists() {
+            exec.remove(&store_file)?;
+        }
+        if !exec.is_dry_run() {
+            cleanup_empty_parents(&store_file, &store);
+        }
+    }
 
     anstream::println!(
         "{}",
         ui::styled(ui::STYLE_OK, &t!("store.untrack_success", file = &cmd.file))
     );
+    if !steps.is_empty() {
+        anstream::println!();
+        crate::context::print_table(&["WORKTREE", "RESULT"], &rows(&|_| false), 2, None);
+    }
+    Ok(())
+}
+
+/// `ws store profile add`: manifest のエントリを profile に加える。
+pub fn cmd_store_profile_add(cmd: &StoreProfileEditCmd, exec: &Executor) -> Result<()> {
+    edit_profile(cmd, true, exec)
+}
+
+/// `ws store profile rm`: manifest のエントリを profile から外す。
+pub fn cmd_store_profile_rm(cmd: &StoreProfileEditCmd, exec: &Executor) -> Result<()> {
+    edit_profile(cmd, false, exec)
+}
+
+fn edit_profile(cmd: &StoreProfileEditCmd, add: bool, exec: &Executor) -> Result<()> {
+    validate_profile(&cmd.profile)?;
+    let store = require_store()?;
+    let _lock = lock_store(&store)?;
+    let mut entries = read_manifest(&store)?;
+    if let Some(file) = cmd
+        .files
+        .iter()
+        .find(|f| !entries.iter().any(|e| e.filepath == **f))
+    {
+        bail!("{}", t!("store.not_tracked", file = file));
+    }
+
+    for entry in entries
+        .iter_mut()
+        .filter(|e| cmd.files.contains(&e.filepath))
+    {
+        entry.profiles.retain(|p| *p != cmd.profile);
+        if add {
+            entry.profiles.push(cmd.profile.clone());
+        }
+    }
+    save_manifest(&store, &entries, exec)?;
+
+    let key = if add {
+        "store.profile_added"
+    } else {
+        "store.profile_removed"
+    };
+    anstream::println!(
+        "{}",
+        ui::styled(
+            ui::STYLE_OK,
+            &t!(key, profile = &cmd.profile, count = cmd.files.len())
+        )
+    );
+    Ok(())
+}
+
+/// `ws store profile list`: profile ごとのエントリを表示する。現在の worktree の profile に `*` を付ける。
+pub fn cmd_store_profile_list() -> Result<()> {
+    let store = require_store()?;
+    let entries = read_manifest(&store)?;
+    let current = worktree_root().ok().and_then(|root| read_profile(&root));
+
+    let mut profiles: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for entry in &entries {
+        for profile in &entry.profiles {
+            profiles.entry(profile).or_default().push(&entry.filepath);
+        }
+    }
+    if profiles.is_empty() {
+        anstream::println!("{}", t!("store.no_profiles"));
+        return Ok(());
+    }
+
+    let markers: Vec<bool> = profiles
+        .keys()
+        .map(|profile| current.as_deref() == Some(*profile))
+        .collect();
+    let rows: Vec<Vec<StyledCell>> = profiles
+        .into_iter()
+        .map(|(profile, files)| {
+            vec![
+                StyledCell::new(profile, ui::STYLE_INFO),
+                StyledCell::plain(files.join(", ")),
+            ]
+        })
+        .collect();
+    crate::context::print_table(&["PROFILE", "FILES"], &rows, 2, Some(&markers));
+    Ok(())
+}
+
+/// `ws store variant add`: 現在の worktree のファイルを、branch パターンに一致する worktree 用の
+/// variant として store に登録する。
+pub fn cmd_store_variant_add(cmd: &StoreVariantEditCmd, exec: &Executor) -> Result<()> {
+    validate_branch_pattern(&cmd.branch)?;
+    let store = require_store()?;
+    let _lock = lock_store(&store)?;
+    let wt_root = worktree_root()?;
+    let mut entries = read_manifest(&store)?;
+    let entry = entries
+        .iter_mut()
+        .find(|e| e.filepath == cmd.file)
+        .ok_or_else(|| anyhow::anyhow!("{}", t!("store.not_tracked", file = &cmd.file)))?;
+    if !entry.strategy.supports_variants() {
+        bail!(
+            "{}",
+            t!(
+                "store.variant_unsupported",
+                file = &cmd.file,
+                strategy = entry.strategy.as_str()
+            )
+        );
+    }
+    if entry
+        .variants
+        .iter()
+        .any(|v| v.branch.as_deref() == Some(cmd.branch.as_str()))
+    {
+        bail!(
+            "{}",
+            t!(
+                "store.variant_exists",
+                file = &cmd.file,
+                branch = &cmd.branch
+            )
+        );
+    }
+
+    let source = worktree_path(&wt_root, &cmd.file)?;
+    if !path_or_symlink_exists(&source) {
+        bail!("{}", t!("store.file_not_found", file = &cmd.file));
+    }
+    check_track_limits(&source, &cmd.file, &entry.strategy)?;
+
+    let mut variant = ManifestEntry::new(entry.strategy.clone(), cmd.file.as_str());
+    variant.branch = Some(cmd.branch.clone());
+    variant.mode = file_mode(&source);
+    variant.filter = entry.filter.clone();
+    if variant.strategy.records_hash() {
+        variant.hash = hash::hash_path_filtered(&source, &variant.filter).ok();
+    }
+    let variant_file = variant.store_path(&store);
+    if let Some(parent) = variant_file.parent() {
+        exec.create_dir_all(parent)?;
+    }
+    // symlink の worktree でもリンク先の内容をコピーするので、元の store とは別の実体になる
+    exec.copy_filtered(&source, &variant_file, &variant.filter)
+        .context(t!("store.copy_to_store_failed").to_string())?;
+    exec.apply_mode(&variant_file, variant.mode)?;
+    entry.variants.push(variant);
+    save_manifest(&store, &entries, exec)?;
+    let log = OpLog::new(&store, Some(wt_root.as_path()), exec);
+    log.record(Action::VariantAdd, &cmd.file, None, log.hash(&variant_file));
+
+    // この worktree が新しい variant を使うなら、store を共有する strategy はリンクを張り直す
+    let entry = entries
+        .iter()
+        .find(|e| e.filepath == cmd.file)
+        .expect("entry was found above");
+    let branch = worktree_branch(&wt_root);
+    let applied = entry.for_branch(branch.as_deref());
+    if applied.branch.as_deref() == Some(cmd.branch.as_str()) && applied.strategy.shares_store() {
+        if applied.strategy != Strategy::LinkTree {
+            exec.remove_entry(&source, &applied.filter)?;
+        }
+        exec.place(&applied.strategy, &variant_file, &source, &applied.filter)?;
+    }
+
+    anstream::println!(
+        "{}",
+        ui::styled(
+            ui::STYLE_OK,
+            &t!(
+                "store.variant_added",
+                file = &cmd.file,
+                branch = &cmd.branch
+            )
+        )
+    );
+    Ok(())
+}
+
+/// `ws store variant rm`: variant を manifest と store から削除する。
+///
+/// その variant にリンクしていた worktree は、残った中から選び直した内容にリンクし直す。
+/// コピー系の worktree のファイルはそのまま残す。
+pub fn cmd_store_variant_rm(cmd: &StoreVariantEditCmd, exec: &Executor) -> Result<()> {
+    let store = require_store()?;
+    let _lock = lock_store(&store)?;
+    let mut entries = read_manifest(&store)?;
+    let entry = entries
+        .iter_mut()
+        .find(|e| e.filepath == cmd.file)
+        .ok_or_else(|| anyhow::anyhow!("{}", t!("store.not_tracked", file = &cmd.file)))?;
+    let pos = entry
+        .variants
+        .iter()
+        .position(|v| v.branch.as_deref() == Some(cmd.branch.as_str()))
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "{}",
+                t!(
+                    "store.variant_not_found",
+                    file = &cmd.file,
+                    branch = &cmd.branch
+                )
+            )
+        })?;
+    let variant_file = entry.variants[pos].store_path(&store);
+
+    // variant を外す前に、それを使っている worktree を調べておく
+    let mut users = Vec::new();
+    for wt_path in worktree_paths()? {
+        let branch = worktree_branch(&wt_path);
+        if entry.for_branch(branch.as_deref()).branch.as_deref() == Some(cmd.branch.as_str()) {
+            users.push((wt_path, branch));
+        }
+    }
+    entry.variants.remove(pos);
+
+    let mut rows = Vec::new();
+    for (wt_path, branch) in users {
+        let Ok(target) = worktree_path(&wt_path, &cmd.file) else {
+            rows.push(vec![
+                StyledCell::plain(crate::context::abbreviate_home(&wt_path)),
+                StyledCell::new(
+                    WorktreeOutcome::Failed.label(),
+                    WorktreeOutcome::Failed.style(),
+                ),
+            ]);
+            continue;
+        };
+        let linked = fs::read_link(&target)
+            .map(|link| link == variant_file)
+            .unwrap_or(false)
+            || is_hardlinked(&variant_file, &target);
+        let outcome = if !path_or_symlink_exists(&target) {
+            WorktreeOutcome::Absent
+        } else if !linked {
+            WorktreeOutcome::Unchanged
+        } else {
+            let applied = entry.for_branch(branch.as_deref());
+            let result = exec.remove_entry(&target, &applied.filter).and_then(|()| {
+                exec.place(
+                    &applied.strategy,
+                    &applied.store_path(&store),
+                    &target,
+                    &applied.filter,
+                )
+            });
+            match result {
+                Ok(()) => WorktreeOutcome::Relinked,
+                Err(_) => WorktreeOutcome::Failed,
+            }
+        };
+        rows.push(vec![
+            StyledCell::plain(crate::context::abbreviate_home(&wt_path)),
+            StyledCell::new(outcome.label(), outcome.style()),
+        ]);
+    }
+
+    save_manifest(&store, &entries, exec)?;
+    let log = OpLog::new(&store, worktree_root().ok().as_deref(), exec);
+    let before = log.hash(&variant_file);
+    if path_or_symlink_exists(&variant_file) {
+        exec.remove(&variant_file)?;
+    }
+    log.record(Action::VariantRm, &cmd.file, before, None);
+    if !exec.is_dry_run() {
+        cleanup_empty_parents(&variant_file, &store);
+    }
+
+    anstream::println!(
+        "{}",
+        ui::styled(
+            ui::STYLE_OK,
+            &t!(
+                "store.variant_removed",
+                file = &cmd.file,
+                branch = &cmd.branch
+            )
+        )
+    );
+    if !rows.is_empty() {
+        anstream::println!();
+        crate::context::print_table(&["WORKTREE", "RESULT"], &rows, 2, None);
+    }
+    Ok(())
+}
+
+/// `ws store variant list`: branch 別の variant を表示する。現在の worktree で使うものに `*` を付ける。
+pub fn cmd_store_variant_list() -> Result<()> {
+    let store = require_store()?;
+    let entries = read_manifest(&store)?;
+    let branch = worktree_root().ok().and_then(|root| worktree_branch(&root));
+
+    let mut rows = Vec::new();
+    let mut markers = Vec::new();
+    for entry in &entries {
+        let applied = entry.for_branch(branch.as_deref());
+        for variant in &entry.variants {
+            markers.push(std::ptr::eq(variant, applied));
+            rows.push(vec![
+                StyledCell::plain(variant.filepath.clone()),
+                ui::variant_cell(variant),
+                StyledCell::plain(variant.strategy.to_string()),
+                StyledCell::new(
+                    format_size(path_size(&variant.store_path(&store))),
+                    ui::STYLE_DIM,
+                ),
+            ]);
+        }
+    }
+    if rows.is_empty() {
+        anstream::println!("{}", t!("store.no_variants"));
+        return Ok(());
+    }
+    crate::context::print_table(
+        &["FILE", "BRANCH", "STRATEGY", "SIZE"],
+        &rows,
+        2,
+        Some(&markers),
+    );
+    Ok(())
+}
+
+/// `ws store log`: store の操作ログを新しい順に表示する。
+///
+/// 既定では store の内容を変えた操作だけを、`--ops` では pull・apply・recover も表示する。
+pub fn cmd_store_log(cmd: &StoreLogCmd) -> Result<()> {
+    let store = require_store()?;
+    let records = read_oplog(&store)?;
+    let repo_root = crate::git::resolve_repo_root(None);
+
+    let rows: Vec<Vec<StyledCell>> = records
+        .iter()
+        .rev()
+        .filter(|r| cmd.ops || r.action.changes_store())
+        .filter(|r| cmd.file.as_ref().is_none_or(|f| r.file == *f))
+        .take(cmd.limit.unwrap_or(usize::MAX))
+        .map(|r| {
+            vec![
+                StyledCell::new(r.time.as_str(), ui::STYLE_DIM),
+                StyledCell::new(r.action.as_str(), ui::STYLE_INFO),
+                StyledCell::plain(r.file.clone()),
+                StyledCell::plain(log_worktree(r, repo_root.as_deref())),
+                StyledCell::plain(r.branch.clone().unwrap_or_else(|| "-".to_string())),
+                StyledCell::plain(r.user.clone()),
+                log_change_cell(r),
+            ]
+        })
+        .collect();
+    if rows.is_empty() {
+        anstream::println!("{}", t!("store.no_log"));
+        return Ok(());
+    }
+    crate::context::print_table(
+        &[
+            "TIME", "ACTION", "FILE", "WORKTREE", "BRANCH", "USER", "CONTENT",
+        ],
+        &rows,
+        2,
+        None,
+    );
+    Ok(())
+}
+
+/// 操作した worktree をリポジトリのルートからの相対パスで表す。
+fn log_worktree(record: &OpRecord, repo_root: Option<&Path>) -> String {
+    if record.worktree.is_empty() {
+        return "-".to_string();
+    }
+    let path = Path::new(&record.worktree);
+    repo_root
+        .and_then(|root| path.strip_prefix(root).ok())
+        .filter(|rel| !rel.as_os_str().is_empty())
+        .map(|rel| rel.display().to_string())
+        .unwrap_or_else(|| abbreviate_home(path))
+}
+
+/// 操作前後の内容のハッシュを短縮して `before → after` の形で表す。
+fn log_change_cell(record: &OpRecord) -> StyledCell {
+    let short = |hash: &Option<String>| match hash {
+        Some(h) => h
+            .strip_prefix("sha256:")
+            .unwrap_or(h)
+            .chars()
+            .take(8)
+            .collect(),
+        None => "-".to_string(),
+    };
+    let text = format!("{} → {}", short(&record.before), short(&record.after));
+    if record.before == record.after {
+        StyledCell::new(text, ui::STYLE_DIM)
+    } else {
+        StyledCell::plain(text)
+    }
+}
+
+/// `ws store recover`: `ws rm` で store のごみ箱に退避したファイルを一覧表示し、現在の worktree に戻す。
+///
+/// 退避したファイルをすべて戻したエントリはごみ箱から削除する。
+pub fn cmd_store_recover(cmd: &StoreRecoverCmd, exec: &Executor) -> Result<()> {
+    let store = require_store()?;
+    let _lock = lock_store(&store)?;
+    let retention = crate::config::load_config()?.store.trash_retention_days();
+    trash::expire(&store, retention, exec)?;
+    let entries = trash::list(&store)?;
+
+    let Some(ref id) = cmd.id else {
+        if entries.is_empty() {
+            anstream::println!("{}", t!("store.trash_empty"));
+            return Ok(());
+        }
+        let rows: Vec<Vec<StyledCell>> = entries
+            .iter()
+            .map(|entry| {
+                vec![
+                    StyledCell::new(entry.id.as_str(), ui::STYLE_INFO),
+                    StyledCell::new(entry.meta.time.as_str(), ui::STYLE_DIM),
+                    StyledCell::plain(abbreviate_home(Path::new(&entry.meta.worktree))),
+                    StyledCell::plain(entry.meta.branch.clone().unwrap_or_else(|| "-".to_string())),
+                    StyledCell::plain(entry.meta.files.join(", ")),
+                ]
+            })
+            .collect();
+        crate::context::print_table(
+            &["ID", "TIME", "WORKTREE", "BRANCH", "FILES"],
+            &rows,
+            2,
+            None,
+        );
+        return Ok(());
+    };
+
+    let entry = entries
+        .iter()
+        .find(|e| e.id == *id)
+        .ok_or_else(|| anyhow::anyhow!("{}", t!("store.trash_not_found", id = id)))?;
+    for file in &cmd.files {
+        if !entry.meta.files.contains(file) {
+            bail!("{}", t!("store.trash_file_not_found", id = id, file = file));
+        }
+    }
+    let files = if cmd.files.is_empty() {
+        &entry.meta.files
+    } else {
+        &cmd.files
+    };
+
+    let wt_root = worktree_root()?;
+    let log = OpLog::new(&store, Some(wt_root.as_path()), exec);
+    let mut skipped = false;
+    for file in files {
+        let target = worktree_path(&wt_root, file)?;
+        let before = log.hash(&target);
+        if path_or_symlink_exists(&target) {
+            if !cmd.force {
+                anstream::eprintln!(
+                    "{}",
+                    ui::styled(
+                        ui::STYLE_WARN,
+                        &t!("store.skip_exists_use_force", file = file)
+                    )
+                );
+                skipped = true;
+                continue;
+            }
+            exec.remove(&target)?;
+        }
+        if let Some(parent) = target.parent() {
+            exec.create_dir_all(parent)?;
+        }
+        exec.copy(&entry.file_path(file), &target)?;
+        log.record(Action::Recover, file, before, log.hash(&target));
+        anstream::println!(
+            "{}",
+            ui::styled(ui::STYLE_OK, &t!("store.recovered", file = file))
+        );
+    }
+
+    if !skipped && entry.meta.files.iter().all(|f| files.contains(f)) {
+        exec.remove(&entry.dir)?;
+    }
+    Ok(())
+}
+
+/// `ws store migrate`: store の内容を `--to` に移し、config の `store_path` をそこに向ける。
+///
+/// symlink と hardlink で配置したファイルは元の store を指しているので、各 worktree で張り直す。
+pub fn cmd_store_migrate(cmd: &StoreMigrateCmd, exec: &Executor) -> Result<()> {
+    let backend = open_store()?;
+    let store = backend.dir().to_path_buf();
+    if !store.is_dir() || !manifest_exists(&store) {
+        bail!("{}", t!("store.store_not_initialized"));
+    }
+    let repo_root = resolve_repo_root(None)
+        .ok_or_else(|| anyhow::anyhow!("{}", t!("store.run_inside_repo")))?;
+
+    let dest = std::path::absolute(shellexpand::tilde(&cmd.to).as_ref())?;
+    // 既定の場所に戻すときに一致を判定できるよう、親ディレクトリは実パスにする
+    let dest = match (
+        dest.parent().and_then(|p| p.canonicalize().ok()),
+        dest.file_name(),
+    ) {
+        (Some(parent), Some(name)) => parent.join(name),
+        _ => dest,
+    };
+    if dest.starts_with(&store) || store.starts_with(&dest) {
+        bail!(
+            "{}",
+            t!("store.migrate_overlaps", path = dest.display().to_string())
+        );
+    }
+    let is_empty_dir = dest.is_dir() && fs::read_dir(&dest)?.next().is_none();
+    if path_or_symlink_exists(&dest) && !is_empty_dir {
+        bail!(
+            "{}",
+            t!(
+                "store.migrate_target_not_empty",
+                path = dest.display().to_string()
+            )
+        );
+    }
+
+    let _config_lock = if exec.is_dry_run() {
+        None
+    } else {
+        Some(crate::config::lock_config()?)
+    };
+    let mut config = crate::config::load_config()?;
+    let name = config
+        .repo_entry(&repo_root)
+        .map(|(name, _)| name.clone())
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "{}",
+                t!(
+                    "store.migrate_repo_not_registered",
+                    path = repo_root.display().to_string()
+                )
+            )
+        })?;
+    let _lock = lock_store(&store)?;
+    // 移行先には旧形式の manifest を持ち込まない
+    migrate_legacy_manifest(&store, exec)?;
+    let entries = read_manifest(&store)?;
+
+    backend.export(&dest, exec)?;
+    // 操作ログも移行先に移ったので、移行先に記録する
+    let log = OpLog::new(&dest, worktree_root().ok().as_deref(), exec);
+    for entry in &entries {
+        log.record(
+            Action::Migrate,
+            &entry.filepath,
+            log.hash(&entry.store_path(&store)),
+            log.hash(&entry.store_path(&dest)),
+        );
+    }
+
+    let mut relinked = 0;
+    for wt_root in worktree_paths()? {
+        let branch = worktree_branch(&wt_root);
+        for entry in entries.iter().filter(|e| e.strategy.shares_store()) {
+            let Ok(wt_file) = worktree_path(&wt_root, &entry.filepath) else {
+                continue;
+            };
+            let variant = entry.for_branch(branch.as_deref());
+            let old = variant.store_path(&store);
+            let linked = match entry.strategy {
+                Strategy::Symlink => fs::read_link(&wt_file).is_ok_and(|target| target == old),
+                // link-tree は張り直しで store のファイルの symlink だけを置き換える
+                Strategy::LinkTree => wt_file.is_dir() && !wt_file.is_symlink(),
+                _ => is_hardlinked(&old, &wt_file),
+            };
+            if !linked {
+                continue;
+            }
+            if entry.strategy != Strategy::LinkTree {
+                exec.remove_entry(&wt_file, &variant.filter)?;
+            }
+            exec.place(
+                &entry.strategy,
+                &variant.store_path(&dest),
+                &wt_file,
+                &variant.filter,
+            )?;
+            relinked += 1;
+        }
+    }
+
+    let default = default_store_dir()?;
+    if let Some(entry) = config.repos.get_mut(&name) {
+        entry.store_path = (dest != default).then(|| dest.clone());
+    }
+    let config_path = crate::config::config_path()?;
+    exec.run(format!("write {}", config_path.display()), || {
+        crate::config::save_config(&config)
+    })?;
+    backend.destroy(exec)?;
+
+    anstream::println!(
+        "{}",
+        ui::styled(
+            ui::STYLE_OK,
+            &t!("store.migrated", path = dest.display().to_string())
+        )
+    );
+    if relinked > 0 {
+        anstream::println!("{}", t!("store.migrate_relinked", count = relinked));
+    }
+    Ok(())
+}
+
+pub fn cmd_store_mv(cmd: &StoreMvCmd, exec: &Executor) -> Result<()> {
+    let store = require_store()?;
+    let _lock = lock_store(&store)?;
+    let mut entries = read_manifest(&store)?;
+
+    let pos = entries
+        .iter()
+        .position(|e| e.filepath == cmd.old)
+        .ok_or_else(|| anyhow::anyhow!("{}", t!("store.not_tracked", file = &cmd.old)))?;
+    if entries.iter().any(|e| e.filepath == cmd.new) {
+        bail!("{}", t!("store.already_tracked", file = &cmd.new));
+    }
+    validate_entry_path(&cmd.new)?;
+
+    // variant のマスターコピーも一緒に移す
+    let new_store_path = |entry: &ManifestEntry| match entry.branch {
+        Some(ref pattern) => variant_dir(&store, pattern).join(&cmd.new),
+        None => store.join(&cmd.new),
+    };
+    let moves: Vec<(PathBuf, PathBuf)> = std::iter::once(&entries[pos])
+        .chain(&entries[pos].variants)
+        .map(|e| (e.store_path(&store), new_store_path(e)))
+        .collect();
+    if moves.iter().any(|(_, new)| path_or_symlink_exists(new)) {
+        bail!("{}", t!("store.move_target_exists", file = &cmd.new));
+    }
+    let log = OpLog::new(&store, worktree_root().ok().as_deref(), exec);
+    let before = log.hash(&entries[pos].store_path(&store));
+
+    // worktree 側の symlink は新しい store のパスを指すように張り直すので、store より先に処理する
+    let mut rows = Vec::new();
+    for wt_path in worktree_paths()? {
+        let branch = worktree_branch(&wt_path);
+        let variant = entries[pos].for_branch(branch.as_deref());
+        let old_store = variant.store_path(&store);
+        let new_store = new_store_path(variant);
+        let outcome = move_in_worktree(&wt_path, &old_store, &new_store, cmd, exec);
+        rows.push(vec![
+            StyledCell::plain(crate::context::abbreviate_home(&wt_path)),
+            StyledCell::new(outcome.label(), outcome.style()),
+        ]);
+    }
+
+    for (old_store, new_store) in &moves {
+        if store_entry_exists(old_store) {
+            if let Some(parent) = new_store.parent() {
+                exec.create_dir_all(parent)?;
+            }
+            exec.rename(old_store, new_store)?;
+            if !exec.is_dry_run() {
+                cleanup_empty_parents(old_store, &store);
+            }
+        }
+    }
+
+    entries[pos].filepath = cmd.new.clone();
+    for variant in entries[pos].variants.iter_mut() {
+        variant.filepath = cmd.new.clone();
+    }
+    save_manifest(&store, &entries, exec)?;
+    // 新しいパスで記録する（内容は変わらないので前後のハッシュは同じ）
+    log.record(
+        Action::Mv,
+        &cmd.new,
+        before,
+        log.hash(&entries[pos].store_path(&store)),
+    );
+
+    anstream::println!(
+        "{}",
+        ui::styled(
+            ui::STYLE_OK,
+            &t!("store.moved", old = &cmd.old, new = &cmd.new)
+        )
+    );
+    if !rows.is_empty() {
+        anstream::println!();
+        crate::context::print_table(&["WORKTREE", "RESULT"], &rows, 2, None);
+    }
+    Ok(())
+}
+
+/// mv / set-strategy で各 worktree に対して行った処理の結果。
+enum WorktreeOutcome {
+    Moved,
+    Relinked,
+    Converted,
+    Unchanged,
+    Modified,
+    Conflict,
+    Absent,
+    Failed,
+}
+
+impl WorktreeOutcome {
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Moved => "MOVED",
+            Self::Relinked => "RELINKED",
+            Self::Converted => "CONVERTED",
+            Self::Unchanged => "UNCHANGED",
+            Self::Modified => "SKIPPED(modified)",
+            Self::Conflict => "SKIPPED(exists)",
+            Self::Absent => "ABSENT",
+            Self::Failed => "FAILED",
+        }
+    }
+
+    fn style(&self) -> anstyle::Style {
+        match self {
+            Self::Moved | Self::Relinked | Self::Converted => ui::STYLE_OK,
+            Self::Unchanged | Self::Absent => ui::STYLE_DIM,
+            Self::Modified | Self::Conflict => ui::STYLE_WARN,
+            Self::Failed => ui::STYLE_ERROR,
+        }
+    }
+}
+
+/// worktree 内のファイルを新しいパスに移す。store を指す symlink は新しい store のパスに張り直す。
+fn move_in_worktree(
+    wt_root: &Path,
+    old_store: &Path,
+    new_store: &Path,
+    cmd: &StoreMvCmd,
+    exec: &Executor,
+) -> WorktreeOutcome {
+    let (Ok(old_target), Ok(new_target)) = (
+        worktree_path(wt_root, &cmd.old),
+        worktree_path(wt_root, &cmd.new),
+    ) else {
+        return WorktreeOutcome::Failed;
+    };
+    if !path_or_symlink_exists(&old_target) {
+        return WorktreeOutcome::Absent;
+    }
+    if path_or_symlink_exists(&new_target) {
+        return WorktreeOutcome::Conflict;
+    }
+
+    let points_to_store = fs::read_link(&old_target)
+        .map(|link| link.starts_with(old_store))
+        .unwrap_or(false);
+    // link-tree のディレクトリは中の symlink が store のファイルを指している
+    let is_link_tree = is_real_dir(&old_target) && tree_links_into(&old_target, old_store);
+    let result = (|| -> Result<WorktreeOutcome> {
+        if let Some(parent) = new_target.parent() {
+            exec.create_dir_all(parent)?;
+        }
+        if points_to_store {
+            exec.remove(&old_target)?;
+            exec.symlink(new_store, &new_target)?;
+        } else {
+            // rename なので hardlink も inode を保ったまま移る
+            exec.rename(&old_target, &new_target)?;
+            if is_link_tree {
+                exec.relink_tree(&new_target, old_store, new_store)?;
+            }
+        }
+        // merge-json の記録も新しいパスに移す
+        if let Ok(old_base) = merge_base_path(wt_root, &cmd.old)
+            && old_base.is_file()
+        {
+            let new_base = merge_base_path(wt_root, &cmd.new)?;
+            if let Some(parent) = new_base.parent() {
+                exec.create_dir_all(parent)?;
+            }
+            exec.rename(&old_base, &new_base)?;
+        }
+        if !exec.is_dry_run() {
+            cleanup_empty_parents(&old_target, wt_root);
+        }
+        Ok(if points_to_store || is_link_tree {
+            WorktreeOutcome::Relinked
+        } else {
+            WorktreeOutcome::Moved
+        })
+    })();
+    result.unwrap_or(WorktreeOutcome::Failed)
+}
+
+pub fn cmd_store_set_strategy(cmd: &StoreSetStrategyCmd, exec: &Executor) -> Result<()> {
+    let store = require_store()?;
+    let _lock = lock_store(&store)?;
+    let mut entries = read_manifest(&store)?;
+
+    let pos = entries
+        .iter()
+        .position(|e| e.filepath == cmd.file)
+        .ok_or_else(|| anyhow::anyhow!("{}", t!("store.not_tracked", file = &cmd.file)))?;
+    let store_file = store.join(&cmd.file);
+    let from = entries[pos].strategy.clone();
+    let to = cmd.strategy.clone();
+
+    match (&to, cmd.command.as_deref()) {
+        (Strategy::Command, Some(command)) => validate_command(command)?,
+        (Strategy::Command, None) if entries[pos].command.is_none() => {
+            bail!("{}", t!("store.command_required"))
+        }
+        (Strategy::Command, None) => {}
+        (_, Some(_)) => bail!("{}", t!("store.command_requires_strategy")),
+        (_, None) => {}
+    }
+    // 同じ strategy でも --force なら、前回スキップした worktree を変換し直す
+    if from == to && cmd.command.is_none() && !cmd.force {
+        anstream::println!(
+            "{}",
+            t!(
+                "store.strategy_unchanged",
+                file = &cmd.file,
+                strategy = to.as_str()
+            )
+        );
+        return Ok(());
+    }
+    if !entries[pos].variants.is_empty() && !to.supports_variants() {
+        bail!(
+            "{}",
+            t!(
+                "store.variant_unsupported",
+                file = &cmd.file,
+                strategy = to.as_str()
+            )
+        );
+    }
+    if !entries[pos].filter.is_empty() && !to.supports_filter() {
+        bail!(
+            "{}",
+            t!(
+                "store.filter_unsupported",
+                file = &cmd.file,
+                strategy = to.as_str()
+            )
+        );
+    }
+    if to == Strategy::Dotenv && store_file.is_dir() {
+        bail!("{}", t!("store.dotenv_requires_file", file = &cmd.file));
+    }
+    if to == Strategy::LinkTree && !store_file.is_dir() {
+        bail!("{}", t!("store.link_tree_requires_dir", file = &cmd.file));
+    }
+    if to == Strategy::MergeJson
+        && fs::read_to_string(&store_file)
+            .ok()
+            .and_then(|content| crate::json::parse_object(&content))
+            .is_none()
+    {
+        bail!("{}", t!("store.merge_json_invalid", file = &cmd.file));
+    }
+
+    let log = OpLog::new(&store, worktree_root().ok().as_deref(), exec);
+    let before = log.hash(&store_file);
+    let entry = &mut entries[pos];
+    entry.strategy = to.clone();
+    if to == Strategy::Command {
+        if let Some(ref command) = cmd.command {
+            entry.command = Some(command.clone());
+        }
+    } else {
+        entry.command = None;
+    }
+    record_strategy_hash(entry, &store);
+    for variant in entry.variants.iter_mut() {
+        variant.strategy = to.clone();
+        record_strategy_hash(variant, &store);
+    }
+
+    let mut rows = Vec::new();
+    for wt_path in worktree_paths()? {
+        let branch = worktree_branch(&wt_path);
+        let outcome = convert_in_worktree(
+            entry.for_branch(branch.as_deref()),
+            &from,
+            &store,
+            &wt_path,
+            cmd.force,
+            exec,
+        );
+        if let WorktreeOutcome::Modified = outcome {
+            anstream::eprintln!(
+                "{}",
+                ui::styled(
+                    ui::STYLE_WARN,
+                    &t!(
+                        "store.convert_skipped_modified",
+                        file = &cmd.file,
+                        path = wt_path.display().to_string()
+                    )
+                )
+            );
+        }
+        rows.push(vec![
+            StyledCell::plain(crate::context::abbreviate_home(&wt_path)),
+            StyledCell::new(outcome.label(), outcome.style()),
+        ]);
+    }
+
+    save_manifest(&store, &entries, exec)?;
+    log.record(
+        Action::SetStrategy,
+        &cmd.file,
+        before,
+        log.hash(&store_file),
+    );
+
+    anstream::println!(
+        "{}",
+        ui::styled(
+            ui::STYLE_OK,
+            &t!(
+                "store.strategy_changed",
+                file = &cmd.file,
+                from = from.as_str(),
+                to = to.as_str()
+            )
+        )
+    );
+    if !rows.is_empty() {
+        anstream::println!();
+        crate::context::print_table(&["WORKTREE", "RESULT"], &rows, 2, None);
+    }
+    Ok(())
+}
+
+/// strategy を変えたエントリのハッシュを、新しい strategy に合わせて記録し直す。
+fn record_strategy_hash(entry: &mut ManifestEntry, store: &Path) {
+    if !entry.strategy.records_hash() {
+        entry.hash = None;
+    } else if entry.hash.is_none() {
+        // symlink などで worktree から直接書き換えられていた store の内容を、ここから記録する
+        entry.hash = hash::hash_path_filtered(&entry.store_path(store), &entry.filter).ok();
+    }
+}
+
+/// worktree 内の既存ファイルを新しい strategy の形に置き換える。
+///
+/// store を共有する形（symlink / hardlink）にするときは、worktree 側の変更が失われるので
+/// 内容が store と異なるものは `force` がなければ残す。コピー系にするときは、
+/// store と実体を共有しているもの（symlink / hardlink）だけを独立したコピーにする。
+fn convert_in_worktree(
+    entry: &ManifestEntry,
+    from: &Strategy,
+    store: &Path,
+    wt_root: &Path,
+    force: bool,
+    exec: &Executor,
+) -> WorktreeOutcome {
+    let Ok(target) = worktree_path(wt_root, &entry.filepath) else {
+        return WorktreeOutcome::Failed;
+    };
+    let store_file = entry.store_path(store);
+    if !path_or_symlink_exists(&target) {
+        return WorktreeOutcome::Absent;
+    }
+
+    let is_symlink = target
+        .symlink_metadata()
+        .map(|m| m.file_type().is_symlink())
+        .unwrap_or(false);
+    let shares_store = is_symlink
+        || (*from == Strategy::Hardlink && is_hardlinked(&store_file, &target))
+        || (*from == Strategy::LinkTree && target.is_dir());
+
+    let result = (|| -> Result<WorktreeOutcome> {
+        match entry.strategy {
+            Strategy::Symlink | Strategy::Hardlink | Strategy::LinkTree => {
+                if file_status(entry, &store_file, &Some(wt_root.to_path_buf())) == FileStatus::Ok {
+                    return Ok(WorktreeOutcome::Unchanged);
+                }
+                if !shares_store
+                    && !force
+                    && !content_matches_store(&store_file, wt_root, &entry.filepath)
+                {
+                    return Ok(WorktreeOutcome::Modified);
+                }
+                // link-tree は store のファイルだけを symlink に置き換え、worktree 固有のファイルは残す
+                if entry.strategy != Strategy::LinkTree || is_symlink {
+                    exec.remove_entry(&target, &entry.filter)?;
+                }
+                exec.place(&entry.strategy, &store_file, &target, &entry.filter)?;
+            }
+            Strategy::Command => {
+                if is_symlink {
+                    exec.remove(&target)?;
+                }
+                generate_file(entry, store, wt_root, exec)?;
+            }
+            _ if *from == Strategy::LinkTree && shares_store && !is_symlink => {
+                exec.unlink_tree(&target)?;
+                exec.apply_mode(&target, entry.mode)?;
+            }
+            _ => {
+                if shares_store {
+                    exec.remove_entry(&target, &entry.filter)?;
+                    exec.place(&entry.strategy, &store_file, &target, &entry.filter)?;
+                    exec.apply_mode(&target, entry.mode)?;
+                }
+                if entry.strategy == Strategy::MergeJson {
+                    save_merge_base(store, wt_root, &entry.filepath, exec)?;
+                }
+                if !shares_store {
+                    return Ok(WorktreeOutcome::Unchanged);
+                }
+            }
+        }
+        Ok(WorktreeOutcome::Converted)
+    })();
+    result.unwrap_or(WorktreeOutcome::Failed)
+}
+
+/// worktree のファイル（またはディレクトリ）の内容が store と同じか（パーミッションは問わない）。
+fn content_matches_store(store_file: &Path, wt_root: &Path, filepath: &str) -> bool {
+    let entry = ManifestEntry::new(Strategy::Copy, filepath);
+    matches!(
+        file_status(&entry, store_file, &Some(wt_root.to_path_buf())),
+        FileStatus::Ok | FileStatus::ModeDiff
+    )
+}
+
+/// 追跡候補として扱わないビルド成果物・キャッシュのディレクトリ名
+const BUILD_DIR_NAMES: &[&str] = &[
+    "node_modules",
+    "target",
+    "dist",
+    "build",
+    "out",
+    ".next",
+    ".nuxt",
+    ".turbo",
+    ".cache",
+    ".direnv",
+    ".gradle",
+    ".venv",
+    "venv",
+    "__pycache__",
+    ".pytest_cache",
+    ".mypy_cache",
+    ".tox",
+    "coverage",
+];
+
+/// 追跡候補として扱わないファイル名・拡張子
+const NOISE_FILE_SUFFIXES: &[&str] = &[".DS_Store", ".log", ".pyc", ".o", ".swp"];
+
+/// `ws store suggest` の候補。
+pub struct Suggestion {
+    pub path: String,
+    pub is_dir: bool,
+    pub strategy: Strategy,
+    /// ファイルが存在する worktree
+    pub present_in: Vec<PathBuf>,
+    pub worktree_count: usize,
+    /// よく共有される設定ファイルのパターンに一致するか
+    pub known: bool,
+}
+
+fn is_build_artifact(path: &str) -> bool {
+    path.split('/')
+        .any(|component| BUILD_DIR_NAMES.contains(&component))
+        || NOISE_FILE_SUFFIXES
+            .iter()
+            .any(|suffix| path.ends_with(suffix))
+}
+
+/// worktree 間で共有されることの多い設定ファイルか判定する。
+fn is_known_config(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    name.starts_with(".env")
+        || name.contains(".local.")
+        || name.ends_with(".local")
+        || name == ".tool-versions"
+        || path == ".vscode/settings.json"
+        || path.starts_with(".claude/")
+}
+
+/// パスから適切な strategy を推測する。
+fn propose_strategy(path: &str, is_dir: bool) -> Strategy {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    if is_dir {
+        Strategy::Copy
+    } else if name.starts_with(".env") && name != ".envrc" {
+        Strategy::Dotenv
+    } else if name == ".envrc"
+        || name.ends_with(".local.json")
+        || path == ".vscode/settings.json"
+        || path.starts_with(".claude/")
+    {
+        Strategy::Symlink
+    } else {
+        Strategy::Copy
+    }
+}
+
+/// 全 worktree の gitignored ファイルを比較し、store への登録候補を優先度順に返す。
+///
+/// 一部の worktree にしか存在しないファイルと、よく共有される設定ファイルを候補とする。
+/// 既に追跡中のパスとビルド成果物は除外する。
+pub fn collect_suggestions() -> Result<Vec<Suggestion>> {
+    let worktrees = worktree_paths()?;
+    let tracked: Vec<String> = store_dir()
+        .ok()
+        .filter(|s| manifest_exists(s))
+        .and_then(|s| read_manifest(&s).ok())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|e| e.filepath)
+        .collect();
+
+    let mut found: BTreeMap<String, (bool, Vec<PathBuf>)> = BTreeMap::new();
+    for wt in &worktrees {
+        let Ok(output) = git_output_in(
+            wt,
+            &[
+                "ls-files",
+                "--others",
+                "--ignored",
+                "--exclude-standard",
+                "--directory",
+            ],
+        ) else {
+            continue;
+        };
+        for line in output.lines() {
+            let is_dir = line.ends_with('/');
+            let path = line.trim_end_matches('/');
+            if path.is_empty() || is_build_artifact(path) {
+                continue;
+            }
+            let is_tracked = tracked
+                .iter()
+                .any(|t| path == t || Path::new(path).starts_with(t));
+            if is_tracked {
+                continue;
+            }
+            let slot = found
+                .entry(path.to_string())
+                .or_insert_with(|| (is_dir, Vec::new()));
+            slot.1.push(wt.clone());
+        }
+    }
+
+    let mut suggestions: Vec<Suggestion> = found
+        .into_iter()
+        .map(|(path, (is_dir, present_in))| Suggestion {
+            strategy: propose_strategy(&path, is_dir),
+            known: is_known_config(&path),
+            path,
+            is_dir,
+            present_in,
+            worktree_count: worktrees.len(),
+        })
+        .filter(|s| s.known || s.present_in.len() < s.worktree_count)
+        .collect();
+
+    // よく知られた設定ファイル → 欠けている worktree が多い順 → パス順
+    suggestions.sort_by(|a, b| {
+        b.known
+            .cmp(&a.known)
+            .then(a.present_in.len().cmp(&b.present_in.len()))
+            .then(a.path.cmp(&b.path))
+    });
+    Ok(suggestions)
+}
+
+pub fn cmd_store_suggest() -> Result<()> {
+    let suggestions = collect_suggestions()?;
+    if suggestions.is_empty() {
+        anstream::println!("{}", t!("store.no_suggestions"));
+        return Ok(());
+    }
+
+    let rows: Vec<Vec<StyledCell>> = suggestions
+        .iter()
+        .map(|s| {
+            let path = if s.is_dir {
+                format!("{}/", s.path)
+            } else {
+                s.path.clone()
+            };
+            vec![
+                StyledCell::plain(path),
+                StyledCell::plain(format!("{}/{}", s.present_in.len(), s.worktree_count)),
+                StyledCell::new(s.strategy.to_string(), ui::STYLE_INFO),
+            ]
+        })
+        .collect();
+    crate::context::print_table(&["FILE", "PRESENT", "STRATEGY"], &rows, 0, None);
+    anstream::println!();
+    anstream::println!("{}", t!("store.suggest_hint"));
+    Ok(())
+}
+
+/// 候補を store に登録し、ファイルが存在しない worktree に配布する。
+pub fn track_suggestion(
+    suggestion: &Suggestion,
+    strategy: &Strategy,
+    exec: &Executor,
+) -> Result<()> {
+    let store = ensure_store(exec)?;
+    let _lock = lock_store(&store)?;
+
+    // カレント worktree にあればそこから、なければ最初に見つかった worktree から登録する
+    let current = worktree_root().ok();
+    let source_wt = current
+        .as_ref()
+        .filter(|c| suggestion.present_in.contains(c))
+        .or_else(|| suggestion.present_in.first())
+        .ok_or_else(|| {
+            anyhow::anyhow!("{}", t!("store.file_not_found", file = &suggestion.path))
+        })?;
+    track_file(
+        &store,
+        source_wt,
+        &suggestion.path,
+        None,
+        strategy,
+        &PathFilter::default(),
+        exec,
+    )?;
+
+    let mut entry = ManifestEntry::new(strategy.clone(), suggestion.path.as_str());
+    entry.mode = file_mode(&source_wt.join(&suggestion.path));
+    for wt in worktree_paths()? {
+        if !suggestion.present_in.contains(&wt) {
+            anstream::println!("{}", abbreviate_home(&wt));
+            apply_file(&entry, &store, &wt, None, exec)?;
+        }
+    }
     Ok(())
 }
@@ -1,29 +1,28 @@
 use anyhow::{Context, Result, bail};
 use rust_i18n::t;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
 use crate::cli::{CloneCmd, NewCmd, RmCmd};
-use crate::git::{find_bare_dir, is_inside_git_worktree};
+use crate::exec::Executor;
+use crate::git::{self, find_bare_dir, is_inside_git_worktree};
+use crate::oplog::{Action, OpLog};
 use crate::store;
+use crate::trash;
 use crate::ui;
 
-pub fn cmd_clone(cmd: &CloneCmd) -> Result<()> {
+pub fn cmd_clone(cmd: &CloneCmd, exec: &Executor) -> Result<()> {
     let bare_dir = PathBuf::from(".bare");
     if bare_dir.exists() {
         bail!("{}", t!("worktree.bare_already_exists"));
     }
 
     let status = if let Some(ref url) = cmd.url {
-        Command::new("git")
-            .args(["clone", "--bare", url, ".bare"])
-            .status()
+        exec.status(Command::new("git").args(["clone", "--bare", url, ".bare"]))
             .context(t!("worktree.clone_bare_failed").to_string())?
     } else {
-        Command::new("git")
-            .args(["init", "--bare", ".bare"])
-            .status()
+        exec.status(Command::new("git").args(["init", "--bare", ".bare"]))
             .context(t!("worktree.init_bare_failed").to_string())?
     };
 
@@ -31,10 +30,13 @@ pub fn cmd_clone(cmd: &CloneCmd) -> Result<()> {
         bail!("{}", t!("worktree.bare_creation_failed"));
     }
 
-    anstream::println!("{}", ui::styled(ui::STYLE_OK, &t!("worktree.bare_created")));
+    if !exec.is_dry_run() {
+        anstream::println!("{}", ui::styled(ui::STYLE_OK, &t!("worktree.bare_created")));
+    }
 
     // URL ありの場合のみ、default branch の worktree を自動作成
-    if cmd.url.is_some() {
+    // （dry-run では bare リポジトリがないため default branch を判定できない）
+    if cmd.url.is_some() && !exec.is_dry_run() {
         match crate::git::git_output(&["symbolic-ref", "HEAD"]) {
             Ok(full_ref) => {
                 if let Some(branch) = full_ref.strip_prefix("refs/heads/") {
@@ -47,8 +49,10 @@ pub fn cmd_clone(cmd: &CloneCmd) -> Result<()> {
                         directory: None,
                         branch: None,
                         from: None,
+                        profile: None,
+                        sparse: Vec::new(),
                     };
-                    cmd_new(&new_cmd)?;
+                    cmd_new(&new_cmd, exec)?;
                 }
             }
             Err(_) => {
@@ -62,8 +66,14 @@ pub fn cmd_clone(cmd: &CloneCmd) -> Result<()> {
     }
 
     // config に自動登録（失敗しても clone 自体は成功扱い）
+    let _lock = if exec.is_dry_run() {
+        None
+    } else {
+        crate::config::lock_config().ok()
+    };
     if let Ok(abs_path) = std::fs::canonicalize(".")
         && let Ok(mut config) = crate::config::load_config()
+        && let Ok(config_path) = crate::config::config_path()
     {
         let name = abs_path
             .file_name()
@@ -75,9 +85,13 @@ pub fn cmd_clone(cmd: &CloneCmd) -> Result<()> {
             crate::config::RepoEntry {
                 path: abs_path,
                 url: cmd.url.clone(),
+                ..Default::default()
             },
         );
-        if let Err(e) = crate::config::save_config(&config) {
+        let saved = exec.run(format!("write {}", config_path.display()), || {
+            crate::config::save_config(&config)
+        });
+        if let Err(e) = saved {
             anstream::eprintln!(
                 "{}",
                 ui::styled(
@@ -91,7 +105,7 @@ pub fn cmd_clone(cmd: &CloneCmd) -> Result<()> {
     Ok(())
 }
 
-pub fn cmd_new(cmd: &NewCmd) -> Result<()> {
+pub fn cmd_new(cmd: &NewCmd, exec: &Executor) -> Result<()> {
     let name = cmd.name.clone();
 
     let branch = cmd.branch.clone().unwrap_or_else(|| name.clone());
@@ -122,6 +136,25 @@ pub fn cmd_new(cmd: &NewCmd) -> Result<()> {
 
     let start_point = cmd.from.as_deref().unwrap_or("HEAD");
 
+    // worktree を作る前に profile を確認する
+    if let Some(ref profile) = cmd.profile {
+        store::validate_profile(profile)?;
+        let entries = store::require_store()
+            .and_then(|sd| store::read_manifest(&sd))
+            .unwrap_or_default();
+        store::require_profile(&entries, profile)?;
+    }
+
+    // sparse checkout するディレクトリも worktree を作る前に確認する
+    let sparse: Vec<String> = cmd
+        .sparse
+        .iter()
+        .map(|dir| dir.trim_end_matches('/').to_string())
+        .collect();
+    for dir in &sparse {
+        store::validate_entry_path(dir)?;
+    }
+
     // 起点の参照が有効かチェック（空の bare リポジトリでは HEAD が無効）
     let mut rev_parse_cmd = Command::new("git");
     if is_bare_root {
@@ -135,7 +168,7 @@ pub fn cmd_new(cmd: &NewCmd) -> Result<()> {
         .map(|s| s.success())
         .unwrap_or(false);
 
-    let args = if branch_exists {
+    let mut args = if branch_exists {
         // 既存ブランチをチェックアウト
         vec!["worktree", "add", &directory, &branch]
     } else if start_point_valid {
@@ -151,52 +184,154 @@ pub fn cmd_new(cmd: &NewCmd) -> Result<()> {
         );
     };
 
+    // orphan ブランチにはチェックアウトするファイルがない
+    let is_orphan = args.contains(&"--orphan");
+    if !sparse.is_empty() && !is_orphan {
+        // 全体を展開してから削るのを避け、sparse checkout を設定してからチェックアウトする
+        args.insert(2, "--no-checkout");
+    }
+
     let mut git_cmd = Command::new("git");
     if is_bare_root {
         git_cmd.arg("--git-dir").arg(".bare");
     }
-    let status = git_cmd
-        .args(&args)
-        .status()
+    let status = exec
+        .status(git_cmd.args(&args))
         .context(t!("worktree.worktree_add_failed").to_string())?;
 
     if !status.success() {
         bail!("{}", t!("worktree.worktree_add_git_failed"));
     }
 
+    if !sparse.is_empty() {
+        sparse_checkout(&directory, &sparse, !is_orphan, exec)?;
+    }
+
     // store が存在すればファイルを適用
     if let Ok(sd) = store::store_dir()
         && sd.is_dir()
-        && sd.join("manifest").is_file()
+        && store::manifest_exists(&sd)
     {
-        let abs_directory = fs::canonicalize(&directory).with_context(|| {
-            t!("worktree.dir_canonicalize_failed", dir = &directory).to_string()
-        })?;
+        // dry-run では worktree が作られていないので canonicalize できない
+        let abs_directory = if exec.is_dry_run() {
+            std::path::absolute(&directory)
+        } else {
+            fs::canonicalize(&directory)
+        }
+        .with_context(|| t!("worktree.dir_canonicalize_failed", dir = &directory).to_string())?;
         anstream::println!("{}", t!("worktree.applying_store_files"));
+        let _lock = store::lock_store_shared(&sd)?;
         let entries = store::read_manifest(&sd)?;
-        for entry in &entries {
-            store::apply_file(&entry.strategy, &entry.filepath, &sd, &abs_directory)?;
+        let profile = cmd.profile.as_deref();
+        let log = OpLog::new(&sd, Some(&abs_directory), exec);
+        // sparse checkout では展開したサブプロジェクトのエントリだけを配置する
+        for entry in entries
+            .iter()
+            .filter(|e| e.in_profile(profile) && (sparse.is_empty() || e.in_sparse(&sparse)))
+        {
+            let wt_file = abs_directory.join(&entry.filepath);
+            let existed = store::path_or_symlink_exists(&wt_file);
+            store::apply_file(entry, &sd, &abs_directory, Some(&branch), exec)?;
+            // 既にあって飛ばしたファイルや、command が失敗して置けなかったファイルは記録しない
+            if !existed && store::path_or_symlink_exists(&wt_file) {
+                log.record(Action::Apply, &entry.filepath, None, log.hash(&wt_file));
+            }
+        }
+        if profile.is_some() {
+            store::save_profile(&abs_directory, profile, exec)?;
         }
     }
 
     Ok(())
 }
 
-pub fn cmd_rm(cmd: &RmCmd) -> Result<()> {
+/// 作成した worktree で `dirs` だけを展開する sparse checkout（cone モード）を設定する。
+fn sparse_checkout(
+    directory: &str,
+    dirs: &[String],
+    checkout: bool,
+    exec: &Executor,
+) -> Result<()> {
+    let mut set_cmd = Command::new("git");
+    set_cmd
+        .args(["-C", directory, "sparse-checkout", "set", "--cone"])
+        .args(dirs);
+    let status = exec
+        .status(&mut set_cmd)
+        .context(t!("worktree.sparse_checkout_failed").to_string())?;
+    if !status.success() {
+        bail!("{}", t!("worktree.sparse_checkout_failed"));
+    }
+    if checkout {
+        let status = exec
+            .status(Command::new("git").args(["-C", directory, "checkout"]))
+            .context(t!("worktree.sparse_checkout_failed").to_string())?;
+        if !status.success() {
+            bail!("{}", t!("worktree.sparse_checkout_failed"));
+        }
+    }
+    Ok(())
+}
+
+pub fn cmd_rm(cmd: &RmCmd, exec: &Executor) -> Result<()> {
+    // git worktree remove は ignored ファイルも消すので、push していない変更を先に store へ退避する
+    let stashed = stash_modified_copies(Path::new(&cmd.directory), exec)?;
+
     let mut args = vec!["worktree", "remove"];
     if cmd.force {
         args.push("--force");
     }
     args.push(&cmd.directory);
 
-    let status = Command::new("git")
-        .args(&args)
-        .status()
+    let status = exec
+        .status(Command::new("git").args(&args))
         .context(t!("worktree.worktree_remove_failed").to_string())?;
 
     if !status.success() {
+        // worktree は残っているので、退避したものは不要
+        if let Some(dir) = stashed {
+            exec.remove(&dir)?;
+        }
         bail!("{}", t!("worktree.worktree_remove_git_failed"));
     }
 
+    if let Some(dir) = stashed {
+        let id = dir.file_name().unwrap_or_default().to_string_lossy();
+        anstream::println!(
+            "{}",
+            ui::styled(ui::STYLE_WARN, &t!("worktree.trash_stashed", id = id))
+        );
+    }
     Ok(())
 }
+
+/// 削除する worktree で store と内容が異なるファイルを store のごみ箱に退避する。
+/// 退避したエントリのディレクトリを返す。保持期間を過ぎたエントリもここで削除する。
+fn stash_modified_copies(directory: &Path, exec: &Executor) -> Result<Option<PathBuf>> {
+    let Ok(store) = store::store_dir() else {
+        return Ok(None);
+    };
+    if !store.is_dir() || !store::manifest_exists(&store) {
+        return Ok(None);
+    }
+    // worktree が存在しなければ git にエラーを報告させる
+    let Ok(wt_root) = fs::canonicalize(directory) else {
+        return Ok(None);
+    };
+    let _lock = store::lock_store(&store)?;
+    let entries = store::read_manifest(&store)?;
+    let branch = git::worktree_branch(&wt_root);
+
+    // config が壊れていても、変更を失わないよう退避は続ける
+    let retention = crate::config::load_config()
+        .map(|c| c.store.trash_retention_days())
+        .unwrap_or(crate::config::DEFAULT_TRASH_RETENTION_DAYS);
+    trash::expire(&store, retention, exec)?;
+
+    let files = trash::modified_copies(&store, &entries, &wt_root, branch.as_deref());
+    if files.is_empty() {
+        return Ok(None);
+    }
+    let id = trash::stash(&store, &wt_root, branch.as_deref(), &files, exec)?;
+    Ok(Some(trash::trash_dir(&store).join(id)))
+}
@@ -5,17 +5,137 @@ use std::path::{Path, PathBuf};
 
 use rust_i18n::t;
 
+use crate::lock::{self, FileLock};
+use crate::pattern;
+
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Config {
     #[serde(default)]
     pub repos: BTreeMap<String, RepoEntry>,
+    #[serde(default, skip_serializing_if = "StoreConfig::is_empty")]
+    pub store: StoreConfig,
+}
+
+/// `[store]` セクション。サイズは `"500MB"` のような単位付き文字列かバイト数で指定する。
+/// 書いた表記のまま保存し直せるよう、文字列のまま保持して使うときに解釈する。
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct StoreConfig {
+    /// これを超えるファイルを track すると警告する
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warn_size: Option<String>,
+    /// これを超えるファイルは track を拒否する（未設定なら制限なし）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<String>,
+    /// これを超えるディレクトリは reflink で複製する
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_threshold: Option<String>,
+    /// 自動 track のルール（グロブ → strategy 名）。`[repos.<name>.store_rules]` のほうが優先される
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rules: BTreeMap<String, String>,
+    /// `ws rm` で store のごみ箱に退避したファイルを残す日数（`0` なら削除しない）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trash_retention_days: Option<u64>,
+}
+
+pub const DEFAULT_WARN_SIZE: u64 = 10 * 1024 * 1024;
+pub const DEFAULT_LINK_THRESHOLD: u64 = 100 * 1024 * 1024;
+pub const DEFAULT_TRASH_RETENTION_DAYS: u64 = 30;
+
+impl StoreConfig {
+    fn is_empty(&self) -> bool {
+        self.warn_size.is_none()
+            && self.max_size.is_none()
+            && self.link_threshold.is_none()
+            && self.rules.is_empty()
+            && self.trash_retention_days.is_none()
+    }
+
+    pub fn warn_size(&self) -> Result<u64> {
+        Ok(size_setting("warn_size", &self.warn_size)?.unwrap_or(DEFAULT_WARN_SIZE))
+    }
+
+    pub fn max_size(&self) -> Result<Option<u64>> {
+        size_setting("max_size", &self.max_size)
+    }
+
+    pub fn link_threshold(&self) -> Result<u64> {
+        Ok(size_setting("link_threshold", &self.link_threshold)?.unwrap_or(DEFAULT_LINK_THRESHOLD))
+    }
+
+    pub fn trash_retention_days(&self) -> u64 {
+        self.trash_retention_days
+            .unwrap_or(DEFAULT_TRASH_RETENTION_DAYS)
+    }
+}
+
+fn size_setting(key: &str, value: &Option<String>) -> Result<Option<u64>> {
+    match value {
+        None => Ok(None),
+        Some(v) => parse_size(v)
+            .map(Some)
+            .ok_or_else(|| anyhow::anyhow!("{}", t!("config.invalid_size", key = key, value = v))),
+    }
+}
+
+/// `"1.5GB"`, `"500M"`, `"1024"` のようなサイズ表記をバイト数に変換する（1KB = 1024B）。
+pub fn parse_size(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return None,
+    };
+    Some((number * multiplier as f64) as u64)
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct RepoEntry {
     pub path: PathBuf,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub url: Option<String>,
+    /// このリポジトリだけに適用する自動 track のルール（`[store.rules]` より優先）
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub store_rules: BTreeMap<String, String>,
+    /// store の場所（未設定なら `<git-common-dir>/worktree-store`）。相対パスはリポジトリのルートから
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub store_path: Option<PathBuf>,
+}
+
+impl Config {
+    /// `repo_root`（canonical なパス）に登録されたリポジトリの名前とエントリを返す。
+    pub fn repo_entry(&self, repo_root: &Path) -> Option<(&String, &RepoEntry)> {
+        self.repos
+            .iter()
+            .find(|(_, entry)| entry.path.canonicalize().ok().as_deref() == Some(repo_root))
+    }
+
+    /// `path` に一致する自動 track のルールの strategy 名を返す。
+    ///
+    /// `repo_root` のリポジトリのルールを全体のルールより優先し、同じ表の中では
+    /// より具体的なパターン（ワイルドカード以外の文字が多いもの）を優先する。
+    pub fn store_rule_for(&self, repo_root: Option<&Path>, path: &str) -> Option<&str> {
+        let repo_rules = repo_root
+            .and_then(|root| self.repo_entry(root))
+            .map(|(_, entry)| &entry.store_rules);
+        repo_rules
+            .into_iter()
+            .chain([&self.store.rules])
+            .find_map(|rules| {
+                rules
+                    .iter()
+                    .filter(|(pattern, _)| pattern::glob_match(pattern, path))
+                    .max_by_key(|(pattern, _)| pattern::specificity(pattern))
+                    .map(|(_, strategy)| strategy.as_str())
+            })
+    }
 }
 
 /// config.toml のパスを返す。
@@ -54,6 +174,7 @@ fn load_config_from(path: &Path) -> Result<Config> {
     // config 内のパスの ~ をホームディレクトリに展開
     for entry in config.repos.values_mut() {
         entry.path = expand_tilde(&entry.path);
+        entry.store_path = entry.store_path.as_deref().map(expand_tilde);
     }
 
     Ok(config)
@@ -67,12 +188,30 @@ fn save_config_to(config: &Config, path: &Path) -> Result<()> {
     }
     let content =
         toml::to_string_pretty(config).context(t!("config.serialize_failed").to_string())?;
-    std::fs::write(path, content).with_context(|| {
+    lock::write_atomic(path, content).with_context(|| {
         t!("config.write_failed", path = path.display().to_string()).to_string()
     })?;
     Ok(())
 }
 
+/// config.toml の隣に置いたロックファイルで排他ロックを取得する。
+/// load → 変更 → save の間保持し、他の `ws` プロセスの更新を上書きしないようにする。
+fn lock_config_at(path: &Path) -> Result<FileLock> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).with_context(|| {
+            t!("config.mkdir_failed", path = parent.display().to_string()).to_string()
+        })?;
+    }
+    let mut lock_path = path.as_os_str().to_owned();
+    lock_path.push(".lock");
+    FileLock::exclusive(Path::new(&lock_path))
+}
+
+/// config.toml の排他ロックを取得する。
+pub fn lock_config() -> Result<FileLock> {
+    lock_config_at(&config_path()?)
+}
+
 /// config.toml を読み込む。ファイルが存在しなければ空の Config を返す。
 pub fn load_config() -> Result<Config> {
     load_config_from(&config_path()?)
@@ -88,6 +227,37 @@ mod tests {
     use super::*;
     use tempfile::TempDir;
 
+    #[test]
+    fn parse_size_accepts_units() {
+        assert_eq!(parse_size("1024"), Some(1024));
+        assert_eq!(parse_size("10KB"), Some(10 * 1024));
+        assert_eq!(parse_size("1.5 GB"), Some(3 * (1 << 29)));
+        assert_eq!(parse_size("500m"), Some(500 * (1 << 20)));
+        assert_eq!(parse_size("ten"), None);
+        assert_eq!(parse_size("10XB"), None);
+    }
+
+    #[test]
+    fn store_section_is_optional_and_preserved() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("config.toml");
+        std::fs::write(&path, "[store]\nmax_size = \"1GB\"\n").unwrap();
+
+        let config = load_config_from(&path).unwrap();
+        assert_eq!(config.store.max_size().unwrap(), Some(1 << 30));
+        assert_eq!(config.store.warn_size().unwrap(), DEFAULT_WARN_SIZE);
+        assert_eq!(
+            config.store.trash_retention_days(),
+            DEFAULT_TRASH_RETENTION_DAYS
+        );
+
+        save_config_to(&config, &path).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.contains("max_size = \"1GB\""));
+        assert!(!content.contains("warn_size"));
+        assert!(!content.contains("trash_retention_days"));
+    }
+
     #[test]
     fn load_config_returns_default_for_missing_file() {
         let tmp = TempDir::new().unwrap();
@@ -107,6 +277,7 @@ mod tests {
             RepoEntry {
                 path: PathBuf::from("/home/user/projects/my-repo"),
                 url: Some("git@github.com:user/my-repo.git".to_string()),
+                ..Default::default()
             },
         );
         save_config_to(&config, &path).unwrap();
@@ -122,6 +293,58 @@ mod tests {
         );
     }
 
+    #[test]
+    fn repo_store_path_is_optional_and_expands_tilde() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "[repos.a]\npath = \"/a\"\nstore_path = \"~/stores/a\"\n\n[repos.b]\npath = \"/b\"\n",
+        )
+        .unwrap();
+
+        let config = load_config_from(&path).unwrap();
+        let store_path = config.repos["a"].store_path.as_ref().unwrap();
+        assert!(!store_path.starts_with("~"));
+        assert!(store_path.ends_with("stores/a"));
+        assert!(config.repos["b"].store_path.is_none());
+
+        save_config_to(&config, &path).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content.matches("store_path").count(), 1);
+    }
+
+    #[test]
+    fn store_rule_prefers_repo_rules_and_specific_patterns() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("repo");
+        std::fs::create_dir_all(&repo).unwrap();
+        let path = tmp.path().join("config.toml");
+        std::fs::write(
+            &path,
+            format!(
+                "[store.rules]\n\".env*\" = \"copy\"\n\".env.local\" = \"dotenv\"\n\
+                 \"*.json\" = \"copy\"\n\n\
+                 [repos.repo]\npath = {:?}\n\n\
+                 [repos.repo.store_rules]\n\"*.json\" = \"symlink\"\n",
+                repo.display().to_string()
+            ),
+        )
+        .unwrap();
+        let config = load_config_from(&path).unwrap();
+        let root = repo.canonicalize().unwrap();
+
+        assert_eq!(config.store_rule_for(None, ".env"), Some("copy"));
+        assert_eq!(config.store_rule_for(None, ".env.local"), Some("dotenv"));
+        assert_eq!(config.store_rule_for(None, "a.json"), Some("copy"));
+        assert_eq!(
+            config.store_rule_for(Some(&root), "a.json"),
+            Some("symlink")
+        );
+        assert_eq!(config.store_rule_for(Some(&root), ".env"), Some("copy"));
+        assert_eq!(config.store_rule_for(None, "README.md"), None);
+    }
+
     #[test]
     fn load_config_errors_on_malformed_toml() {
         let tmp = TempDir::new().unwrap();
@@ -1,19 +1,62 @@
 use anyhow::Result;
+use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use crate::commands::repos::{WorktreeEntry, parse_worktree_list};
 use crate::config::{Config, load_config};
 use crate::git::{resolve_repo_root, worktree_root};
-use crate::store::{ManifestEntry, read_manifest, store_dir};
+use crate::lock;
+use crate::store::{ManifestEntry, manifest_exists, read_manifest, store_dir};
 use crate::ui::{self, StyledCell};
 
+/// 登録済みリポジトリ 1 件を調べるのを待つ上限。遅いディスクやマウントが外れた
+/// ネットワークドライブで `ws status` 全体が止まらないようにする。
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+/// worktree 一覧のキャッシュを使う期間。`git worktree list` の HEAD はコミットで変わるので短くする。
+const WORKTREE_CACHE_TTL: Duration = Duration::from_secs(10);
+/// worktree 一覧のキャッシュのファイル（リポジトリの git ディレクトリの下）。
+const WORKTREE_CACHE_FILE: &str = "ws/worktree-list";
+
 pub struct AppContext {
     pub config: Config,
+    /// config に登録されたリポジトリを調べた結果（config と同じ順）。
+    pub repos: Vec<RepoProbe>,
     pub current_repo: Option<CurrentRepo>,
     pub current_workspace: Option<CurrentWorkspace>,
 }
 
+/// 登録済みリポジトリの種類。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RepoKind {
+    Bare,
+    Git,
+    /// パスが存在しないか、`PROBE_TIMEOUT` 以内に調べ終わらなかった
+    NotFound,
+}
+
+impl RepoKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Bare => "bare",
+            Self::Git => "git",
+            Self::NotFound => "NOT_FOUND",
+        }
+    }
+}
+
+pub struct RepoProbe {
+    pub name: String,
+    /// config に書かれたパス
+    pub path: PathBuf,
+    /// canonical なパス。調べられなかった場合は `None`
+    pub canonical: Option<PathBuf>,
+    pub kind: RepoKind,
+}
+
 pub struct CurrentRepo {
     pub name: Option<String>,
     pub root: PathBuf,
@@ -29,52 +72,45 @@ pub struct CurrentWorkspace {
 }
 
 impl AppContext {
+    /// 現在のリポジトリ・workspace の解決と、登録済みリポジトリの調査を並行して行う。
     pub fn build() -> Result<Self> {
         let config = load_config()?;
-        let current_repo = Self::resolve_current_repo(&config);
-        let current_workspace = Self::resolve_current_workspace();
+        let (repos, current_repo, current_workspace) = thread::scope(|s| {
+            let current_repo = s.spawn(Self::resolve_current_repo);
+            let current_workspace = s.spawn(Self::resolve_current_workspace);
+            let repos = probe_repos(&config, PROBE_TIMEOUT);
+            (
+                repos,
+                current_repo.join().ok().flatten(),
+                current_workspace.join().ok().flatten(),
+            )
+        });
+        let current_repo = current_repo.map(|mut repo| {
+            repo.name = repos
+                .iter()
+                .find(|probe| probe.canonical.as_ref() == Some(&repo.root))
+                .map(|probe| probe.name.clone());
+            repo
+        });
 
         Ok(AppContext {
             config,
+            repos,
             current_repo,
             current_workspace,
         })
     }
 
-    fn resolve_current_repo(config: &Config) -> Option<CurrentRepo> {
+    /// 現在のリポジトリを解決する。`name` は `build` で登録済みリポジトリと照合して埋める。
+    fn resolve_current_repo() -> Option<CurrentRepo> {
         let root = resolve_repo_root(None)?;
         let is_bare = root.join(".bare").is_dir();
-
-        let name = config.repos.iter().find_map(|(name, entry)| {
-            entry
-                .path
-                .canonicalize()
-                .ok()
-                .filter(|p| *p == root)
-                .map(|_| name.clone())
-        });
-
-        let wt_output = if is_bare {
-            Command::new("git")
-                .args(["--git-dir", ".bare", "worktree", "list"])
-                .current_dir(&root)
-                .output()
-                .ok()
-        } else {
-            Command::new("git")
-                .args(["worktree", "list"])
-                .current_dir(&root)
-                .output()
-                .ok()
-        };
-
-        let worktrees = wt_output
-            .filter(|o| o.status.success())
-            .map(|o| parse_worktree_list(&String::from_utf8_lossy(&o.stdout), &root))
+        let worktrees = worktree_list_output(&root, is_bare)
+            .map(|output| parse_worktree_list(&output, &root))
             .unwrap_or_default();
 
         Some(CurrentRepo {
-            name,
+            name: None,
             root,
             is_bare,
             worktrees,
@@ -88,7 +124,7 @@ impl AppContext {
 
         let store = store_dir()
             .ok()
-            .filter(|s| s.is_dir() && s.join("manifest").is_file());
+            .filter(|s| s.is_dir() && manifest_exists(s));
         let manifest = store
             .as_ref()
             .and_then(|s| read_manifest(s).ok())
@@ -103,6 +139,146 @@ impl AppContext {
     }
 }
 
+/// 登録済みリポジトリをそれぞれ別のスレッドで調べる。
+///
+/// `timeout` までに終わらなかったものは `NotFound` とし、そのスレッドは待たずに置いていく
+/// （応答しないパスの `stat` はプロセスの終了とともに捨てられる）。
+pub fn probe_repos(config: &Config, timeout: Duration) -> Vec<RepoProbe> {
+    let (tx, rx) = mpsc::channel();
+    for (index, entry) in config.repos.values().enumerate() {
+        let tx = tx.clone();
+        let path = entry.path.clone();
+        thread::spawn(move || {
+            let _ = tx.send((index, probe_path(&path)));
+        });
+    }
+    drop(tx);
+
+    let mut results = vec![None; config.repos.len()];
+    let deadline = Instant::now() + timeout;
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        match rx.recv_timeout(remaining) {
+            Ok((index, result)) => results[index] = Some(result),
+            // 全スレッドが終わったか、時間切れ
+            Err(_) => break,
+        }
+    }
+
+    config
+        .repos
+        .iter()
+        .zip(results)
+        .map(|((name, entry), result)| {
+            let (canonical, kind) = result.unwrap_or((None, RepoKind::NotFound));
+            RepoProbe {
+                name: name.clone(),
+                path: entry.path.clone(),
+                canonical,
+                kind,
+            }
+        })
+        .collect()
+}
+
+fn probe_path(path: &Path) -> (Option<PathBuf>, RepoKind) {
+    let Ok(canonical) = path.canonicalize() else {
+        return (None, RepoKind::NotFound);
+    };
+    let kind = if canonical.join(".bare").is_dir() {
+        RepoKind::Bare
+    } else {
+        RepoKind::Git
+    };
+    (Some(canonical), kind)
+}
+
+/// `git worktree list` の出力。`worktree_cache_key` が前回と同じで、
+/// `WORKTREE_CACHE_TTL` 以内ならキャッシュを使う。
+fn worktree_list_output(root: &Path, is_bare: bool) -> Option<String> {
+    let git_dir = root.join(if is_bare { ".bare" } else { ".git" });
+    let cache = git_dir.join(WORKTREE_CACHE_FILE);
+    let key = worktree_cache_key(&git_dir);
+    if let Some(ref key) = key
+        && let Some(output) = read_worktree_cache(&cache, key, SystemTime::now())
+    {
+        return Some(output);
+    }
+
+    let mut cmd = Command::new("git");
+    if is_bare {
+        cmd.args(["--git-dir", ".bare"]);
+    }
+    let output = cmd
+        .args(["worktree", "list"])
+        .current_dir(root)
+        .output()
+        .ok()
+        .filter(|o| o.status.success())?;
+    let output = String::from_utf8_lossy(&output.stdout).into_owned();
+    // キャッシュは速くするためだけのものなので、書けなくても続ける
+    if let Some(ref key) = key {
+        let _ = write_worktree_cache(&cache, key, SystemTime::now(), &output);
+    }
+    Some(output)
+}
+
+/// キャッシュのキー。worktree の追加・削除で変わる `<git ディレクトリ>/worktrees` の mtime に、
+/// 各 worktree の HEAD と、HEAD が指す branch の ref の mtime を続ける。
+/// 既存の worktree での `git switch` は HEAD だけを、commit は branch の ref だけを書き換えるので。
+fn worktree_cache_key(git_dir: &Path) -> Option<String> {
+    let mtime = |path: &Path| {
+        fs::metadata(path)
+            .and_then(|m| m.modified())
+            .map(format_mtime)
+            .unwrap_or_else(|_| "-".to_string())
+    };
+    let worktrees = git_dir.join("worktrees");
+    let mut key = fs::metadata(&worktrees)
+        .and_then(|m| m.modified())
+        .ok()
+        .map(format_mtime)?;
+    let mut admin_dirs: Vec<PathBuf> = fs::read_dir(&worktrees)
+        .map(|entries| entries.flatten().map(|e| e.path()).collect())
+        .unwrap_or_default();
+    admin_dirs.sort();
+    for dir in std::iter::once(git_dir.to_path_buf()).chain(admin_dirs) {
+        let head = dir.join("HEAD");
+        // branch の ref は共通の git ディレクトリにある（pack 済みなら packed-refs）
+        let branch_ref = fs::read_to_string(&head)
+            .ok()
+            .and_then(|content| Some(content.strip_prefix("ref: ")?.trim().to_string()))
+            .map(|name| git_dir.join(name))
+            .filter(|path| path.is_file())
+            .unwrap_or_else(|| git_dir.join("packed-refs"));
+        key.push_str(&format!(",{}:{}", mtime(&head), mtime(&branch_ref)));
+    }
+    Some(key)
+}
+
+fn format_mtime(time: SystemTime) -> String {
+    let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!("{}.{:09}", since.as_secs(), since.subsec_nanos())
+}
+
+/// キャッシュの 1 行目は `<キー> <作成時刻（Unix 秒）>`、2 行目以降が出力。
+fn read_worktree_cache(path: &Path, key: &str, now: SystemTime) -> Option<String> {
+    let content = fs::read_to_string(path).ok()?;
+    let (header, output) = content.split_once('\n')?;
+    let (cached_key, created) = header.split_once(' ')?;
+    let created = UNIX_EPOCH + Duration::from_secs(created.parse().ok()?);
+    let age = now.duration_since(created).ok()?;
+    (cached_key == key && age < WORKTREE_CACHE_TTL).then(|| output.to_string())
+}
+
+fn write_worktree_cache(path: &Path, key: &str, now: SystemTime, output: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let created = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    lock::write_atomic(path, format!("{} {}\n{}", key, created, output))?;
+    Ok(())
+}
+
 /// ホームディレクトリを `~` に短縮して表示する。
 pub fn abbreviate_home(path: &Path) -> String {
     if let Ok(home) = std::env::var("HOME") {
@@ -216,6 +392,95 @@ mod tests {
         assert_eq!(abbreviate_home(&path), "~/Projects/test");
     }
 
+    #[test]
+    fn probe_repos_reports_kind_and_missing_paths() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let bare = tmp.path().join("bare");
+        fs::create_dir_all(bare.join(".bare")).unwrap();
+        let plain = tmp.path().join("plain");
+        fs::create_dir_all(&plain).unwrap();
+        let mut config = Config::default();
+        for (name, path) in [
+            ("a", bare.clone()),
+            ("b", plain.clone()),
+            ("c", tmp.path().join("gone")),
+        ] {
+            config.repos.insert(
+                name.to_string(),
+                crate::config::RepoEntry {
+                    path,
+                    ..Default::default()
+                },
+            );
+        }
+
+        let probes = probe_repos(&config, PROBE_TIMEOUT);
+        let kinds: Vec<_> = probes.iter().map(|p| (p.name.as_str(), p.kind)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("a", RepoKind::Bare),
+                ("b", RepoKind::Git),
+                ("c", RepoKind::NotFound)
+            ]
+        );
+        assert_eq!(probes[0].canonical, Some(bare.canonicalize().unwrap()));
+        assert_eq!(probes[2].canonical, None);
+    }
+
+    #[test]
+    fn worktree_cache_expires_and_follows_key() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join(WORKTREE_CACHE_FILE);
+        let now = SystemTime::now();
+        write_worktree_cache(&path, "1.000000001", now, "/repo/main abc [main]\n").unwrap();
+
+        assert_eq!(
+            read_worktree_cache(&path, "1.000000001", now).as_deref(),
+            Some("/repo/main abc [main]\n")
+        );
+        assert_eq!(read_worktree_cache(&path, "2.000000000", now), None);
+        assert_eq!(
+            read_worktree_cache(&path, "1.000000001", now + WORKTREE_CACHE_TTL),
+            None
+        );
+    }
+
+    #[test]
+    fn worktree_cache_key_follows_each_worktree_head() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let git_dir = tmp.path().join(".git");
+        let admin = git_dir.join("worktrees/feat");
+        fs::create_dir_all(&admin).unwrap();
+        fs::create_dir_all(git_dir.join("refs/heads")).unwrap();
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        fs::write(git_dir.join("refs/heads/main"), "abc\n").unwrap();
+        fs::write(admin.join("HEAD"), "ref: refs/heads/feat\n").unwrap();
+        fs::write(git_dir.join("refs/heads/feat"), "abc\n").unwrap();
+        let set_mtime = |path: &Path, secs: u64| {
+            fs::File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(UNIX_EPOCH + Duration::from_secs(secs))
+                .unwrap();
+        };
+        set_mtime(&admin.join("HEAD"), 1);
+        set_mtime(&git_dir.join("refs/heads/feat"), 1);
+        let key = worktree_cache_key(&git_dir).unwrap();
+
+        // 既存の worktree で git switch すると HEAD だけが変わる
+        fs::write(admin.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        set_mtime(&admin.join("HEAD"), 2);
+        let switched = worktree_cache_key(&git_dir).unwrap();
+        assert_ne!(switched, key);
+
+        // commit すると branch の ref だけが変わる
+        set_mtime(&git_dir.join("refs/heads/main"), 3);
+        assert_ne!(worktree_cache_key(&git_dir).unwrap(), switched);
+        assert!(!switched.contains(' '));
+    }
+
     #[test]
     fn abbreviate_home_leaves_non_home_paths() {
         let path = PathBuf::from("/tmp/something");
@@ -0,0 +1,282 @@
+/// キー単位の差分（store → worktree の向き）。
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyChange {
+    /// worktree にだけ存在するキー
+    Added { key: String, value: String },
+    /// store にだけ存在するキー
+    Removed { key: String, value: String },
+    /// 両方に存在し値が異なるキー
+    Changed {
+        key: String,
+        store_value: String,
+        worktree_value: String,
+    },
+}
+
+/// 行が `KEY=VALUE`（`export KEY=VALUE` を含む）形式ならキーと値を返す。
+/// 値は引用符を含めた生の文字列として扱う。
+fn parse_line(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim_start();
+    if trimmed.starts_with('#') {
+        return None;
+    }
+    let trimmed = trimmed.strip_prefix("export ").unwrap_or(trimmed);
+    let (key, value) = trimmed.split_once('=')?;
+    let key = key.trim();
+    if key.is_empty() || key.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((key, value.trim()))
+}
+
+/// 値が引用符で始まり、まだ閉じていないか判定する。`"` の中だけ `\` でエスケープできる。
+fn is_open_quote(value: &str) -> bool {
+    let mut chars = value.chars();
+    let Some(quote) = chars.next().filter(|c| matches!(c, '"' | '\'' | '`')) else {
+        return false;
+    };
+    let mut escaped = false;
+    for c in chars {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' && quote == '"' {
+            escaped = true;
+        } else if c == quote {
+            return false;
+        }
+    }
+    true
+}
+
+/// 内容を論理行に分ける。引用符で囲んだ値が閉じるまでの行（`KEY="a\nb"`）は 1 つにまとめる。
+/// 閉じないままファイルが終わった場合は末尾までを値とする。
+fn records(content: &str) -> Vec<String> {
+    let mut records = Vec::new();
+    let mut lines = content.lines();
+    while let Some(line) = lines.next() {
+        let mut record = line.to_string();
+        while parse_line(&record).is_some_and(|(_, value)| is_open_quote(value)) {
+            let Some(next) = lines.next() else { break };
+            record.push('\n');
+            record.push_str(next);
+        }
+        records.push(record);
+    }
+    records
+}
+
+/// ファイル内容からキーと値を出現順に取り出す。同じキーが複数回現れた場合は後勝ち。
+pub fn parse(content: &str) -> Vec<(String, String)> {
+    let mut entries: Vec<(String, String)> = Vec::new();
+    let records = records(content);
+    for (key, value) in records.iter().filter_map(|r| parse_line(r)) {
+        match entries.iter_mut().find(|(k, _)| k == key) {
+            Some(existing) => existing.1 = value.to_string(),
+            None => entries.push((key.to_string(), value.to_string())),
+        }
+    }
+    entries
+}
+
+fn lookup<'a>(entries: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    entries
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+}
+
+/// 2つの内容がキーと値の集合として等しいか判定する（コメントや並び順は無視）。
+pub fn equivalent(a: &str, b: &str) -> bool {
+    let mut a = parse(a);
+    let mut b = parse(b);
+    a.sort();
+    b.sort();
+    a == b
+}
+
+/// store の内容と worktree の内容をキー単位で比較する。
+pub fn diff(store: &str, worktree: &str) -> Vec<KeyChange> {
+    let store_entries = parse(store);
+    let wt_entries = parse(worktree);
+    let mut changes = Vec::new();
+
+    for (key, value) in &wt_entries {
+        match lookup(&store_entries, key) {
+            None => changes.push(KeyChange::Added {
+                key: key.clone(),
+                value: value.clone(),
+            }),
+            Some(store_value) if store_value != value => changes.push(KeyChange::Changed {
+                key: key.clone(),
+                store_value: store_value.to_string(),
+                worktree_value: value.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for (key, value) in &store_entries {
+        if lookup(&wt_entries, key).is_none() {
+            changes.push(KeyChange::Removed {
+                key: key.clone(),
+                value: value.clone(),
+            });
+        }
+    }
+    changes
+}
+
+/// `source` のキーを `target` に取り込んだ内容と、変更したキーの数を返す。
+///
+/// - `target` にないキーは末尾に追加する
+/// - `overwrite` が true なら、値の異なる既存キーを `source` の値で置き換える
+/// - `target` にだけあるキーはそのまま残す
+pub fn merge(target: &str, source: &str, overwrite: bool) -> (String, usize) {
+    let source_entries = parse(source);
+    let target_entries = parse(target);
+    let mut changed = 0;
+
+    let mut lines: Vec<String> = Vec::new();
+    for line in records(target) {
+        match parse_line(&line) {
+            Some((key, value)) if overwrite => match lookup(&source_entries, key) {
+                Some(new_value) if new_value != value => {
+                    let prefix = if line.trim_start().starts_with("export ") {
+                        "export "
+                    } else {
+                        ""
+                    };
+                    lines.push(format!("{prefix}{key}={new_value}"));
+                    changed += 1;
+                }
+                _ => lines.push(line.clone()),
+            },
+            _ => lines.push(line.clone()),
+        }
+    }
+
+    for (key, value) in &source_entries {
+        if lookup(&target_entries, key).is_none() {
+            lines.push(format!("{key}={value}"));
+            changed += 1;
+        }
+    }
+
+    let mut merged = lines.join("\n");
+    if !merged.is_empty() {
+        merged.push('\n');
+    }
+    (merged, changed)
+}
+
+/// 表示用に値をマスクする。
+pub fn mask(value: &str, show_values: bool) -> String {
+    if show_values {
+        value.to_string()
+    } else {
+        "****".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_skips_comments_and_handles_export() {
+        let entries = parse("# comment\n\nexport A=1\nB = \"two\"\nnot a pair\nA=3\n");
+        assert_eq!(
+            entries,
+            vec![
+                ("A".to_string(), "3".to_string()),
+                ("B".to_string(), "\"two\"".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_keeps_multiline_quoted_values_together() {
+        let content = "A=\"first\nB=not a key\\\"\n\"\nC='x\ny'\nD=4\n";
+        assert_eq!(
+            parse(content),
+            vec![
+                ("A".to_string(), "\"first\nB=not a key\\\"\n\"".to_string()),
+                ("C".to_string(), "'x\ny'".to_string()),
+                ("D".to_string(), "4".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_round_trips_multiline_values() {
+        let store = "KEY=\"-----BEGIN-----\nabc\n-----END-----\"\nA=1\n";
+        let wt = "KEY=\"-----BEGIN-----\nxyz\n-----END-----\"\nA=1\nB=2\n";
+        let (merged, changed) = merge(store, wt, true);
+        assert_eq!(
+            merged,
+            "KEY=\"-----BEGIN-----\nxyz\n-----END-----\"\nA=1\nB=2\n"
+        );
+        assert_eq!(changed, 2);
+
+        let (unchanged, changed) = merge(store, store, true);
+        assert_eq!(unchanged, store);
+        assert_eq!(changed, 0);
+    }
+
+    #[test]
+    fn equivalent_ignores_comments_and_order() {
+        assert!(equivalent("A=1\nB=2\n", "# header\nB=2\nA=1"));
+        assert!(!equivalent("A=1\n", "A=2\n"));
+    }
+
+    #[test]
+    fn diff_reports_added_removed_and_changed() {
+        let changes = diff("A=1\nB=2\n", "A=1\nB=20\nC=3\n");
+        assert_eq!(
+            changes,
+            vec![
+                KeyChange::Changed {
+                    key: "B".into(),
+                    store_value: "2".into(),
+                    worktree_value: "20".into(),
+                },
+                KeyChange::Added {
+                    key: "C".into(),
+                    value: "3".into(),
+                },
+            ]
+        );
+
+        let changes = diff("A=1\nB=2\n", "A=1\n");
+        assert_eq!(
+            changes,
+            vec![KeyChange::Removed {
+                key: "B".into(),
+                value: "2".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn merge_with_overwrite_updates_only_changed_keys() {
+        let store = "# shared\nA=1\nexport B=2\nKEEP=x\n";
+        let wt = "A=1\nB=20\nC=3\n";
+        let (merged, changed) = merge(store, wt, true);
+        assert_eq!(merged, "# shared\nA=1\nexport B=20\nKEEP=x\nC=3\n");
+        assert_eq!(changed, 2);
+    }
+
+    #[test]
+    fn merge_without_overwrite_keeps_local_values() {
+        let wt = "A=local\n";
+        let store = "A=shared\nNEW=1\n";
+        let (merged, changed) = merge(wt, store, false);
+        assert_eq!(merged, "A=local\nNEW=1\n");
+        assert_eq!(changed, 1);
+    }
+
+    #[test]
+    fn mask_hides_values_by_default() {
+        assert_eq!(mask("secret", false), "****");
+        assert_eq!(mask("secret", true), "secret");
+    }
+}
@@ -0,0 +1,343 @@
+use anyhow::Result;
+use std::fmt::Display;
+use std::fs;
+use std::os::unix::fs as unix_fs;
+use std::os::unix::process::ExitStatusExt;
+use std::path::Path;
+use std::process::{Command, ExitStatus};
+
+use crate::lock;
+use crate::pattern::PathFilter;
+use crate::store::{self, Strategy};
+use crate::ui;
+
+/// ファイルシステムと git への変更操作を実行する層。
+///
+/// `--dry-run` 時は操作を実行せず、実行するはずだった操作を表示するだけにする。
+/// 変更を伴う操作はすべてここを経由させ、読み取り専用の操作は直接呼んでよい。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Executor {
+    dry_run: bool,
+}
+
+impl Executor {
+    pub fn new(dry_run: bool) -> Self {
+        Executor { dry_run }
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// dry-run なら `op` を表示し、そうでなければ `f` を実行する。
+    pub fn run<T: Default>(&self, op: impl Display, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        if self.dry_run {
+            anstream::println!("{} {}", ui::styled(ui::STYLE_WARN, "[dry-run]"), op);
+            Ok(T::default())
+        } else {
+            f()
+        }
+    }
+
+    /// 外部コマンドを実行する。dry-run では成功したものとして扱う。
+    pub fn status(&self, cmd: &mut Command) -> std::io::Result<ExitStatus> {
+        if self.dry_run {
+            anstream::println!(
+                "{} {}",
+                ui::styled(ui::STYLE_WARN, "[dry-run]"),
+                format_command(cmd)
+            );
+            Ok(ExitStatus::from_raw(0))
+        } else {
+            cmd.status()
+        }
+    }
+
+    pub fn create_dir_all(&self, path: &Path) -> Result<()> {
+        if path.is_dir() {
+            return Ok(());
+        }
+        self.run(format!("mkdir -p {}", path.display()), || {
+            Ok(fs::create_dir_all(path)?)
+        })
+    }
+
+    pub fn write(&self, path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+        self.run(format!("write {}", path.display()), || {
+            lock::write_atomic(path, contents)
+        })
+    }
+
+    pub fn copy(&self, src: &Path, dst: &Path) -> Result<()> {
+        let flag = if src.is_dir() { "-Rp" } else { "-p" };
+        self.run(
+            format!("cp {} {} {}", flag, src.display(), dst.display()),
+            || store::copy_entry(src, dst),
+        )
+    }
+
+    /// ディレクトリのうち `filter` が扱うパスだけをコピーする（`store::copy_entry_filtered`）。
+    pub fn copy_filtered(&self, src: &Path, dst: &Path, filter: &PathFilter) -> Result<()> {
+        if filter.is_empty() || !src.is_dir() {
+            return self.copy(src, dst);
+        }
+        self.run(
+            format!(
+                "rsync -a{} {}/ {}",
+                filter_args(filter),
+                src.display(),
+                dst.display()
+            ),
+            || store::copy_entry_filtered(src, dst, filter, store::link_threshold()),
+        )
+    }
+
+    /// strategy に応じて `src` を `dst` に配置する（`store::place_entry`）。
+    pub fn place(
+        &self,
+        strategy: &Strategy,
+        src: &Path,
+        dst: &Path,
+        filter: &PathFilter,
+    ) -> Result<()> {
+        let recursive = if src.is_dir() { "R" } else { "" };
+        let op = match strategy {
+            Strategy::LinkTree => format!("cp -Rs {}/. {}", src.display(), dst.display()),
+            _ if !filter.is_empty() && src.is_dir() => {
+                let link = if *strategy == Strategy::Hardlink {
+                    format!(" --link-dest={}", src.display())
+                } else {
+                    String::new()
+                };
+                format!(
+                    "rsync -a{}{} {}/ {}",
+                    link,
+                    filter_args(filter),
+                    src.display(),
+                    dst.display()
+                )
+            }
+            Strategy::Symlink => format!("ln -s {} {}", src.display(), dst.display()),
+            Strategy::Copy
+            | Strategy::Dotenv
+            | Strategy::Seed
+            | Strategy::Command
+            | Strategy::MergeJson => {
+                format!("cp -{}p {} {}", recursive, src.display(), dst.display())
+            }
+            Strategy::Hardlink if src.is_dir() => {
+                format!("cp -Rl {} {}", src.display(), dst.display())
+            }
+            Strategy::Hardlink => format!("ln {} {}", src.display(), dst.display()),
+            Strategy::Clone => format!(
+                "cp --reflink=auto -{}p {} {}",
+                recursive,
+                src.display(),
+                dst.display()
+            ),
+        };
+        self.run(op, || store::place_entry(strategy, src, dst, filter))
+    }
+
+    /// link-tree で track する。worktree のファイルを store に移し、store への symlink に置き換える
+    /// （`store::adopt_tree`）。
+    pub fn adopt_tree(&self, wt_dir: &Path, store_dir: &Path, filter: &PathFilter) -> Result<()> {
+        self.run(
+            format!(
+                "cp -Rp {wt}/. {store} && cp -Rsf {store}/. {wt}",
+                wt = wt_dir.display(),
+                store = store_dir.display()
+            ),
+            || store::adopt_tree(wt_dir, store_dir, filter),
+        )
+    }
+
+    /// link-tree の symlink を、リンク先の内容のコピーに置き換える（`store::unlink_tree`）。
+    pub fn unlink_tree(&self, dir: &Path) -> Result<()> {
+        self.run(
+            format!(
+                "cp -RLp {0} {0}.tmp && rm -rf {0} && mv {0}.tmp {0}",
+                dir.display()
+            ),
+            || store::unlink_tree(dir),
+        )
+    }
+
+    /// link-tree のディレクトリを store から切り離して作り直す（`store::detach_tree`）。
+    pub fn detach_tree(&self, src: &Path, dst: &Path, store_dir: &Path, purge: bool) -> Result<()> {
+        let line = if purge {
+            format!(
+                "cp -Rp {} {} (without links to {})",
+                src.display(),
+                dst.display(),
+                store_dir.display()
+            )
+        } else {
+            format!("cp -RLp {} {}", src.display(), dst.display())
+        };
+        self.run(line, || store::detach_tree(src, dst, store_dir, purge))
+    }
+
+    /// link-tree の symlink を新しい store のパスに張り直す（`store::relink_tree`）。
+    pub fn relink_tree(&self, dir: &Path, old_store: &Path, new_store: &Path) -> Result<()> {
+        self.run(
+            format!(
+                "relink {} ({} -> {})",
+                dir.display(),
+                old_store.display(),
+                new_store.display()
+            ),
+            || store::relink_tree(dir, old_store, new_store),
+        )
+    }
+
+    pub fn rename(&self, src: &Path, dst: &Path) -> Result<()> {
+        self.run(format!("mv {} {}", src.display(), dst.display()), || {
+            Ok(fs::rename(src, dst)?)
+        })
+    }
+
+    pub fn symlink(&self, src: &Path, dst: &Path) -> Result<()> {
+        self.run(format!("ln -s {} {}", src.display(), dst.display()), || {
+            Ok(unix_fs::symlink(src, dst)?)
+        })
+    }
+
+    /// ファイル・symlink・ディレクトリを削除する。
+    pub fn remove(&self, path: &Path) -> Result<()> {
+        let is_dir = path.symlink_metadata().map(|m| m.is_dir()).unwrap_or(false);
+        if is_dir {
+            self.run(format!("rm -rf {}", path.display()), || {
+                Ok(fs::remove_dir_all(path)?)
+            })
+        } else {
+            self.run(format!("rm {}", path.display()), || {
+                Ok(fs::remove_file(path)?)
+            })
+        }
+    }
+
+    /// エントリを削除する。ディレクトリは `filter` が扱うファイルだけを削除し、除外したものは残す。
+    pub fn remove_entry(&self, path: &Path, filter: &PathFilter) -> Result<()> {
+        let is_dir = path.symlink_metadata().map(|m| m.is_dir()).unwrap_or(false);
+        if filter.is_empty() || !is_dir {
+            return self.remove(path);
+        }
+        self.run(
+            format!("rm -r {} (filter:{})", path.display(), filter_args(filter)),
+            || store::remove_filtered(path, filter),
+        )
+    }
+
+    /// manifest に記録されたパーミッションを適用する。記録がなければ何もしない。
+    pub fn apply_mode(&self, path: &Path, mode: Option<u32>) -> Result<()> {
+        let Some(mode) = mode else {
+            return Ok(());
+        };
+        self.run(
+            format!("chmod {} {}", store::format_mode(mode), path.display()),
+            || store::apply_mode(path, Some(mode)),
+        )
+    }
+}
+
+/// dry-run の表示用に、フィルタを rsync の引数の形で並べる（先頭に空白が付く）。
+fn filter_args(filter: &PathFilter) -> String {
+    let include = filter.include.iter().map(|p| format!(" --include={}", p));
+    let exclude = filter.exclude.iter().map(|p| format!(" --exclude={}", p));
+    exclude.chain(include).collect()
+}
+
+/// `Command` を表示用のコマンドライン文字列に変換する。
+fn format_command(cmd: &Command) -> String {
+    let mut parts = vec![cmd.get_program().to_string_lossy().into_owned()];
+    parts.extend(cmd.get_args().map(|a| a.to_string_lossy().into_owned()));
+    let line = parts.join(" ");
+    match cmd.get_current_dir() {
+        Some(dir) => format!("(cd {} && {})", dir.display(), line),
+        None => line,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn dry_run_does_not_touch_filesystem() {
+        let tmp = TempDir::new().unwrap();
+        let exec = Executor::new(true);
+        let src = tmp.path().join("src");
+        fs::write(&src, "x").unwrap();
+
+        exec.create_dir_all(&tmp.path().join("a/b")).unwrap();
+        exec.write(&tmp.path().join("file"), "content").unwrap();
+        exec.copy(&src, &tmp.path().join("dst")).unwrap();
+        exec.symlink(&src, &tmp.path().join("link")).unwrap();
+        exec.remove(&src).unwrap();
+
+        assert!(!tmp.path().join("a").exists());
+        assert!(!tmp.path().join("file").exists());
+        assert!(!tmp.path().join("dst").exists());
+        assert!(tmp.path().join("link").symlink_metadata().is_err());
+        assert!(src.exists());
+    }
+
+    #[test]
+    fn dry_run_does_not_spawn_commands() {
+        let tmp = TempDir::new().unwrap();
+        let marker = tmp.path().join("marker");
+        let status = Executor::new(true)
+            .status(Command::new("touch").arg(&marker))
+            .unwrap();
+        assert!(status.success());
+        assert!(!marker.exists());
+    }
+
+    #[test]
+    fn executes_when_not_dry_run() {
+        let tmp = TempDir::new().unwrap();
+        let exec = Executor::new(false);
+        let dir = tmp.path().join("a/b");
+        exec.create_dir_all(&dir).unwrap();
+        exec.write(&dir.join("file"), "content").unwrap();
+        exec.remove(&tmp.path().join("a")).unwrap();
+        assert!(!tmp.path().join("a").exists());
+    }
+
+    #[test]
+    fn filtered_copy_and_remove_keep_excluded_files() {
+        let tmp = TempDir::new().unwrap();
+        let exec = Executor::new(false);
+        let src = tmp.path().join("src");
+        fs::create_dir_all(src.join("caches")).unwrap();
+        fs::write(src.join("modules.xml"), "m").unwrap();
+        fs::write(src.join("workspace.xml"), "w").unwrap();
+        fs::write(src.join("caches/blob"), "b").unwrap();
+        let filter = PathFilter {
+            include: Vec::new(),
+            exclude: vec!["workspace.xml".to_string(), "caches/".to_string()],
+        };
+
+        let dst = tmp.path().join("dst");
+        exec.copy_filtered(&src, &dst, &filter).unwrap();
+        assert!(dst.join("modules.xml").is_file());
+        assert!(!dst.join("workspace.xml").exists());
+        assert!(!dst.join("caches").exists());
+
+        exec.remove_entry(&src, &filter).unwrap();
+        assert!(!src.join("modules.xml").exists());
+        assert!(src.join("workspace.xml").is_file());
+        assert!(src.join("caches/blob").is_file());
+        exec.remove_entry(&dst, &filter).unwrap();
+        assert!(!dst.exists());
+    }
+
+    #[test]
+    fn format_command_includes_cwd() {
+        let mut cmd = Command::new("git");
+        cmd.args(["worktree", "add", "feat"]).current_dir("/repo");
+        assert_eq!(format_command(&cmd), "(cd /repo && git worktree add feat)");
+    }
+}
@@ -76,6 +76,41 @@ pub fn git_output_in(dir: &Path, args: &[&str]) -> Result<String> {
     Ok(String::from_utf8(output.stdout)?.trim().to_string())
 }
 
+/// 全 worktree（bare エントリを除く）の絶対パスを返す。
+pub fn worktree_paths() -> Result<Vec<PathBuf>> {
+    let output = git_output(&["worktree", "list", "--porcelain"])?;
+    Ok(parse_worktree_porcelain(&output))
+}
+
+/// `git worktree list --porcelain` の出力から bare 以外の worktree パスを抽出する。
+fn parse_worktree_porcelain(output: &str) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    for block in output.split("\n\n") {
+        let mut path = None;
+        let mut is_bare = false;
+        for line in block.lines() {
+            if let Some(p) = line.strip_prefix("worktree ") {
+                path = Some(PathBuf::from(p));
+            } else if line == "bare" {
+                is_bare = true;
+            }
+        }
+        if let Some(p) = path
+            && !is_bare
+        {
+            paths.push(p);
+        }
+    }
+    paths
+}
+
+/// worktree でチェックアウトしているブランチ名を返す。detached HEAD なら `None`。
+pub fn worktree_branch(dir: &Path) -> Option<String> {
+    git_output_in(dir, &["symbolic-ref", "--short", "-q", "HEAD"])
+        .ok()
+        .filter(|b| !b.is_empty())
+}
+
 pub fn worktree_root() -> Result<PathBuf> {
     let root = git_output(&["rev-parse", "--show-toplevel"])
         .context(t!("git.run_inside_worktree").to_string())?;
@@ -146,3 +181,18 @@ pub fn resolve_repo_root(path: Option<&Path>) -> Option<PathBuf> {
 
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_worktree_porcelain_skips_bare() {
+        let output = "worktree /repo/.bare\nbare\n\nworktree /repo/main\nHEAD abc123\nbranch refs/heads/main\n\nworktree /repo/feat x\nHEAD def456\ndetached\n";
+        let paths = parse_worktree_porcelain(output);
+        assert_eq!(
+            paths,
+            vec![PathBuf::from("/repo/main"), PathBuf::from("/repo/feat x")]
+        );
+    }
+}
@@ -0,0 +1,285 @@
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use crate::lock;
+use crate::pattern::PathFilter;
+
+/// manifest に記録するハッシュの接頭辞。アルゴリズムを変えたときに区別できるようにする。
+const PREFIX: &str = "sha256:";
+
+/// ファイルまたはディレクトリの内容のハッシュを計算する。
+///
+/// ディレクトリは配下の相対パスと各ファイルのハッシュから計算するので、
+/// ファイル名の変更や空ディレクトリの追加も差分として扱う。
+pub fn hash_path(path: &Path) -> io::Result<String> {
+    hash_path_filtered(path, &PathFilter::default())
+}
+
+/// `hash_path` と同じだが、ディレクトリは `filter` が扱うパスだけから計算する。
+pub fn hash_path_filtered(path: &Path, filter: &PathFilter) -> io::Result<String> {
+    HashCache::default().hash_filtered(path, "", filter)
+}
+
+/// バイト列のハッシュを manifest の表記で返す（書き込む前の内容を記録するとき用）。
+pub fn hash_bytes(content: impl AsRef<[u8]>) -> String {
+    format!("{}{}", PREFIX, to_hex(&Sha256::digest(content)))
+}
+
+fn hash_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut file = fs::File::open(path)?;
+    io::copy(&mut file, &mut hasher)?;
+    Ok(to_hex(&hasher.finalize()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// ファイルが書き換えられたかを判定するための stat 情報。
+///
+/// ctime は利用者が戻せないので、`cp -p` などで mtime が揃っていても書き換えを検出できる。
+#[derive(Debug, Clone, PartialEq)]
+struct Stamp {
+    size: u64,
+    mtime: (i64, i64),
+    ctime: (i64, i64),
+    ino: u64,
+}
+
+impl Stamp {
+    fn of(meta: &fs::Metadata) -> Self {
+        Stamp {
+            size: meta.size(),
+            mtime: (meta.mtime(), meta.mtime_nsec()),
+            ctime: (meta.ctime(), meta.ctime_nsec()),
+            ino: meta.ino(),
+        }
+    }
+}
+
+/// worktree のファイルのハッシュを stat 情報と一緒に覚えておくキャッシュ。
+///
+/// stat 情報が変わっていないファイルは読み直さないので、大きなファイルや
+/// ディレクトリでも `ws store status` が stat だけで済む。
+#[derive(Debug, Default)]
+pub struct HashCache {
+    path: Option<PathBuf>,
+    /// キャッシュファイルを書いた時刻。これ以降に更新されたファイルは同じ stat のまま
+    /// 書き換えられた可能性があるので、キャッシュを使わない。
+    saved_at: (i64, i64),
+    entries: BTreeMap<String, (Stamp, String)>,
+    used: BTreeMap<String, (Stamp, String)>,
+    dirty: bool,
+}
+
+impl HashCache {
+    /// キャッシュファイルを読み込む。存在しない・壊れている場合は空のキャッシュになる。
+    pub fn load(path: PathBuf) -> Self {
+        let entries = fs::read_to_string(&path)
+            .map(|content| content.lines().filter_map(parse_line).collect())
+            .unwrap_or_default();
+        let saved_at = fs::metadata(&path)
+            .map(|m| (m.mtime(), m.mtime_nsec()))
+            .unwrap_or_default();
+        HashCache {
+            path: Some(path),
+            saved_at,
+            entries,
+            ..Default::default()
+        }
+    }
+
+    /// `path` の内容のハッシュを返す。`key` はキャッシュ上の名前（worktree からの相対パス）。
+    pub fn hash(&mut self, path: &Path, key: &str) -> io::Result<String> {
+        self.hash_filtered(path, key, &PathFilter::default())
+    }
+
+    /// `hash` と同じだが、ディレクトリは `filter` が扱うパスだけから計算する。
+    pub fn hash_filtered(
+        &mut self,
+        path: &Path,
+        key: &str,
+        filter: &PathFilter,
+    ) -> io::Result<String> {
+        let meta = fs::metadata(path)?;
+        if meta.is_dir() {
+            let mut lines = String::new();
+            self.collect_dir(path, key, "", filter, &mut lines)?;
+            return Ok(format!("{}{}", PREFIX, to_hex(&Sha256::digest(lines))));
+        }
+        Ok(format!("{}{}", PREFIX, self.file_hash(path, key, &meta)?))
+    }
+
+    fn collect_dir(
+        &mut self,
+        dir: &Path,
+        key: &str,
+        rel: &str,
+        filter: &PathFilter,
+        out: &mut String,
+    ) -> io::Result<()> {
+        let mut names: Vec<_> = fs::read_dir(dir)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name())
+            .collect();
+        names.sort();
+        for name in names {
+            let path = dir.join(&name);
+            let rel = format!("{}{}", rel, name.to_string_lossy());
+            let meta = fs::metadata(&path)?;
+            if !filter.allows(&rel, meta.is_dir()) {
+                continue;
+            }
+            if meta.is_dir() {
+                out.push_str(&format!("{}/\n", rel));
+                self.collect_dir(&path, key, &format!("{}/", rel), filter, out)?;
+            } else {
+                let hash = self.file_hash(&path, &format!("{}/{}", key, rel), &meta)?;
+                out.push_str(&format!("{}\0{}\n", rel, hash));
+            }
+        }
+        Ok(())
+    }
+
+    fn file_hash(&mut self, path: &Path, key: &str, meta: &fs::Metadata) -> io::Result<String> {
+        let stamp = Stamp::of(meta);
+        let hash = match self.entries.get(key) {
+            Some((cached, hash)) if *cached == stamp && stamp.mtime < self.saved_at => hash.clone(),
+            _ => {
+                self.dirty = true;
+                hash_file(path)?
+            }
+        };
+        if self.path.is_some() {
+            self.used.insert(key.to_string(), (stamp, hash.clone()));
+        }
+        Ok(hash)
+    }
+
+    /// 変更があればキャッシュを書き出す。今回参照しなかったエントリは捨てる。
+    pub fn save(&self) -> Result<()> {
+        let Some(ref path) = self.path else {
+            return Ok(());
+        };
+        if !self.dirty && self.used.len() == self.entries.len() {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut content = String::new();
+        // 改行を含むパスは1行に書けないので、キャッシュせず毎回計算する
+        for (key, (stamp, hash)) in self.used.iter().filter(|(key, _)| !key.contains('\n')) {
+            content.push_str(&format!(
+                "{}\t{}\t{}.{}\t{}.{}\t{}\t{}\n",
+                hash,
+                stamp.size,
+                stamp.mtime.0,
+                stamp.mtime.1,
+                stamp.ctime.0,
+                stamp.ctime.1,
+                stamp.ino,
+                key
+            ));
+        }
+        lock::write_atomic(path, content)
+    }
+}
+
+/// キャッシュファイルの1行（`hash size mtime ctime ino key` のタブ区切り）を読む。
+fn parse_line(line: &str) -> Option<(String, (Stamp, String))> {
+    let mut fields = line.splitn(6, '\t');
+    let hash = fields.next()?.to_string();
+    let size = fields.next()?.parse().ok()?;
+    let mtime = parse_time(fields.next()?)?;
+    let ctime = parse_time(fields.next()?)?;
+    let ino = fields.next()?.parse().ok()?;
+    let key = fields.next()?.to_string();
+    Some((
+        key,
+        (
+            Stamp {
+                size,
+                mtime,
+                ctime,
+                ino,
+            },
+            hash,
+        ),
+    ))
+}
+
+fn parse_time(s: &str) -> Option<(i64, i64)> {
+    let (secs, nsecs) = s.split_once('.')?;
+    Some((secs.parse().ok()?, nsecs.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn hash_path_of_file_is_sha256() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("file");
+        fs::write(&path, "abc").unwrap();
+        assert_eq!(
+            hash_path(&path).unwrap(),
+            "sha256:ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn hash_path_of_dir_detects_renames_and_empty_dirs() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("dir");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a"), "x").unwrap();
+        let original = hash_path(&dir).unwrap();
+
+        fs::rename(dir.join("a"), dir.join("b")).unwrap();
+        let renamed = hash_path(&dir).unwrap();
+        assert_ne!(original, renamed);
+
+        fs::create_dir(dir.join("empty")).unwrap();
+        assert_ne!(renamed, hash_path(&dir).unwrap());
+    }
+
+    #[test]
+    fn cache_roundtrip_reuses_hashes() {
+        let tmp = TempDir::new().unwrap();
+        let file = tmp.path().join("file");
+        fs::write(&file, "abc").unwrap();
+        let cache_path = tmp.path().join("cache");
+
+        let mut cache = HashCache::load(cache_path.clone());
+        let hash = cache.hash(&file, "file").unwrap();
+        cache.save().unwrap();
+
+        let cache = HashCache::load(cache_path);
+        let (_, cached) = cache.entries.get("file").unwrap();
+        assert_eq!(format!("{}{}", PREFIX, cached), hash);
+    }
+
+    #[test]
+    fn cache_rehashes_rewritten_file() {
+        let tmp = TempDir::new().unwrap();
+        let file = tmp.path().join("file");
+        fs::write(&file, "abc").unwrap();
+
+        let mut cache = HashCache::load(tmp.path().join("cache"));
+        let before = cache.hash(&file, "file").unwrap();
+        // 同じサイズで書き換えても ctime が変わるので読み直す
+        fs::write(&file, "xyz").unwrap();
+        let after = cache.hash(&file, "file").unwrap();
+        assert_ne!(before, after);
+        assert_eq!(after, hash_path(&file).unwrap());
+    }
+}
@@ -0,0 +1,200 @@
+use serde_json::{Map, Value};
+
+/// JSON を読み込む。merge-json の対象はトップレベルがオブジェクトのファイルに限る。
+pub fn parse_object(content: &str) -> Option<Value> {
+    serde_json::from_str::<Value>(content)
+        .ok()
+        .filter(Value::is_object)
+}
+
+/// `base` に `overlay` を再帰的に重ねる。両方がオブジェクトのキーはさらにマージし、
+/// それ以外（配列やスカラー）は `overlay` の値で置き換える。
+pub fn deep_merge(base: &Value, overlay: &Value) -> Value {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            let mut merged = base.clone();
+            for (key, value) in overlay {
+                let value = match merged.get(key) {
+                    Some(existing) => deep_merge(existing, value),
+                    None => value.clone(),
+                };
+                merged.insert(key.clone(), value);
+            }
+            Value::Object(merged)
+        }
+        (_, overlay) => overlay.clone(),
+    }
+}
+
+/// `merged` を `base` に重ねた結果として見たときの overlay（worktree 側の変更分）を取り出す。
+///
+/// `deep_merge(base, &overlay(base, merged))` は、`base` から削除されたキーを除いて `merged` と等しい。
+/// オブジェクトは変わったキーだけを残し、それ以外は値ごと overlay に入れる。
+pub fn overlay(base: &Value, merged: &Value) -> Value {
+    match (base, merged) {
+        (Value::Object(base), Value::Object(merged)) => {
+            let mut diff = Map::new();
+            for (key, value) in merged {
+                match base.get(key) {
+                    Some(base_value) if base_value == value => {}
+                    Some(base_value @ Value::Object(_)) if value.is_object() => {
+                        diff.insert(key.clone(), overlay(base_value, value));
+                    }
+                    _ => {
+                        diff.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+            Value::Object(diff)
+        }
+        (_, merged) => merged.clone(),
+    }
+}
+
+/// worktree の内容 `local` のうち、前回マージした base（`previous`）にあったキーの変更だけを `base` に反映する。
+///
+/// 値の変更と削除を反映し、`previous` になかったキー（worktree 側で足した overlay）は
+/// `include_overlay` のときだけ反映する。反映した値の数も返す。
+pub fn apply_base_changes(
+    base: &Value,
+    previous: &Value,
+    local: &Value,
+    include_overlay: bool,
+) -> (Value, usize) {
+    let (Value::Object(base), Value::Object(previous), Value::Object(local)) =
+        (base, previous, local)
+    else {
+        return if previous == local {
+            (base.clone(), 0)
+        } else {
+            (local.clone(), 1)
+        };
+    };
+    let mut result = base.clone();
+    let mut changed = 0;
+    for (key, previous_value) in previous {
+        match local.get(key) {
+            None => {
+                if result.shift_remove(key).is_some() {
+                    changed += 1;
+                }
+            }
+            Some(value) if value == previous_value => {}
+            Some(value) => {
+                let (updated, count) = match result.get(key) {
+                    Some(current) => {
+                        apply_base_changes(current, previous_value, value, include_overlay)
+                    }
+                    None => (value.clone(), 1),
+                };
+                if result.get(key) != Some(&updated) {
+                    changed += count.max(1);
+                }
+                result.insert(key.clone(), updated);
+            }
+        }
+    }
+    if include_overlay {
+        for (key, value) in local {
+            if !previous.contains_key(key) && result.get(key) != Some(value) {
+                result.insert(key.clone(), value.clone());
+                changed += 1;
+            }
+        }
+    }
+    (Value::Object(result), changed)
+}
+
+/// 整形した JSON 文字列（末尾改行付き）にする。
+pub fn to_pretty(value: &Value) -> String {
+    let mut s = serde_json::to_string_pretty(value).unwrap_or_default();
+    s.push('\n');
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parse_object_rejects_non_objects() {
+        assert!(parse_object(r#"{"a": 1}"#).is_some());
+        assert!(parse_object("[1, 2]").is_none());
+        assert!(parse_object("{ broken").is_none());
+    }
+
+    #[test]
+    fn deep_merge_merges_nested_objects() {
+        let base = json!({"editor": {"tabSize": 2, "rulers": [80]}, "theme": "dark"});
+        let overlay = json!({"editor": {"tabSize": 4}, "local": true});
+        assert_eq!(
+            deep_merge(&base, &overlay),
+            json!({"editor": {"tabSize": 4, "rulers": [80]}, "theme": "dark", "local": true})
+        );
+    }
+
+    #[test]
+    fn deep_merge_replaces_arrays() {
+        let base = json!({"allow": ["a", "b"]});
+        let overlay = json!({"allow": ["c"]});
+        assert_eq!(deep_merge(&base, &overlay), json!({"allow": ["c"]}));
+    }
+
+    #[test]
+    fn overlay_keeps_only_changed_keys() {
+        let base = json!({"editor": {"tabSize": 2, "rulers": [80]}, "theme": "dark"});
+        let merged =
+            json!({"editor": {"tabSize": 4, "rulers": [80]}, "theme": "dark", "local": true});
+        let diff = overlay(&base, &merged);
+        assert_eq!(diff, json!({"editor": {"tabSize": 4}, "local": true}));
+        assert_eq!(deep_merge(&base, &diff), merged);
+    }
+
+    #[test]
+    fn overlay_reapplies_on_new_base() {
+        let old_base = json!({"theme": "dark", "editor": {"tabSize": 2}});
+        let local = json!({"theme": "dark", "editor": {"tabSize": 4}});
+        let new_base = json!({"theme": "light", "editor": {"tabSize": 2, "wrap": true}});
+        assert_eq!(
+            deep_merge(&new_base, &overlay(&old_base, &local)),
+            json!({"theme": "light", "editor": {"tabSize": 4, "wrap": true}})
+        );
+    }
+
+    #[test]
+    fn apply_base_changes_leaves_overlay_keys_out() {
+        let base = json!({"theme": "dark", "editor": {"tabSize": 2}, "shared": 1});
+        let previous = json!({"theme": "dark", "editor": {"tabSize": 2}, "removed": true});
+        let local = json!({
+            "theme": "light",
+            "editor": {"tabSize": 2, "fontSize": 14},
+            "local": true
+        });
+
+        let (pushed, changed) = apply_base_changes(&base, &previous, &local, false);
+        assert_eq!(
+            pushed,
+            json!({"theme": "light", "editor": {"tabSize": 2}, "shared": 1})
+        );
+        assert_eq!(changed, 1);
+
+        let (pushed, changed) = apply_base_changes(&base, &previous, &local, true);
+        assert_eq!(
+            pushed,
+            json!({
+                "theme": "light",
+                "editor": {"tabSize": 2, "fontSize": 14},
+                "shared": 1,
+                "local": true
+            })
+        );
+        assert_eq!(changed, 3);
+    }
+
+    #[test]
+    fn to_pretty_preserves_key_order() {
+        let value = parse_object(r#"{"z": 1, "a": 2}"#).unwrap();
+        assert_eq!(to_pretty(&value), "{\n  \"z\": 1,\n  \"a\": 2\n}\n");
+    }
+}
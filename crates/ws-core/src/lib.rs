@@ -2,8 +2,17 @@ pub mod cli;
 pub mod commands;
 pub mod config;
 pub mod context;
+pub mod dotenv;
+pub mod exec;
 pub mod git;
+pub mod hash;
+pub mod json;
+pub mod lock;
+pub mod oplog;
+pub mod pattern;
+pub mod reflink;
 pub mod store;
+pub mod trash;
 pub mod ui;
 
 rust_i18n::i18n!("../../locales", fallback = "en");
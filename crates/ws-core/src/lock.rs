@@ -0,0 +1,137 @@
+use anyhow::{Context, Result};
+use rust_i18n::t;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// advisory ロックのガード。drop 時（ファイルを閉じた時点）でロックが解放される。
+///
+/// 複数の `ws` プロセス（フックとユーザー操作など）が同じファイルを
+/// read-modify-write するときに、書き込みが交互に混ざらないようにする。
+#[derive(Debug)]
+pub struct FileLock {
+    _file: File,
+}
+
+impl FileLock {
+    /// 排他ロックを取得する。他のプロセスが保持している間はブロックする。
+    pub fn exclusive(path: &Path) -> Result<Self> {
+        let file = open_lock_file(path)?;
+        file.lock().with_context(|| {
+            t!("lock.acquire_failed", path = path.display().to_string()).to_string()
+        })?;
+        Ok(FileLock { _file: file })
+    }
+
+    /// 共有ロックを取得する。読み取り同士は並行でき、排他ロックとは待ち合わせる。
+    pub fn shared(path: &Path) -> Result<Self> {
+        let file = open_lock_file(path)?;
+        file.lock_shared().with_context(|| {
+            t!("lock.acquire_failed", path = path.display().to_string()).to_string()
+        })?;
+        Ok(FileLock { _file: file })
+    }
+}
+
+fn open_lock_file(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+        .with_context(|| t!("lock.acquire_failed", path = path.display().to_string()).to_string())
+}
+
+/// 一時ファイルに書き込んでから rename で置き換える。
+///
+/// rename は同一ファイルシステム内でアトミックなので、途中でクラッシュしても
+/// 元の内容か新しい内容のどちらかが残り、書きかけのファイルにはならない。
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    let tmp = temp_path(path);
+    let result = (|| -> std::io::Result<()> {
+        let mut file = File::create(&tmp)?;
+        // 既存ファイルのパーミッションを引き継ぐ
+        if let Ok(meta) = fs::metadata(path) {
+            file.set_permissions(meta.permissions())?;
+        }
+        file.write_all(contents.as_ref())?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result.with_context(|| t!("lock.write_failed", path = path.display().to_string()).to_string())
+}
+
+/// 対象と同じディレクトリに置く一時ファイルのパス（rename をアトミックにするため）。
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.tmp.{}", name, std::process::id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    #[test]
+    fn write_atomic_replaces_content_and_leaves_no_temp_file() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("manifest");
+        fs::write(&path, "old\n").unwrap();
+
+        write_atomic(&path, "new\n").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+        let names: Vec<_> = fs::read_dir(tmp.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(names, vec!["manifest"]);
+    }
+
+    #[test]
+    fn write_atomic_keeps_permissions() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join(".env");
+        fs::write(&path, "A=1\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+
+        write_atomic(&path, "A=2\n").unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o600);
+    }
+
+    #[test]
+    fn write_atomic_fails_without_touching_target_dir() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("missing").join("file");
+        assert!(write_atomic(&path, "x").is_err());
+        assert!(!tmp.path().join("missing").exists());
+    }
+
+    #[test]
+    fn exclusive_lock_can_be_reacquired_after_drop() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join(".lock");
+        {
+            let _lock = FileLock::exclusive(&path).unwrap();
+            assert!(path.exists());
+        }
+        let _lock = FileLock::exclusive(&path).unwrap();
+    }
+
+    #[test]
+    fn shared_locks_can_coexist() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join(".lock");
+        let _a = FileLock::shared(&path).unwrap();
+        let _b = FileLock::shared(&path).unwrap();
+    }
+}
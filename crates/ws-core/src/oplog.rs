@@ -0,0 +1,283 @@
+use anyhow::{Context, Result};
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::exec::Executor;
+use crate::git;
+use crate::hash;
+use crate::store::path_or_symlink_exists;
+use crate::ui;
+
+/// store の操作ログのファイル名。1 行に 1 件の JSON を追記する（JSON Lines）。
+pub const OPLOG_FILE: &str = "oplog.jsonl";
+
+/// 操作ログに記録する store の操作。
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    Track,
+    Push,
+    Pull,
+    Untrack,
+    Apply,
+    Mv,
+    SetStrategy,
+    VariantAdd,
+    VariantRm,
+    Recover,
+    Migrate,
+}
+
+impl Action {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Action::Track => "track",
+            Action::Push => "push",
+            Action::Pull => "pull",
+            Action::Untrack => "untrack",
+            Action::Apply => "apply",
+            Action::Mv => "mv",
+            Action::SetStrategy => "set-strategy",
+            Action::VariantAdd => "variant-add",
+            Action::VariantRm => "variant-rm",
+            Action::Recover => "recover",
+            Action::Migrate => "migrate",
+        }
+    }
+
+    /// store 側の内容を変える操作か。pull・apply・recover は worktree 側だけを変える。
+    pub fn changes_store(&self) -> bool {
+        !matches!(self, Action::Pull | Action::Apply | Action::Recover)
+    }
+}
+
+/// 操作ログの 1 件。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpRecord {
+    /// UTC の RFC 3339 形式（例: `2026-10-19T12:34:56Z`）
+    pub time: String,
+    /// 操作した worktree の絶対パス。worktree の外から実行したときは空
+    pub worktree: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    pub user: String,
+    pub file: String,
+    pub action: Action,
+    /// 操作前の内容のハッシュ。pull / apply / recover は worktree 側、それ以外は store 側の内容。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    /// 操作後の内容のハッシュ。対象が消えた場合は `None`。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+}
+
+/// store の操作ログのパス。
+pub fn oplog_path(store: &Path) -> PathBuf {
+    store.join(OPLOG_FILE)
+}
+
+/// 1 つのコマンドの間、同じ worktree・branch・ユーザーで操作を記録する。
+///
+/// dry-run では何も記録せず、ハッシュの計算も省く。
+pub struct OpLog {
+    path: Option<PathBuf>,
+    worktree: String,
+    branch: Option<String>,
+    user: String,
+}
+
+impl OpLog {
+    /// `wt_root` は操作した worktree。worktree の外から実行したときは `None`。
+    pub fn new(store: &Path, wt_root: Option<&Path>, exec: &Executor) -> Self {
+        OpLog {
+            path: (!exec.is_dry_run()).then(|| oplog_path(store)),
+            worktree: wt_root.map(|p| p.display().to_string()).unwrap_or_default(),
+            branch: wt_root.and_then(git::worktree_branch),
+            user: current_user(),
+        }
+    }
+
+    /// 記録用に `path` の内容のハッシュを求める。存在しない場合と dry-run では `None`。
+    pub fn hash(&self, path: &Path) -> Option<String> {
+        if self.path.is_none() || !path_or_symlink_exists(path) {
+            return None;
+        }
+        hash::hash_path(path).ok()
+    }
+
+    /// 操作を 1 件追記する。ログに書けなくても操作自体は終わっているので、警告だけ出す。
+    pub fn record(
+        &self,
+        action: Action,
+        file: &str,
+        before: Option<String>,
+        after: Option<String>,
+    ) {
+        let Some(ref path) = self.path else {
+            return;
+        };
+        let record = OpRecord {
+            time: format_utc(now_secs()),
+            worktree: self.worktree.clone(),
+            branch: self.branch.clone(),
+            user: self.user.clone(),
+            file: file.to_string(),
+            action,
+            before,
+            after,
+        };
+        if let Err(e) = append(path, &record) {
+            anstream::eprintln!(
+                "{}",
+                ui::styled(
+                    ui::STYLE_WARN,
+                    &t!(
+                        "store.oplog_write_failed",
+                        path = path.display().to_string(),
+                        error = e.to_string()
+                    )
+                )
+            );
+        }
+    }
+}
+
+/// 1 件を 1 回の write で追記する。O_APPEND なので、並行する ws プロセスの行が混ざらない。
+fn append(path: &Path, record: &OpRecord) -> Result<()> {
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+/// 操作ログを古い順に読み込む。ログがまだなければ空。
+pub fn read_oplog(store: &Path) -> Result<Vec<OpRecord>> {
+    let path = oplog_path(store);
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path).with_context(|| {
+        t!("store.oplog_read_failed", path = path.display().to_string()).to_string()
+    })?;
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|e| {
+                anyhow::anyhow!(
+                    "{}",
+                    t!(
+                        "store.oplog_line_invalid",
+                        path = path.display().to_string(),
+                        line = index + 1,
+                        detail = e.to_string()
+                    )
+                )
+            })
+        })
+        .collect()
+}
+
+fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("LOGNAME"))
+        .ok()
+        .filter(|u| !u.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Unix 時刻を UTC の RFC 3339 形式にする。
+pub fn format_utc(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    // 1970-01-01 からの日数を年月日に変換する（proleptic Gregorian）
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn format_utc_converts_unix_time() {
+        assert_eq!(format_utc(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_utc(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_utc(1_792_413_296), "2026-10-19T12:34:56Z");
+    }
+
+    #[test]
+    fn record_appends_and_read_returns_in_order() {
+        let tmp = TempDir::new().unwrap();
+        let log = OpLog {
+            path: Some(oplog_path(tmp.path())),
+            worktree: "/repo/main".to_string(),
+            branch: Some("main".to_string()),
+            user: "alice".to_string(),
+        };
+        log.record(Action::Track, ".env", None, Some("sha256:a".to_string()));
+        log.record(
+            Action::Push,
+            ".env",
+            Some("sha256:a".to_string()),
+            Some("sha256:b".to_string()),
+        );
+
+        let records = read_oplog(tmp.path()).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].action, Action::Track);
+        assert_eq!(records[1].before.as_deref(), Some("sha256:a"));
+        assert_eq!(records[1].after.as_deref(), Some("sha256:b"));
+        assert_eq!(records[1].user, "alice");
+        let content = fs::read_to_string(oplog_path(tmp.path())).unwrap();
+        assert!(content.lines().all(|l| l.contains("\"action\":")));
+    }
+
+    #[test]
+    fn dry_run_log_records_nothing() {
+        let tmp = TempDir::new().unwrap();
+        let exec = Executor::new(true);
+        let log = OpLog::new(tmp.path(), Some(tmp.path()), &exec);
+        log.record(Action::Track, ".env", None, None);
+        assert!(log.hash(tmp.path()).is_none());
+        assert!(!oplog_path(tmp.path()).exists());
+    }
+
+    #[test]
+    fn read_oplog_reports_line_number() {
+        let tmp = TempDir::new().unwrap();
+        fs::write(oplog_path(tmp.path()), "\nnot json\n").unwrap();
+        let err = read_oplog(tmp.path()).err().unwrap().to_string();
+        assert!(err.contains("oplog.jsonl:2:"), "{}", err);
+    }
+}
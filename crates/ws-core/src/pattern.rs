@@ -0,0 +1,147 @@
+/// gitignore に近い書き方のグロブでパスを判定する。
+///
+/// - `*` は `/` 以外の0文字以上、`?` は `/` 以外の1文字に一致する
+/// - `**` はパスの区切りをまたいで0個以上のディレクトリに一致する（`a/**/b` は `a/b` にも一致）
+/// - `/` を含まないパターンは、どの階層のファイル名にも一致する（`.env*` は `apps/web/.env` にも一致）
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.trim_start_matches('/');
+    if !pattern.contains('/') {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        return match_segment(pattern.as_bytes(), name.as_bytes());
+    }
+    let pattern: Vec<&str> = pattern.split('/').collect();
+    let path: Vec<&str> = path.split('/').collect();
+    match_segments(&pattern, &path)
+}
+
+/// ディレクトリのエントリで扱う配下のパスを決める include / exclude のパターン。
+///
+/// パターンは `glob_match` と同じ書き方で、ディレクトリからの相対パスと比較する。
+/// `/` で終わるパターンはディレクトリだけに一致する。
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PathFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl PathFilter {
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// ディレクトリからの相対パス `rel` を扱うか。
+    ///
+    /// exclude に一致するものは除く（ディレクトリなら配下ごと）。include があれば、ファイルは
+    /// そのどれかに一致するものだけを扱う。ディレクトリは include に関係なくたどる。
+    pub fn allows(&self, rel: &str, is_dir: bool) -> bool {
+        let matches = |pattern: &String| match pattern.strip_suffix('/') {
+            Some(dir_pattern) => is_dir && glob_match(dir_pattern, rel),
+            None => glob_match(pattern, rel),
+        };
+        if self.exclude.iter().any(matches) {
+            return false;
+        }
+        is_dir || self.include.is_empty() || self.include.iter().any(matches)
+    }
+}
+
+/// ブランチ名をパターンと比較する。`glob_match` と違い、`/` を含まないパターンも常に先頭から比較する
+/// （`main` は `feature/main` に一致しない）。
+pub fn branch_match(pattern: &str, branch: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('/').collect();
+    let branch: Vec<&str> = branch.split('/').collect();
+    match_segments(&pattern, &branch)
+}
+
+/// パターンの具体性。ワイルドカード以外の文字数で、複数のパターンに一致したときに大きいほうを優先する。
+pub fn specificity(pattern: &str) -> usize {
+    pattern.chars().filter(|c| !matches!(c, '*' | '?')).count()
+}
+
+fn match_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| match_segments(rest, &path[skip..])),
+        Some((head, rest)) => match path.split_first() {
+            Some((name, path_rest)) => {
+                match_segment(head.as_bytes(), name.as_bytes()) && match_segments(rest, path_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+/// 1階層分（`/` を含まない）のパターンと名前を比較する。
+fn match_segment(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|skip| match_segment(rest, &name[skip..])),
+        Some((b'?', rest)) => !name.is_empty() && match_segment(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && match_segment(rest, &name[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pattern_without_slash_matches_any_level() {
+        assert!(glob_match(".env*", ".env"));
+        assert!(glob_match(".env*", ".env.local"));
+        assert!(glob_match(".env*", "apps/web/.env.local"));
+        assert!(!glob_match(".env*", "env"));
+        assert!(glob_match("?.txt", "a.txt"));
+        assert!(!glob_match("?.txt", "ab.txt"));
+    }
+
+    #[test]
+    fn pattern_with_slash_is_anchored() {
+        assert!(glob_match(".vscode/settings.json", ".vscode/settings.json"));
+        assert!(!glob_match(
+            ".vscode/settings.json",
+            "a/.vscode/settings.json"
+        ));
+        assert!(glob_match("/config/*.yml", "config/app.yml"));
+        assert!(!glob_match("config/*.yml", "config/sub/app.yml"));
+    }
+
+    #[test]
+    fn double_star_matches_zero_or_more_directories() {
+        let pattern = ".claude/**/*.local.json";
+        assert!(glob_match(pattern, ".claude/settings.local.json"));
+        assert!(glob_match(pattern, ".claude/a/b/settings.local.json"));
+        assert!(!glob_match(pattern, ".claude/settings.json"));
+        assert!(glob_match("**/node_modules", "packages/a/node_modules"));
+    }
+
+    #[test]
+    fn branch_pattern_is_always_anchored() {
+        assert!(branch_match("release/*", "release/1.2"));
+        assert!(!branch_match("release/*", "release/1.2/hotfix"));
+        assert!(branch_match("release/**", "release/1.2/hotfix"));
+        assert!(branch_match("main", "main"));
+        assert!(!branch_match("main", "feature/main"));
+    }
+
+    #[test]
+    fn path_filter_applies_exclude_before_include() {
+        let filter = PathFilter {
+            include: vec!["*.xml".to_string()],
+            exclude: vec!["workspace.xml".to_string(), "caches/".to_string()],
+        };
+        assert!(filter.allows("modules.xml", false));
+        assert!(filter.allows("codeStyles/Project.xml", false));
+        assert!(!filter.allows("workspace.xml", false));
+        assert!(!filter.allows(".DS_Store", false));
+        assert!(filter.allows("codeStyles", true));
+        assert!(!filter.allows("caches", true));
+        assert!(PathFilter::default().allows("anything", false));
+    }
+
+    #[test]
+    fn specificity_counts_literal_characters() {
+        assert!(specificity(".env.local") > specificity(".env*"));
+        assert!(specificity(".claude/**/*.json") > specificity("*.json"));
+    }
+}
@@ -1,24 +1,104 @@
 use anyhow::{Context, Result, bail};
 use rust_i18n::t;
 use std::fs;
-use std::io::Write;
 use std::os::unix::fs as unix_fs;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
 use std::path::{Path, PathBuf};
+use std::sync::Once;
 
+use serde::{Deserialize, Serialize};
+use toml::Spanned;
+
+use crate::dotenv;
+use crate::exec::Executor;
 use crate::git;
+use crate::hash::HashCache;
+use crate::json;
+use crate::lock::{self, FileLock};
+use crate::pattern::{self, PathFilter};
+use crate::reflink;
 use crate::ui;
 
 #[derive(Debug, Clone, PartialEq, clap::ValueEnum)]
 pub enum Strategy {
     Symlink,
     Copy,
+    Dotenv,
+    Hardlink,
+    Clone,
+    Seed,
+    Command,
+    MergeJson,
+    LinkTree,
 }
 
 impl Strategy {
+    /// すべての strategy（定義順）。
+    pub fn all() -> &'static [Strategy] {
+        <Self as clap::ValueEnum>::value_variants()
+    }
+
+    /// worktree 側の変更を `ws store push` で store に反映できる strategy か。
+    /// command の出力はコマンドから生成し直すものなので push しない。
+    /// link-tree は共有するファイルがすでに store を指していて、新しいファイルは worktree 固有のまま残す。
+    pub fn is_pushable(&self) -> bool {
+        !matches!(self, Self::Symlink | Self::Command | Self::LinkTree)
+    }
+
+    /// ファイル指定なしの `ws store push` / `pull` の対象になる strategy か。
+    ///
+    /// seed は worktree 作成時に一度だけコピーし、以降は各 worktree で自由に変えてよいので、
+    /// 明示的にファイルを指定したときだけ同期する。
+    pub fn syncs_by_default(&self) -> bool {
+        !matches!(self, Self::Seed)
+    }
+
+    /// store の内容が ws の操作（track / push）でしか変わらず、manifest にハッシュを記録できる strategy か。
+    ///
+    /// symlink・hardlink・link-tree は worktree での編集がそのまま store に届き、
+    /// command の store はコマンド出力のキャッシュなので記録しない。
+    pub fn records_hash(&self) -> bool {
+        !matches!(
+            self,
+            Self::Symlink | Self::Hardlink | Self::Command | Self::LinkTree
+        )
+    }
+
+    /// worktree 側が store と同じ実体を共有する strategy か。パーミッションは store 側のものになる。
+    pub fn shares_store(&self) -> bool {
+        matches!(self, Self::Symlink | Self::Hardlink | Self::LinkTree)
+    }
+
+    /// branch ごとの variant を持てる strategy か。
+    ///
+    /// command はコマンドの出力、merge-json は worktree ごとの base との差分が内容になるので、
+    /// store にマスターコピーを置く strategy だけが対象。
+    pub fn supports_variants(&self) -> bool {
+        !matches!(self, Self::Command | Self::MergeJson)
+    }
+
+    /// ディレクトリのエントリで include / exclude を指定できる strategy か。
+    ///
+    /// symlink はディレクトリそのものを指すので一部だけを除けず、ファイル単位の内容を扱う
+    /// dotenv・command・merge-json はディレクトリを対象にしない。
+    pub fn supports_filter(&self) -> bool {
+        matches!(
+            self,
+            Self::Copy | Self::Clone | Self::Hardlink | Self::Seed | Self::LinkTree
+        )
+    }
+
     pub fn as_str(&self) -> &'static str {
         match self {
             Self::Symlink => "symlink",
             Self::Copy => "copy",
+            Self::Dotenv => "dotenv",
+            Self::Hardlink => "hardlink",
+            Self::Clone => "clone",
+            Self::Seed => "seed",
+            Self::Command => "command",
+            Self::MergeJson => "merge-json",
+            Self::LinkTree => "link-tree",
         }
     }
 }
@@ -35,6 +115,13 @@ impl std::str::FromStr for Strategy {
         match s {
             "symlink" => Ok(Self::Symlink),
             "copy" => Ok(Self::Copy),
+            "dotenv" => Ok(Self::Dotenv),
+            "hardlink" => Ok(Self::Hardlink),
+            "clone" => Ok(Self::Clone),
+            "seed" => Ok(Self::Seed),
+            "command" => Ok(Self::Command),
+            "merge-json" => Ok(Self::MergeJson),
+            "link-tree" => Ok(Self::LinkTree),
             _ => Err(anyhow::anyhow!("{}", t!("store.invalid_strategy"))),
         }
     }
@@ -43,9 +130,361 @@ impl std::str::FromStr for Strategy {
 pub struct ManifestEntry {
     pub strategy: Strategy,
     pub filepath: String,
+    /// パーミッションビット（例: 0o600）。旧形式の manifest では記録されていない。
+    pub mode: Option<u32>,
+    /// command strategy で、ファイルの内容を標準出力に書き出すシェルコマンド。
+    pub command: Option<String>,
+    /// store 側の内容のハッシュ（`sha256:<hex>`）。track / push で更新する。
+    /// 記録するのは `Strategy::records_hash` が true のものだけ。
+    pub hash: Option<String>,
+    /// このエントリを含む profile。profile を指定した worktree には、その profile のエントリだけを配置する。
+    pub profiles: Vec<String>,
+    /// branch 別の variant なら、対象の branch パターン（例: `release/*`）。
+    pub branch: Option<String>,
+    /// このエントリの branch 別の variant。manifest ではエントリの `[[files.variants]]` として記録する。
+    pub variants: Vec<ManifestEntry>,
+    /// monorepo のサブプロジェクトのディレクトリ（worktree の root からの相対パス）。
+    /// manifest では `path` をこのディレクトリからの相対パスで記録する。`filepath` は常に root からの相対パス。
+    pub project: Option<String>,
+    /// ディレクトリのエントリで扱う配下のパス。空ならディレクトリ全体を扱う。
+    pub filter: PathFilter,
+}
+
+impl ManifestEntry {
+    pub fn new(strategy: Strategy, filepath: impl Into<String>) -> Self {
+        ManifestEntry {
+            strategy,
+            filepath: filepath.into(),
+            mode: None,
+            command: None,
+            hash: None,
+            profiles: Vec::new(),
+            branch: None,
+            variants: Vec::new(),
+            project: None,
+            filter: PathFilter::default(),
+        }
+    }
+
+    /// `profile` を適用した worktree に配置するエントリか。`None`（profile なし）はすべてのエントリを含む。
+    pub fn in_profile(&self, profile: Option<&str>) -> bool {
+        profile.is_none_or(|p| self.profiles.iter().any(|name| name == p))
+    }
+
+    /// サブプロジェクトからの相対パス。サブプロジェクトに属さなければ `filepath` と同じ。
+    pub fn project_path(&self) -> &str {
+        self.project
+            .as_deref()
+            .and_then(|project| self.filepath.strip_prefix(project))
+            .and_then(|rest| rest.strip_prefix('/'))
+            .unwrap_or(&self.filepath)
+    }
+
+    /// sparse checkout で `dirs` だけを展開した worktree に配置するエントリか。
+    /// サブプロジェクトに属さないエントリはリポジトリ全体の設定とみなして常に配置する。
+    pub fn in_sparse(&self, dirs: &[String]) -> bool {
+        let Some(ref project) = self.project else {
+            return true;
+        };
+        dirs.iter()
+            .any(|dir| path_contains(dir, project) || path_contains(project, dir))
+    }
+
+    /// store 内のマスターコピーのパス。variant は `.variants/<branch パターン>/` の下に置く。
+    pub fn store_path(&self, store: &Path) -> PathBuf {
+        match self.branch {
+            Some(ref pattern) => variant_dir(store, pattern).join(&self.filepath),
+            None => store.join(&self.filepath),
+        }
+    }
+
+    /// 表示用のパス。variant は `.env [release/*]` のように branch パターンを付ける。
+    pub fn display_path(&self) -> String {
+        match self.branch {
+            Some(ref pattern) => format!("{} [{}]", self.filepath, pattern),
+            None => self.filepath.clone(),
+        }
+    }
+
+    /// `branch` の worktree に配置するエントリ。一致する variant のうち最も具体的なものを選び、
+    /// なければ自身（既定の内容）を返す。
+    pub fn for_branch(&self, branch: Option<&str>) -> &ManifestEntry {
+        match self.variant_index(branch) {
+            Some(i) => &self.variants[i],
+            None => self,
+        }
+    }
+
+    /// `for_branch` の可変版。push で variant のハッシュとパーミッションを更新するのに使う。
+    pub fn for_branch_mut(&mut self, branch: Option<&str>) -> &mut ManifestEntry {
+        match self.variant_index(branch) {
+            Some(i) => &mut self.variants[i],
+            None => self,
+        }
+    }
+
+    fn variant_index(&self, branch: Option<&str>) -> Option<usize> {
+        let branch = branch?;
+        self.variants
+            .iter()
+            .enumerate()
+            .filter_map(|(i, v)| v.branch.as_deref().map(|pattern| (i, pattern)))
+            .filter(|(_, pattern)| pattern::branch_match(pattern, branch))
+            .max_by_key(|(_, pattern)| pattern::specificity(pattern))
+            .map(|(i, _)| i)
+    }
+}
+
+/// `/` 区切りの相対パス `dir` が `path` 自身かその祖先か。
+fn path_contains(dir: &str, path: &str) -> bool {
+    path == dir
+        || path
+            .strip_prefix(dir)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// サブプロジェクト `project` で指定された `file` を、worktree の root からの相対パスにする。
+///
+/// `./` と `..` は字句的に解決する。root より上を指す `..` は残すので、`validate_entry_path` で拒否される。
+pub fn project_file(project: Option<&str>, file: &str) -> String {
+    if file.starts_with('/') {
+        return file.to_string();
+    }
+    let mut segments: Vec<&str> = Vec::new();
+    let joined = match project {
+        Some(project) => format!("{}/{}", project, file),
+        None => file.to_string(),
+    };
+    for segment in joined.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." if segments.last().is_some_and(|s| *s != "..") => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+/// カレントディレクトリが worktree のサブディレクトリなら、root からの相対パスを返す。
+pub fn current_project(wt_root: &Path) -> Option<String> {
+    let cwd = std::env::current_dir().ok()?.canonicalize().ok()?;
+    let root = wt_root.canonicalize().ok()?;
+    let relative = cwd.strip_prefix(&root).ok()?;
+    let segments: Vec<String> = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    (!segments.is_empty()).then(|| segments.join("/"))
+}
+
+/// variant のマスターコピーを置くディレクトリ。branch パターンの `/` はディレクトリにせず `%2F` に置き換える。
+pub fn variant_dir(store: &Path, pattern: &str) -> PathBuf {
+    let encoded = pattern.replace('%', "%25").replace('/', "%2F");
+    store.join(".variants").join(encoded)
+}
+
+/// variant の branch パターンを確認する。パターンは store のディレクトリ名にもなるので、
+/// 空の要素と `.` / `..` は使えない。
+pub fn validate_branch_pattern(pattern: &str) -> Result<()> {
+    if pattern.is_empty()
+        || pattern
+            .split('/')
+            .any(|seg| seg.is_empty() || seg == "." || seg == "..")
+    {
+        bail!("{}", t!("store.variant_invalid", branch = pattern));
+    }
+    Ok(())
+}
+
+/// manifest に記録するパスを確認する。
+///
+/// worktree の root からの相対パスだけを受け付ける。絶対パスと `..` は worktree の外を指し、
+/// `.` や空の要素（`a//b`）は同じファイルを別の名前で登録できてしまうので使えない。
+pub fn validate_entry_path(filepath: &str) -> Result<()> {
+    if filepath.starts_with('/') {
+        bail!("{}", t!("store.path_absolute", file = filepath));
+    }
+    if filepath.split('/').any(|seg| seg == "..") {
+        bail!("{}", t!("store.path_escapes", file = filepath));
+    }
+    if filepath.is_empty() || filepath.split('/').any(|seg| seg.is_empty() || seg == ".") {
+        bail!(
+            "{}",
+            t!("store.path_invalid", file = filepath.escape_debug())
+        );
+    }
+    Ok(())
+}
+
+/// worktree 内の `filepath` のパスを返す。
+///
+/// `validate_entry_path` に加えて、途中のディレクトリが symlink でないことを確認する。
+/// symlink をたどると worktree の外（リンク先）を読み書きしてしまうため。
+pub fn worktree_path(root: &Path, filepath: &str) -> Result<PathBuf> {
+    validate_entry_path(filepath)?;
+    let mut dir = root.to_path_buf();
+    let mut segments: Vec<&str> = filepath.split('/').collect();
+    segments.pop();
+    for (i, segment) in segments.iter().enumerate() {
+        dir.push(segment);
+        let is_symlink = dir
+            .symlink_metadata()
+            .map(|m| m.file_type().is_symlink())
+            .unwrap_or(false);
+        if is_symlink {
+            bail!(
+                "{}",
+                t!(
+                    "store.path_symlinked_parent",
+                    file = filepath,
+                    dir = segments[..=i].join("/")
+                )
+            );
+        }
+    }
+    Ok(root.join(filepath))
+}
+
+/// store の保存先の実装。
+///
+/// worktree への配置などの操作はローカルのディレクトリ（`dir`）に対して行う。
+/// ファイルシステム以外に保存するバックエンドは、内容を `dir` に用意してから返す。
+pub trait StoreBackend {
+    /// store の内容を置くローカルのディレクトリ。
+    fn dir(&self) -> &Path;
+    /// store の内容をすべて `dest` にコピーする（`ws store migrate` 用）。ロックファイルはコピーしない。
+    fn export(&self, dest: &Path, exec: &Executor) -> Result<()>;
+    /// store の内容を削除する。
+    fn destroy(&self, exec: &Executor) -> Result<()>;
+}
+
+/// ファイルシステム上のディレクトリをそのまま store にするバックエンド。
+pub struct FsStore {
+    dir: PathBuf,
+}
+
+impl FsStore {
+    pub fn new(dir: PathBuf) -> Self {
+        FsStore { dir }
+    }
 }
 
+impl StoreBackend for FsStore {
+    fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn export(&self, dest: &Path, exec: &Executor) -> Result<()> {
+        exec.create_dir_all(dest)?;
+        for child in fs::read_dir(&self.dir)?.flatten() {
+            if child.file_name() == ".lock" {
+                continue;
+            }
+            let src = child.path();
+            let dst = dest.join(child.file_name());
+            // 大きなディレクトリでも reflink や hardlink に頼らず、移行先を独立したコピーにする
+            exec.run(
+                format!("cp -Rp {} {}", src.display(), dst.display()),
+                || {
+                    if src.is_dir() {
+                        copy_dir_recursive(&src, &dst)
+                    } else {
+                        copy_entry(&src, &dst)
+                    }
+                },
+            )?;
+        }
+        Ok(())
+    }
+
+    fn destroy(&self, exec: &Executor) -> Result<()> {
+        exec.remove(&self.dir)
+    }
+}
+
+/// 現在のリポジトリの store を開く。
+///
+/// config の `[repos.<name>]` に `store_path` があればそこを、なければ `<git-common-dir>/worktree-store` を使う。
+pub fn open_store() -> Result<Box<dyn StoreBackend>> {
+    let dir = match configured_store_path() {
+        Some(path) => path,
+        None => default_store_dir()?,
+    };
+    Ok(Box::new(FsStore::new(dir)))
+}
+
+/// 現在のリポジトリの store のディレクトリ（`open_store` のローカルのディレクトリ）。
 pub fn store_dir() -> Result<PathBuf> {
+    Ok(open_store()?.dir().to_path_buf())
+}
+
+/// config で指定された現在のリポジトリの store の場所。相対パスはリポジトリのルートからのパス。
+///
+/// config が読めないときは警告して既定の場所を使う。config の誤りで store が使えなくならないように。
+fn configured_store_path() -> Option<PathBuf> {
+    let repo_root = git::resolve_repo_root(None)?;
+    let config = match crate::config::load_config() {
+        Ok(config) => config,
+        Err(e) => {
+            static WARNED: Once = Once::new();
+            WARNED.call_once(|| {
+                anstream::eprintln!(
+                    "{}",
+                    ui::styled(
+                        ui::STYLE_WARN,
+                        &t!("store.config_unreadable", error = format!("{:#}", e))
+                    )
+                );
+            });
+            return None;
+        }
+    };
+    config
+        .repo_entry(&repo_root)
+        .and_then(|(_, entry)| entry.store_path.as_ref())
+        .map(|path| repo_root.join(path))
+}
+
+/// 旧形式の `manifest` だけがある store を `manifest.toml` に移行する（`ws store migrate` 用）。
+///
+/// 読むだけのコマンドは旧形式をそのまま読み、manifest を書き込むコマンドが `manifest.toml` で置き換える。
+/// 呼び出し側で store をロックしておくこと。dry-run では何もしない。
+pub fn migrate_legacy_manifest(store: &Path, exec: &Executor) -> Result<()> {
+    if exec.is_dry_run()
+        || manifest_path(store).is_file()
+        || !store.join(LEGACY_MANIFEST_FILE).is_file()
+    {
+        return Ok(());
+    }
+    let entries = read_manifest(store)?;
+    exec.write(&manifest_path(store), format_manifest(&entries))?;
+    retire_legacy_manifest(store, exec)?;
+    anstream::eprintln!(
+        "{}",
+        ui::styled(
+            ui::STYLE_INFO,
+            &t!(
+                "store.manifest_migrated",
+                path = manifest_path(store).display().to_string()
+            )
+        )
+    );
+    Ok(())
+}
+
+/// 旧形式の `manifest` を `manifest.legacy` に退避する。`manifest.toml` を書き込んだ後に呼ぶ。
+pub fn retire_legacy_manifest(store: &Path, exec: &Executor) -> Result<()> {
+    let legacy = store.join(LEGACY_MANIFEST_FILE);
+    if legacy.is_file() {
+        exec.rename(&legacy, &store.join(LEGACY_MANIFEST_BACKUP))?;
+    }
+    Ok(())
+}
+
+/// config で場所を指定していないときの store（`<git-common-dir>/worktree-store`）。
+pub fn default_store_dir() -> Result<PathBuf> {
     // まず git rev-parse --git-common-dir を試す
     if let Ok(common_dir) = git::git_output(&["rev-parse", "--git-common-dir"]) {
         let canonical = fs::canonicalize(&common_dir).with_context(|| {
@@ -71,61 +510,434 @@ pub fn store_dir() -> Result<PathBuf> {
 
 pub fn require_store() -> Result<PathBuf> {
     let store = store_dir()?;
-    if !store.is_dir() || !store.join("manifest").is_file() {
+    if !store.is_dir() || !manifest_exists(&store) {
         bail!("{}", t!("store.store_not_initialized"));
     }
     Ok(store)
 }
 
-pub fn ensure_store() -> Result<PathBuf> {
+pub fn ensure_store(exec: &Executor) -> Result<PathBuf> {
     let store = store_dir()?;
-    fs::create_dir_all(&store)?;
-    let manifest = store.join("manifest");
-    if !manifest.is_file() {
-        fs::File::create(&manifest)?;
+    exec.create_dir_all(&store)?;
+    if !manifest_exists(&store) {
+        exec.write(&manifest_path(&store), format_manifest(&[]))?;
     }
     Ok(store)
 }
 
+/// store の排他ロックを取得する。manifest と store 内のファイルを書き換える間は保持すること。
+/// store がまだ存在しない場合（dry-run で未作成のとき）は `None` を返す。
+pub fn lock_store(store: &Path) -> Result<Option<FileLock>> {
+    if !store.is_dir() {
+        return Ok(None);
+    }
+    FileLock::exclusive(&store.join(".lock")).map(Some)
+}
+
+/// store の共有ロックを取得する。store から worktree へ配布する間に保持する。
+pub fn lock_store_shared(store: &Path) -> Result<Option<FileLock>> {
+    if !store.is_dir() {
+        return Ok(None);
+    }
+    FileLock::shared(&store.join(".lock")).map(Some)
+}
+
+/// manifest のファイル名。
+pub const MANIFEST_FILE: &str = "manifest.toml";
+/// 旧形式（`strategy:filepath` の行形式）の manifest のファイル名。
+pub const LEGACY_MANIFEST_FILE: &str = "manifest";
+/// 移行後の旧形式 manifest の退避先。
+pub const LEGACY_MANIFEST_BACKUP: &str = "manifest.legacy";
+/// このバージョンの ws が読み書きする manifest のスキーマバージョン。
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// store の manifest（`manifest.toml`）のパスを返す。
+pub fn manifest_path(store: &Path) -> PathBuf {
+    store.join(MANIFEST_FILE)
+}
+
+/// store に manifest があるか（旧形式を含む）を返す。
+pub fn manifest_exists(store: &Path) -> bool {
+    manifest_path(store).is_file() || store.join(LEGACY_MANIFEST_FILE).is_file()
+}
+
+/// `manifest.toml` の文書全体。
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ManifestDoc {
+    version: Spanned<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    files: Vec<FileRecord>,
+}
+
+/// `[[files]]` テーブル。管理対象のファイル 1 つに対応する。
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileRecord {
+    path: Spanned<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    project: Option<Spanned<String>>,
+    strategy: Spanned<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mode: Option<Spanned<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    command: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    profiles: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    include: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    exclude: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    variants: Vec<VariantRecord>,
+}
+
+/// `[[files.variants]]` テーブル。strategy・profile・include / exclude は元のエントリに従う。
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VariantRecord {
+    branch: Spanned<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mode: Option<Spanned<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hash: Option<String>,
+}
+
+/// store の manifest を読み込む。
+///
+/// `manifest.toml` がなく旧形式の `manifest` だけがある場合は旧形式を読む
+/// （manifest を書き込むコマンドや `ws store migrate` が `manifest.toml` に移行する）。
 pub fn read_manifest(store: &Path) -> Result<Vec<ManifestEntry>> {
-    let manifest_path = store.join("manifest");
-    let content = fs::read_to_string(&manifest_path).with_context(|| {
+    let toml_path = manifest_path(store);
+    let legacy_path = store.join(LEGACY_MANIFEST_FILE);
+    let (path, legacy) = if !toml_path.is_file() && legacy_path.is_file() {
+        (legacy_path, true)
+    } else {
+        (toml_path, false)
+    };
+    let content = fs::read_to_string(&path).with_context(|| {
         t!(
             "store.manifest_read_failed",
-            path = manifest_path.display().to_string()
+            path = path.display().to_string()
         )
         .to_string()
     })?;
+    if legacy {
+        parse_legacy_manifest(&path, &content)
+    } else {
+        parse_manifest(&path, &content)
+    }
+}
+
+/// `manifest.toml` の内容を解析する。`path` はエラーメッセージに使う。
+///
+/// 構文やスキーマの誤り、危険なパスは `path:line: detail` 形式のエラーにする。
+pub fn parse_manifest(path: &Path, content: &str) -> Result<Vec<ManifestEntry>> {
+    let line_error = |offset: usize, detail: String| {
+        anyhow::anyhow!(
+            "{}",
+            t!(
+                "store.manifest_line_invalid",
+                path = path.display().to_string(),
+                line = line_of(content, offset),
+                detail = detail
+            )
+        )
+    };
+    let doc: ManifestDoc = toml::from_str(content).map_err(|e| {
+        let offset = e.span().map_or(0, |span| span.start);
+        line_error(offset, e.message().trim().to_string())
+    })?;
+    if *doc.version.get_ref() != MANIFEST_VERSION {
+        return Err(line_error(
+            doc.version.span().start,
+            t!(
+                "store.manifest_version_unsupported",
+                version = doc.version.get_ref(),
+                supported = MANIFEST_VERSION
+            )
+            .to_string(),
+        ));
+    }
+
+    let mut entries: Vec<ManifestEntry> = Vec::new();
+    for record in doc.files {
+        let at =
+            |spanned_start: usize| move |e: anyhow::Error| line_error(spanned_start, e.to_string());
+        let path_at = record.path.span().start;
+        let strategy = record
+            .strategy
+            .get_ref()
+            .parse::<Strategy>()
+            .map_err(at(record.strategy.span().start))?;
+        // 手で編集された manifest で worktree や store の外に書き込まないよう、読み込み時に確認する
+        let project = record
+            .project
+            .map(|project| {
+                let project_at = project.span().start;
+                let project = project.into_inner();
+                validate_entry_path(&project)
+                    .map(|()| project)
+                    .map_err(at(project_at))
+            })
+            .transpose()?;
+        let filepath = match project {
+            Some(ref project) => format!("{}/{}", project, record.path.get_ref()),
+            None => record.path.into_inner(),
+        };
+        validate_entry_path(&filepath).map_err(at(path_at))?;
+        if entries.iter().any(|e| e.filepath == filepath) {
+            return Err(line_error(
+                path_at,
+                t!("store.manifest_duplicate", file = &filepath).to_string(),
+            ));
+        }
+        for profile in &record.profiles {
+            validate_profile(profile).map_err(at(path_at))?;
+        }
+
+        let mut entry = ManifestEntry::new(strategy.clone(), filepath);
+        entry.mode = record
+            .mode
+            .map(parse_spanned_mode)
+            .transpose()
+            .map_err(at(path_at))?;
+        entry.command = record.command;
+        entry.hash = record.hash;
+        entry.profiles = record.profiles;
+        entry.project = project;
+        entry.filter = PathFilter {
+            include: record.include,
+            exclude: record.exclude,
+        };
+        if !entry.filter.is_empty() && !strategy.supports_filter() {
+            return Err(line_error(
+                path_at,
+                t!(
+                    "store.filter_unsupported",
+                    file = &entry.filepath,
+                    strategy = strategy.as_str()
+                )
+                .to_string(),
+            ));
+        }
+        if !record.variants.is_empty() && !strategy.supports_variants() {
+            return Err(line_error(
+                path_at,
+                t!(
+                    "store.variant_unsupported",
+                    file = &entry.filepath,
+                    strategy = strategy.as_str()
+                )
+                .to_string(),
+            ));
+        }
+        for variant in record.variants {
+            let branch_at = variant.branch.span().start;
+            let branch = variant.branch.into_inner();
+            validate_branch_pattern(&branch).map_err(at(branch_at))?;
+            if entry
+                .variants
+                .iter()
+                .any(|v| v.branch.as_deref() == Some(&branch))
+            {
+                return Err(line_error(
+                    branch_at,
+                    t!(
+                        "store.manifest_duplicate",
+                        file = format!("{} [{}]", entry.filepath, branch)
+                    )
+                    .to_string(),
+                ));
+            }
+            let mut v = ManifestEntry::new(strategy.clone(), entry.filepath.clone());
+            v.branch = Some(branch);
+            v.filter = entry.filter.clone();
+            v.mode = variant
+                .mode
+                .map(parse_spanned_mode)
+                .transpose()
+                .map_err(at(branch_at))?;
+            v.hash = variant.hash;
+            entry.variants.push(v);
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// バイト位置から 1 始まりの行番号を求める。
+fn line_of(content: &str, offset: usize) -> usize {
+    content[..offset.min(content.len())].matches('\n').count() + 1
+}
 
-    let mut entries = Vec::new();
-    for line in content.lines() {
+fn parse_spanned_mode(value: Spanned<String>) -> Result<u32> {
+    parse_mode(value.get_ref())
+        .ok_or_else(|| anyhow::anyhow!("{}", t!("store.mode_invalid", mode = value.get_ref())))
+}
+
+/// 旧形式の manifest（`strategy:filepath` の後ろにタブ区切りの `key=value` 属性が続く行形式）を解析する。
+///
+/// 解釈できない行は読み飛ばさずにエラーにする。移行で黙ってエントリを失わないため。
+pub fn parse_legacy_manifest(path: &Path, content: &str) -> Result<Vec<ManifestEntry>> {
+    let mut entries: Vec<ManifestEntry> = Vec::new();
+    for (index, line) in content.lines().enumerate() {
         if line.is_empty() {
             continue;
         }
-        if let Some((strategy_str, filepath)) = line.split_once(':')
-            && let Ok(strategy) = strategy_str.parse::<Strategy>()
-        {
-            entries.push(ManifestEntry {
-                strategy,
-                filepath: filepath.to_string(),
-            });
+        let line_error = |detail: String| {
+            anyhow::anyhow!(
+                "{}",
+                t!(
+                    "store.manifest_line_invalid",
+                    path = path.display().to_string(),
+                    line = index + 1,
+                    detail = detail
+                )
+            )
+        };
+        let mut fields = line.split('\t');
+        let head = fields.next().unwrap_or_default();
+        let Some((strategy_str, filepath)) = head.split_once(':') else {
+            return Err(line_error(t!("store.manifest_line_malformed").to_string()));
+        };
+        let strategy = strategy_str
+            .parse::<Strategy>()
+            .map_err(|e| line_error(e.to_string()))?;
+        let mut entry = ManifestEntry::new(strategy, filepath);
+        for field in fields {
+            match field.split_once('=') {
+                Some(("mode", value)) => {
+                    entry.mode = Some(parse_mode(value).ok_or_else(|| {
+                        line_error(t!("store.mode_invalid", mode = value).to_string())
+                    })?)
+                }
+                Some(("command", value)) => entry.command = Some(value.to_string()),
+                Some(("hash", value)) => entry.hash = Some(value.to_string()),
+                Some(("profiles", value)) => {
+                    entry.profiles = value
+                        .split(',')
+                        .filter(|p| !p.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                }
+                Some(("branch", value)) => entry.branch = Some(value.to_string()),
+                _ => {
+                    return Err(line_error(
+                        t!("store.manifest_attribute_unknown", attribute = field).to_string(),
+                    ));
+                }
+            }
+        }
+        // 手で編集された manifest で worktree や store の外に書き込まないよう、読み込み時に確認する
+        validate_entry_path(&entry.filepath)
+            .and_then(|()| {
+                entry
+                    .branch
+                    .as_deref()
+                    .map_or(Ok(()), validate_branch_pattern)
+            })
+            .map_err(|e| line_error(e.to_string()))?;
+        // variant は同じパスのエントリにまとめる
+        if entry.branch.is_some() {
+            let Some(base) = entries
+                .iter_mut()
+                .rev()
+                .find(|e: &&mut ManifestEntry| e.filepath == entry.filepath)
+            else {
+                return Err(line_error(
+                    t!(
+                        "store.manifest_variant_without_base",
+                        file = &entry.filepath
+                    )
+                    .to_string(),
+                ));
+            };
+            base.variants.push(entry);
+            continue;
         }
+        entries.push(entry);
     }
     Ok(entries)
 }
 
+/// エントリを `manifest.toml` の内容に変換する。
+pub fn format_manifest(entries: &[ManifestEntry]) -> String {
+    let doc = ManifestDoc {
+        version: unspanned(MANIFEST_VERSION),
+        files: entries
+            .iter()
+            .map(|entry| FileRecord {
+                path: unspanned(entry.project_path().to_string()),
+                // サブプロジェクトの外に移動したエントリは root からの相対パスで記録する
+                project: entry
+                    .project
+                    .clone()
+                    .filter(|_| entry.project_path() != entry.filepath)
+                    .map(unspanned),
+                strategy: unspanned(entry.strategy.as_str().to_string()),
+                mode: entry.mode.map(|m| unspanned(format_mode(m))),
+                command: entry.command.clone(),
+                hash: entry.hash.clone(),
+                profiles: entry.profiles.clone(),
+                include: entry.filter.include.clone(),
+                exclude: entry.filter.exclude.clone(),
+                variants: entry
+                    .variants
+                    .iter()
+                    .map(|v| VariantRecord {
+                        branch: unspanned(v.branch.clone().unwrap_or_default()),
+                        mode: v.mode.map(|m| unspanned(format_mode(m))),
+                        hash: v.hash.clone(),
+                    })
+                    .collect(),
+            })
+            .collect(),
+    };
+    toml::to_string(&doc).expect("manifest serializes to TOML")
+}
+
+/// 書き出し用の値。位置情報は読み込み時にしか使わない。
+fn unspanned<T>(value: T) -> Spanned<T> {
+    Spanned::new(0..0, value)
+}
+
+/// manifest を書き込む。一時ファイル経由で置き換えるので、途中で中断しても壊れない。
+///
+/// 旧形式の `manifest` は残るので、`retire_legacy_manifest` で退避する。
 pub fn write_manifest(store: &Path, entries: &[ManifestEntry]) -> Result<()> {
-    let manifest_path = store.join("manifest");
-    let mut file = fs::File::create(&manifest_path).with_context(|| {
+    let path = manifest_path(store);
+    lock::write_atomic(&path, format_manifest(entries)).with_context(|| {
         t!(
             "store.manifest_write_failed",
-            path = manifest_path.display().to_string()
+            path = path.display().to_string()
         )
         .to_string()
-    })?;
+    })
+}
+
+/// パーミッションビットを manifest 表記（8進数4桁）に変換する。
+pub fn format_mode(mode: u32) -> String {
+    format!("{:04o}", mode & 0o7777)
+}
+
+fn parse_mode(value: &str) -> Option<u32> {
+    u32::from_str_radix(value, 8).ok().map(|m| m & 0o7777)
+}
+
+/// パスのパーミッションビットを返す（symlink は辿る）。
+pub fn file_mode(path: &Path) -> Option<u32> {
+    fs::metadata(path)
+        .ok()
+        .map(|m| m.permissions().mode() & 0o7777)
+}
 
-    for entry in entries {
-        writeln!(file, "{}:{}", entry.strategy.as_str(), entry.filepath)?;
+/// manifest に記録されたパーミッションを適用する。記録がなければ何もしない。
+pub fn apply_mode(path: &Path, mode: Option<u32>) -> Result<()> {
+    if let Some(mode) = mode {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
     }
     Ok(())
 }
@@ -142,393 +954,2059 @@ pub fn store_entry_exists(path: &Path) -> bool {
     path.is_file() || path.is_dir()
 }
 
-/// ディレクトリを再帰的にコピーする。
-pub fn copy_dir_recursive(src: &Path, dst: &Path) -> Result<()> {
-    fs::create_dir_all(dst)?;
-    for entry in fs::read_dir(src)? {
-        let entry = entry?;
-        let src_path = entry.path();
-        let dst_path = dst.join(entry.file_name());
-        if src_path.is_dir() {
-            copy_dir_recursive(&src_path, &dst_path)?;
-        } else {
-            fs::copy(&src_path, &dst_path)?;
-        }
+/// パス配下の合計サイズ（バイト）。symlink は辿らない。
+pub fn path_size(path: &Path) -> u64 {
+    let Ok(meta) = path.symlink_metadata() else {
+        return 0;
+    };
+    if !meta.is_dir() {
+        return meta.len();
     }
-    Ok(())
+    fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| path_size(&e.path()))
+                .sum()
+        })
+        .unwrap_or(0)
 }
 
-/// 2つのディレクトリの内容を再帰的に比較する（copy strategy の MODIFIED 判定用）。
-fn dirs_equal_recursive(a: &Path, b: &Path) -> bool {
-    let Ok(a_entries) = fs::read_dir(a) else {
-        return false;
-    };
-    let Ok(b_entries) = fs::read_dir(b) else {
-        return false;
-    };
-
-    let mut a_names: Vec<_> = a_entries
-        .filter_map(|e| e.ok())
-        .map(|e| e.file_name())
-        .collect();
-    let mut b_names: Vec<_> = b_entries
-        .filter_map(|e| e.ok())
-        .map(|e| e.file_name())
-        .collect();
-
-    a_names.sort();
-    b_names.sort();
+/// バイナリ判定で読む先頭のバイト数（git と同じ）
+const BINARY_SNIFF_LEN: usize = 8000;
+
+/// バイナリファイル（先頭に NUL バイトを含む）を探し、最初に見つかったパスを返す。
+pub fn find_binary(path: &Path) -> Option<PathBuf> {
+    let meta = path.symlink_metadata().ok()?;
+    if meta.is_dir() {
+        return fs::read_dir(path)
+            .ok()?
+            .filter_map(|e| e.ok())
+            .find_map(|e| find_binary(&e.path()));
+    }
+    if !meta.is_file() {
+        return None;
+    }
+    let mut buf = vec![0u8; BINARY_SNIFF_LEN];
+    let n = std::io::Read::read(&mut fs::File::open(path).ok()?, &mut buf).ok()?;
+    buf[..n].contains(&0).then(|| path.to_path_buf())
+}
 
-    if a_names != b_names {
-        return false;
+/// バイト数を `12.3 MB` のような表記にする（1KB = 1024B）。
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
     }
+}
 
-    for name in &a_names {
-        let a_path = a.join(name);
-        let b_path = b.join(name);
+/// reflink で複製に切り替えるディレクトリサイズ（config の `store.link_threshold`）。
+/// 呼ぶたびに config を読むので、途中で変わった設定もそのまま反映する。
+pub fn link_threshold() -> u64 {
+    crate::config::load_config()
+        .ok()
+        .and_then(|c| c.store.link_threshold().ok())
+        .unwrap_or(crate::config::DEFAULT_LINK_THRESHOLD)
+}
 
-        if a_path.is_dir() && b_path.is_dir() {
-            if !dirs_equal_recursive(&a_path, &b_path) {
-                return false;
+/// ファイル単位の複製方法。
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CopyMethod {
+    /// 通常のコピー
+    Copy,
+    /// reflink（非対応なら通常のコピー）
+    Clone,
+    /// hardlink（失敗したらエラー）
+    Hardlink,
+}
+
+impl CopyMethod {
+    /// ファイルを 1 つ複製する。データを丸ごとコピーした（reflink や hardlink で共有できなかった）ら `true`。
+    fn copy_file(self, src: &Path, dst: &Path) -> Result<bool> {
+        // 既存のファイルには上書きせず作り直す。読み取り専用（0400 など）で保存したコピーにも書き込めるように。
+        // symlink や hardlink の先を書き換えてしまうこともない
+        if dst.symlink_metadata().is_ok_and(|m| !m.is_dir()) {
+            fs::remove_file(dst)?;
+        }
+        let copied = match self {
+            Self::Copy => {
+                fs::copy(src, dst)?;
+                true
             }
-        } else if a_path.is_file() && b_path.is_file() {
-            let a_content = fs::read(&a_path).ok();
-            let b_content = fs::read(&b_path).ok();
-            if a_content != b_content {
-                return false;
+            Self::Clone => !reflink::reflink_or_copy(src, dst)?,
+            Self::Hardlink => {
+                fs::hard_link(src, dst).with_context(|| {
+                    t!(
+                        "store.hardlink_failed",
+                        src = src.display().to_string(),
+                        dst = dst.display().to_string()
+                    )
+                    .to_string()
+                })?;
+                // inode を共有しているのでパーミッションも所有者も同じ
+                return Ok(false);
             }
-        } else {
-            // 一方がファイルで他方がディレクトリ → 不一致
-            return false;
+        };
+        if self != Self::Copy {
+            fs::set_permissions(dst, fs::metadata(src)?.permissions())?;
         }
+        copy_ownership(src, dst);
+        Ok(copied)
     }
+}
 
-    true
+/// ファイルまたはディレクトリをコピーする。パーミッションと（可能なら）所有者を保持する。
+///
+/// `store.link_threshold` を超える大きなディレクトリは、ファイル単位で reflink を試す。
+/// hardlink は使わない（inode を共有すると、片方の編集が store や他の worktree に漏れる）。
+pub fn copy_entry(src: &Path, dst: &Path) -> Result<()> {
+    copy_entry_filtered(src, dst, &PathFilter::default(), link_threshold())
 }
 
-pub fn apply_file(
-    strategy: &Strategy,
-    filepath: &str,
-    store: &Path,
-    target_root: &Path,
+/// `copy_entry` と同じだが、ディレクトリは `filter` が扱うパスだけをコピーする。
+/// `link_threshold` を超えるディレクトリは reflink を試し、できなければ警告して丸ごとコピーする。
+pub fn copy_entry_filtered(
+    src: &Path,
+    dst: &Path,
+    filter: &PathFilter,
+    link_threshold: u64,
 ) -> Result<()> {
-    let target = target_root.join(filepath);
-    let source = store.join(filepath);
-
-    if path_or_symlink_exists(&target) {
+    if !src.is_dir() {
+        return CopyMethod::Copy.copy_file(src, dst).map(drop);
+    }
+    let size = path_size(src);
+    if size <= link_threshold {
+        return copy_dir_with(src, dst, CopyMethod::Copy, filter, "").map(drop);
+    }
+    if copy_dir_with(src, dst, CopyMethod::Clone, filter, "")? {
         anstream::eprintln!(
             "{}",
-            ui::styled(ui::STYLE_WARN, &t!("store.skip_exists", file = filepath))
+            ui::styled(
+                ui::STYLE_WARN,
+                &t!(
+                    "store.reflink_unavailable",
+                    path = dst.display().to_string(),
+                    size = format_size(size)
+                )
+            )
         );
-        return Ok(());
     }
+    Ok(())
+}
 
-    if let Some(parent) = target.parent() {
-        fs::create_dir_all(parent)?;
+/// clone strategy 用。reflink で複製し、非対応のファイルシステムでは通常のコピーにする。
+pub fn clone_entry(src: &Path, dst: &Path, filter: &PathFilter) -> Result<()> {
+    if src.is_dir() {
+        copy_dir_with(src, dst, CopyMethod::Clone, filter, "").map(drop)
+    } else {
+        CopyMethod::Clone.copy_file(src, dst).map(drop)
     }
+}
 
-    match strategy {
-        Strategy::Symlink => {
-            unix_fs::symlink(&source, &target)?;
-            anstream::println!(
-                "  {}",
-                ui::styled(ui::STYLE_OK, &format!("symlink: {}", filepath))
-            );
+/// hardlink strategy 用。ディレクトリは構造を作り直し、中のファイルを hardlink する。
+pub fn hardlink_entry(src: &Path, dst: &Path, filter: &PathFilter) -> Result<()> {
+    if src.is_dir() {
+        copy_dir_with(src, dst, CopyMethod::Hardlink, filter, "").map(drop)
+    } else {
+        CopyMethod::Hardlink.copy_file(src, dst).map(drop)
+    }
+}
+
+/// strategy に応じて `src` を `dst` に配置する。ディレクトリは `filter` が扱うパスだけを配置する。
+pub fn place_entry(strategy: &Strategy, src: &Path, dst: &Path, filter: &PathFilter) -> Result<()> {
+    match strategy {
+        Strategy::Symlink => Ok(unix_fs::symlink(src, dst)?),
+        // command は store にキャッシュした前回の出力を配置する
+        Strategy::Copy
+        | Strategy::Dotenv
+        | Strategy::Seed
+        | Strategy::Command
+        | Strategy::MergeJson => copy_entry_filtered(src, dst, filter, link_threshold()),
+        Strategy::Hardlink => hardlink_entry(src, dst, filter),
+        Strategy::Clone => clone_entry(src, dst, filter),
+        Strategy::LinkTree => link_tree(src, dst, filter),
+    }
+}
+
+/// link-tree strategy 用。`dst` を通常のディレクトリとして作り、`src` 内の各ファイルへの symlink を置く。
+///
+/// `dst` にある store のファイルと同じパスのものは symlink に置き換え、store にないファイルは残す。
+pub fn link_tree(src: &Path, dst: &Path, filter: &PathFilter) -> Result<()> {
+    if dst.is_symlink() {
+        fs::remove_file(dst)?;
+    }
+    link_tree_in(src, dst, filter, "")
+}
+
+fn link_tree_in(src: &Path, dst: &Path, filter: &PathFilter, rel: &str) -> Result<()> {
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let src_path = entry.path();
+        let dst_path = dst.join(entry.file_name());
+        let child = join_rel(rel, &entry.file_name().to_string_lossy());
+        let is_dir = src_path.is_dir();
+        if !filter.allows(&child, is_dir) {
+            continue;
         }
-        Strategy::Copy => {
-            if source.is_dir() {
-                copy_dir_recursive(&source, &target)?;
-            } else {
-                fs::copy(&source, &target)?;
+        if is_dir {
+            if path_or_symlink_exists(&dst_path) && !is_real_dir(&dst_path) {
+                fs::remove_file(&dst_path)?;
             }
-            anstream::println!(
-                "  {}",
-                ui::styled(ui::STYLE_OK, &format!("copy: {}", filepath))
-            );
+            link_tree_in(&src_path, &dst_path, filter, &child)?;
+            continue;
+        }
+        if fs::read_link(&dst_path).is_ok_and(|target| target == src_path) {
+            continue;
+        }
+        if is_real_dir(&dst_path) {
+            fs::remove_dir_all(&dst_path)?;
+        } else if path_or_symlink_exists(&dst_path) {
+            fs::remove_file(&dst_path)?;
         }
+        unix_fs::symlink(&src_path, &dst_path)?;
     }
-
     Ok(())
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum FileStatus {
-    Ok,
-    Missing,
-    MissingStore,
-    Modified,
-    NotLink,
-    WrongLink,
-    Error,
-    StoreOnly,
+/// link-tree strategy で track する。`wt_dir` 内の通常ファイルを `store_dir` にコピーし、
+/// worktree 側をそのコピーへの symlink に置き換える。既存の symlink はそのまま残す。
+pub fn adopt_tree(wt_dir: &Path, store_dir: &Path, filter: &PathFilter) -> Result<()> {
+    adopt_tree_in(wt_dir, store_dir, filter, "")
 }
 
-impl std::fmt::Display for FileStatus {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Ok => write!(f, "OK"),
-            Self::Missing => write!(f, "MISSING"),
-            Self::MissingStore => write!(f, "MISSING(store)"),
-            Self::Modified => write!(f, "MODIFIED"),
-            Self::NotLink => write!(f, "NOT_LINK"),
-            Self::WrongLink => write!(f, "WRONG_LINK"),
-            Self::Error => write!(f, "ERROR"),
-            Self::StoreOnly => write!(f, "(store only)"),
+fn adopt_tree_in(wt_dir: &Path, store_dir: &Path, filter: &PathFilter, rel: &str) -> Result<()> {
+    fs::create_dir_all(store_dir)?;
+    for entry in fs::read_dir(wt_dir)? {
+        let entry = entry?;
+        let wt_path = entry.path();
+        let store_path = store_dir.join(entry.file_name());
+        let child = join_rel(rel, &entry.file_name().to_string_lossy());
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() || !filter.allows(&child, file_type.is_dir()) {
+            continue;
+        }
+        if file_type.is_dir() {
+            adopt_tree_in(&wt_path, &store_path, filter, &child)?;
+        } else {
+            CopyMethod::Copy.copy_file(&wt_path, &store_path)?;
+            fs::remove_file(&wt_path)?;
+            unix_fs::symlink(&store_path, &wt_path)?;
         }
     }
+    fs::set_permissions(store_dir, fs::metadata(wt_dir)?.permissions())?;
+    Ok(())
 }
 
-pub fn file_status(
-    entry: &ManifestEntry,
-    store_file: &Path,
-    wt_root: &Option<PathBuf>,
-) -> FileStatus {
-    if !store_entry_exists(store_file) {
-        return FileStatus::MissingStore;
+/// link-tree から別の strategy に変えるとき用。`dir` 内の symlink を、リンク先の内容のコピーに置き換える。
+pub fn unlink_tree(dir: &Path) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if is_real_dir(&path) {
+            unlink_tree(&path)?;
+        } else if path.is_symlink() && path.is_file() {
+            let target = fs::read_link(&path)?;
+            fs::remove_file(&path)?;
+            CopyMethod::Copy.copy_file(&target, &path)?;
+        }
     }
+    Ok(())
+}
 
-    let Some(root) = wt_root else {
-        return FileStatus::StoreOnly;
+/// `dir` 内に `store_dir` 以下を指す symlink があるか（link-tree で配置したディレクトリか）。
+pub fn tree_links_into(dir: &Path, store_dir: &Path) -> bool {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return false;
     };
+    entries.flatten().any(|entry| {
+        let path = entry.path();
+        if is_real_dir(&path) {
+            tree_links_into(&path, store_dir)
+        } else {
+            fs::read_link(&path).is_ok_and(|target| target.starts_with(store_dir))
+        }
+    })
+}
 
-    let wt_file = root.join(&entry.filepath);
+/// link-tree のディレクトリ `src` を、store から切り離して `dst` に作り直す（untrack 用）。
+///
+/// `store_dir` 以下を指す symlink はリンク先の内容のコピーにし、`purge` なら作らない。
+/// worktree 固有のファイルやそれ以外の symlink はそのまま残す。`purge` で空になったディレクトリは作らない。
+pub fn detach_tree(src: &Path, dst: &Path, store_dir: &Path, purge: bool) -> Result<()> {
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let src_path = entry.path();
+        let dst_path = dst.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            detach_tree(&src_path, &dst_path, store_dir, purge)?;
+        } else if file_type.is_symlink() {
+            let target = fs::read_link(&src_path)?;
+            if !target.starts_with(store_dir) {
+                unix_fs::symlink(&target, &dst_path)?;
+            } else if !purge {
+                copy_entry(&target, &dst_path)?;
+            }
+        } else {
+            CopyMethod::Copy.copy_file(&src_path, &dst_path)?;
+        }
+    }
+    if purge && fs::read_dir(dst)?.next().is_none() {
+        fs::remove_dir(dst)?;
+        return Ok(());
+    }
+    fs::set_permissions(dst, fs::metadata(src)?.permissions())?;
+    Ok(())
+}
 
-    if !path_or_symlink_exists(&wt_file) {
-        return FileStatus::Missing;
+/// `dir` 内で `old_store` 以下を指す symlink を、`new_store` の同じパスを指すように張り直す（`ws store mv` 用）。
+pub fn relink_tree(dir: &Path, old_store: &Path, new_store: &Path) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if is_real_dir(&path) {
+            relink_tree(&path, old_store, new_store)?;
+            continue;
+        }
+        let Ok(target) = fs::read_link(&path) else {
+            continue;
+        };
+        if let Ok(rest) = target.strip_prefix(old_store) {
+            fs::remove_file(&path)?;
+            unix_fs::symlink(new_store.join(rest), &path)?;
+        }
     }
+    Ok(())
+}
 
-    match entry.strategy {
-        Strategy::Symlink => {
-            let is_link = wt_file
-                .symlink_metadata()
-                .map(|m| m.file_type().is_symlink())
-                .unwrap_or(false);
+/// symlink ではない実際のディレクトリか。
+pub fn is_real_dir(path: &Path) -> bool {
+    path.symlink_metadata().is_ok_and(|m| m.is_dir())
+}
 
-            if !is_link {
-                return FileStatus::NotLink;
-            }
+/// link-tree のディレクトリ内で、store と食い違うファイルの一覧（パスはディレクトリからの相対パス）。
+///
+/// store のファイルが worktree にない（`Missing`）、通常ファイルのまま（`NotLink`）、
+/// 別の場所を指している（`WrongLink`）もの、および store にない worktree 固有のファイル（`Extra`）を返す。
+pub fn link_tree_status(
+    store_dir: &Path,
+    wt_dir: &Path,
+    filter: &PathFilter,
+) -> Vec<(String, FileStatus)> {
+    let mut issues = Vec::new();
+    link_tree_status_in(store_dir, wt_dir, filter, "", &mut issues);
+    issues.sort_by(|a, b| a.0.cmp(&b.0));
+    issues
+}
 
-            let link_target = match fs::read_link(&wt_file) {
-                Ok(t) => t,
-                Err(_) => return FileStatus::Error,
-            };
-            if link_target != *store_file {
-                FileStatus::WrongLink
-            } else {
-                FileStatus::Ok
+fn link_tree_status_in(
+    store_dir: &Path,
+    wt_dir: &Path,
+    filter: &PathFilter,
+    rel: &str,
+    issues: &mut Vec<(String, FileStatus)>,
+) {
+    if let Ok(entries) = fs::read_dir(store_dir) {
+        for entry in entries.filter_map(|e| e.ok()) {
+            let store_path = entry.path();
+            let wt_path = wt_dir.join(entry.file_name());
+            let child = join_rel(rel, &entry.file_name().to_string_lossy());
+            let is_dir = store_path.is_dir();
+            if !filter.allows(&child, is_dir) {
+                continue;
             }
-        }
-        Strategy::Copy => {
-            if store_file.is_dir() {
-                if wt_file.is_dir() {
-                    if dirs_equal_recursive(store_file, &wt_file) {
-                        FileStatus::Ok
-                    } else {
-                        FileStatus::Modified
-                    }
+            if is_dir {
+                if is_real_dir(&wt_path) {
+                    link_tree_status_in(&store_path, &wt_path, filter, &child, issues);
+                } else if path_or_symlink_exists(&wt_path) {
+                    issues.push((child, FileStatus::NotLink));
                 } else {
-                    FileStatus::Modified
+                    issues.push((child, FileStatus::Missing));
                 }
+                continue;
+            }
+            let status = match fs::read_link(&wt_path) {
+                Ok(target) if target == store_path => continue,
+                Ok(_) => FileStatus::WrongLink,
+                Err(_) if path_or_symlink_exists(&wt_path) => FileStatus::NotLink,
+                Err(_) => FileStatus::Missing,
+            };
+            issues.push((child, status));
+        }
+    }
+    let Ok(entries) = fs::read_dir(wt_dir) else {
+        return;
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let child = join_rel(rel, &entry.file_name().to_string_lossy());
+        let is_dir = is_real_dir(&entry.path());
+        if filter.allows(&child, is_dir)
+            && !path_or_symlink_exists(&store_dir.join(entry.file_name()))
+        {
+            issues.push((child, FileStatus::Extra));
+        }
+    }
+}
+
+/// ディレクトリを再帰的にコピーする。各ファイル・ディレクトリのパーミッションを保持する。
+pub fn copy_dir_recursive(src: &Path, dst: &Path) -> Result<()> {
+    copy_dir_with(src, dst, CopyMethod::Copy, &PathFilter::default(), "").map(drop)
+}
+
+/// `rel` はコピー元のエントリのルートから `src` までの相対パス（ルートなら空）。
+/// 丸ごとコピーしたファイルがあれば `true` を返す。
+fn copy_dir_with(
+    src: &Path,
+    dst: &Path,
+    method: CopyMethod,
+    filter: &PathFilter,
+    rel: &str,
+) -> Result<bool> {
+    fs::create_dir_all(dst)?;
+    let mut copied = false;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let src_path = entry.path();
+        let dst_path = dst.join(entry.file_name());
+        let child = join_rel(rel, &entry.file_name().to_string_lossy());
+        let is_dir = src_path.is_dir();
+        if !filter.allows(&child, is_dir) {
+            continue;
+        }
+        copied |= if is_dir {
+            copy_dir_with(&src_path, &dst_path, method, filter, &child)?
+        } else {
+            method.copy_file(&src_path, &dst_path)?
+        };
+    }
+    // 中身をコピーした後に設定する（書き込み不可のディレクトリでもコピーできるように）
+    fs::set_permissions(dst, fs::metadata(src)?.permissions())?;
+    copy_ownership(src, dst);
+    Ok(copied)
+}
+
+/// エントリのルートからの相対パスに名前を足す。
+fn join_rel(rel: &str, name: &str) -> String {
+    if rel.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", rel, name)
+    }
+}
+
+/// ディレクトリのうち `filter` が扱うファイルだけを削除する。除外したファイルと、
+/// それを含むディレクトリは残す。
+pub fn remove_filtered(dir: &Path, filter: &PathFilter) -> Result<()> {
+    if remove_filtered_in(dir, filter, "")? {
+        fs::remove_dir(dir)?;
+    }
+    Ok(())
+}
+
+/// 削除して空になったら true を返す。
+fn remove_filtered_in(dir: &Path, filter: &PathFilter, rel: &str) -> Result<bool> {
+    let mut empty = true;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let child = join_rel(rel, &entry.file_name().to_string_lossy());
+        let is_dir = path.is_dir() && !path.is_symlink();
+        if !filter.allows(&child, is_dir) {
+            empty = false;
+        } else if is_dir {
+            if remove_filtered_in(&path, filter, &child)? {
+                fs::remove_dir(&path)?;
             } else {
-                let store_content = fs::read(store_file).ok();
-                let wt_content = fs::read(&wt_file).ok();
-                if store_content != wt_content {
-                    FileStatus::Modified
-                } else {
-                    FileStatus::Ok
-                }
+                empty = false;
+            }
+        } else {
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(empty)
+}
+
+/// 所有者・グループをコピー元に合わせる。権限がない場合は黙って諦める。
+fn copy_ownership(src: &Path, dst: &Path) {
+    if let Ok(meta) = fs::metadata(src) {
+        let _ = unix_fs::chown(dst, Some(meta.uid()), Some(meta.gid()));
+    }
+}
+
+/// 2つのディレクトリの内容を再帰的に比較する（copy strategy の MODIFIED 判定用）。
+/// `filter` が扱わないパスは比較しない。`rel` はエントリのルートから `a` までの相対パス。
+fn dirs_equal_recursive(a: &Path, b: &Path, filter: &PathFilter, rel: &str) -> bool {
+    let Ok(a_entries) = fs::read_dir(a) else {
+        return false;
+    };
+    let Ok(b_entries) = fs::read_dir(b) else {
+        return false;
+    };
+
+    let allowed = |e: &fs::DirEntry| {
+        filter.allows(
+            &join_rel(rel, &e.file_name().to_string_lossy()),
+            e.path().is_dir(),
+        )
+    };
+    let mut a_names: Vec<_> = a_entries
+        .filter_map(|e| e.ok())
+        .filter(allowed)
+        .map(|e| e.file_name())
+        .collect();
+    let mut b_names: Vec<_> = b_entries
+        .filter_map(|e| e.ok())
+        .filter(allowed)
+        .map(|e| e.file_name())
+        .collect();
+
+    a_names.sort();
+    b_names.sort();
+
+    if a_names != b_names {
+        return false;
+    }
+
+    for name in &a_names {
+        let a_path = a.join(name);
+        let b_path = b.join(name);
+
+        if a_path.is_dir() && b_path.is_dir() {
+            let child = join_rel(rel, &name.to_string_lossy());
+            if !dirs_equal_recursive(&a_path, &b_path, filter, &child) {
+                return false;
+            }
+        } else if a_path.is_file() && b_path.is_file() {
+            let a_content = fs::read(&a_path).ok();
+            let b_content = fs::read(&b_path).ok();
+            if a_content != b_content {
+                return false;
+            }
+        } else {
+            // 一方がファイルで他方がディレクトリ → 不一致
+            return false;
+        }
+    }
+
+    true
+}
+
+/// 2つのパスが同じ inode（hardlink）か判定する。
+fn same_inode(a: &Path, b: &Path) -> bool {
+    match (a.symlink_metadata(), b.symlink_metadata()) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+/// store 側ディレクトリの全ファイルが worktree 側の対応するファイルと hardlink されているか判定する。
+fn dirs_linked_recursive(store: &Path, wt: &Path) -> bool {
+    let Ok(entries) = fs::read_dir(store) else {
+        return false;
+    };
+    entries.filter_map(|e| e.ok()).all(|entry| {
+        let store_path = entry.path();
+        let wt_path = wt.join(entry.file_name());
+        if store_path.is_dir() {
+            wt_path.is_dir() && dirs_linked_recursive(&store_path, &wt_path)
+        } else {
+            same_inode(&store_path, &wt_path)
+        }
+    })
+}
+
+/// worktree 側が store と hardlink を共有しているか判定する（ディレクトリは再帰的に）。
+pub fn is_hardlinked(store_file: &Path, wt_file: &Path) -> bool {
+    if store_file.is_dir() {
+        wt_file.is_dir() && dirs_linked_recursive(store_file, wt_file)
+    } else {
+        same_inode(store_file, wt_file)
+    }
+}
+
+/// 2つのディレクトリ配下のパーミッションビットを再帰的に比較する。
+fn dir_modes_equal_recursive(a: &Path, b: &Path) -> bool {
+    let Ok(entries) = fs::read_dir(a) else {
+        return false;
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let a_path = entry.path();
+        let b_path = b.join(entry.file_name());
+        if file_mode(&a_path) != file_mode(&b_path) {
+            return false;
+        }
+        if a_path.is_dir() && !dir_modes_equal_recursive(&a_path, &b_path) {
+            return false;
+        }
+    }
+    true
+}
+
+/// エントリを worktree に配置する。`branch` に一致する variant があれば、そちらの内容を使う。
+pub fn apply_file(
+    entry: &ManifestEntry,
+    store: &Path,
+    target_root: &Path,
+    branch: Option<&str>,
+    exec: &Executor,
+) -> Result<()> {
+    let entry = entry.for_branch(branch);
+    let filepath = entry.filepath.as_str();
+    let target = worktree_path(target_root, filepath)?;
+    let source = entry.store_path(store);
+
+    if path_or_symlink_exists(&target) {
+        anstream::eprintln!(
+            "{}",
+            ui::styled(ui::STYLE_WARN, &t!("store.skip_exists", file = filepath))
+        );
+        return Ok(());
+    }
+
+    if let Some(parent) = target.parent() {
+        exec.create_dir_all(parent)?;
+    }
+
+    if entry.strategy == Strategy::Command {
+        if let Err(e) = generate_file(entry, store, target_root, exec) {
+            // コマンドが失敗しても worktree の作成は続け、キャッシュがあればそれを配置する
+            anstream::eprintln!("{}", ui::styled(ui::STYLE_WARN, &format!("{:#}", e)));
+            if !store_entry_exists(&source) {
+                return Ok(());
             }
+            anstream::eprintln!(
+                "{}",
+                ui::styled(
+                    ui::STYLE_WARN,
+                    &t!("store.command_cache_used", file = filepath)
+                )
+            );
+            exec.place(&entry.strategy, &source, &target, &entry.filter)?;
+            exec.apply_mode(&target, entry.mode)?;
         }
+        anstream::println!(
+            "  {}",
+            ui::styled(ui::STYLE_OK, &format!("{}: {}", entry.strategy, filepath))
+        );
+        return Ok(());
+    }
+
+    exec.place(&entry.strategy, &source, &target, &entry.filter)?;
+    // symlink・hardlink・link-tree は store と同じ実体を指すので、パーミッションを適用しない
+    if !entry.strategy.shares_store() {
+        exec.apply_mode(&target, entry.mode)?;
+    }
+    if entry.strategy == Strategy::MergeJson {
+        save_merge_base(store, target_root, filepath, exec)?;
+    }
+    anstream::println!(
+        "  {}",
+        ui::styled(ui::STYLE_OK, &format!("{}: {}", entry.strategy, filepath))
+    );
+
+    Ok(())
+}
+
+/// command strategy のコマンドを `target_root` で実行し、出力を worktree と store のキャッシュに書き込む。
+///
+/// 既存のファイルは上書きする。キャッシュは `ws store status` の比較と、
+/// コマンドが失敗したときの代わりに使う。
+pub fn generate_file(
+    entry: &ManifestEntry,
+    store: &Path,
+    target_root: &Path,
+    exec: &Executor,
+) -> Result<()> {
+    let filepath = entry.filepath.as_str();
+    let Some(command) = entry.command.as_deref() else {
+        bail!("{}", t!("store.command_required"));
+    };
+    let target = worktree_path(target_root, filepath)?;
+    let cache = store.join(filepath);
+
+    let output = exec.run(
+        format!(
+            "(cd {} && sh -c {:?} > {})",
+            target_root.display(),
+            command,
+            target.display()
+        ),
+        || run_command(command, filepath, target_root).map(Some),
+    )?;
+    let Some(output) = output else {
+        return Ok(());
+    };
+
+    for path in [&target, &cache] {
+        if let Some(parent) = path.parent() {
+            exec.create_dir_all(parent)?;
+        }
+        // 生成したファイルは通常ファイルで置き換える（symlink の先を書き換えない）
+        if path.is_symlink() {
+            exec.remove(path)?;
+        }
+        exec.write(path, &output)?;
+        exec.apply_mode(path, entry.mode)?;
+    }
+    Ok(())
+}
+
+/// merge-json で最後にマージした base の保存先。
+///
+/// worktree ごとの状態なので、worktree 内ではなく worktree の git ディレクトリ
+/// （`.bare/worktrees/<name>/`）の下に置く。
+pub fn merge_base_path(wt_root: &Path, filepath: &str) -> Result<PathBuf> {
+    let git_dir = git::git_output_in(wt_root, &["rev-parse", "--absolute-git-dir"])?;
+    Ok(PathBuf::from(git_dir)
+        .join("ws")
+        .join("merge-base")
+        .join(filepath))
+}
+
+/// worktree のファイルのハッシュキャッシュの保存先（merge-base と同じく worktree の git ディレクトリの下）。
+pub fn hash_cache_path(wt_root: &Path) -> Result<PathBuf> {
+    let git_dir = git::git_output_in(wt_root, &["rev-parse", "--absolute-git-dir"])?;
+    Ok(PathBuf::from(git_dir).join("ws").join("hash-cache"))
+}
+
+/// worktree のハッシュキャッシュを読み込む。git ディレクトリを引けなければ保存しないキャッシュを返す。
+pub fn load_hash_cache(wt_root: &Option<PathBuf>) -> HashCache {
+    wt_root
+        .as_deref()
+        .and_then(|root| hash_cache_path(root).ok())
+        .map(HashCache::load)
+        .unwrap_or_default()
+}
+
+/// worktree に適用した profile の記録先。
+fn profile_path(wt_root: &Path) -> Result<PathBuf> {
+    let git_dir = git::git_output_in(wt_root, &["rev-parse", "--absolute-git-dir"])?;
+    Ok(PathBuf::from(git_dir).join("ws").join("profile"))
+}
+
+/// worktree に適用した profile を返す。記録がなければ `None`（すべてのエントリ）。
+pub fn read_profile(wt_root: &Path) -> Option<String> {
+    let content = fs::read_to_string(profile_path(wt_root).ok()?).ok()?;
+    Some(content.trim().to_string()).filter(|p| !p.is_empty())
+}
+
+/// worktree に適用した profile を記録する。`None` なら記録を消してすべてのエントリに戻す。
+pub fn save_profile(wt_root: &Path, profile: Option<&str>, exec: &Executor) -> Result<()> {
+    // dry-run の ws new では worktree がまだないので git ディレクトリを引けない
+    if exec.is_dry_run() && !wt_root.is_dir() {
+        return Ok(());
+    }
+    let path = profile_path(wt_root)?;
+    match profile {
+        Some(profile) => {
+            if let Some(parent) = path.parent() {
+                exec.create_dir_all(parent)?;
+            }
+            exec.write(&path, format!("{}\n", profile))
+        }
+        None if path.is_file() => exec.remove(&path),
+        None => Ok(()),
+    }
+}
+
+/// profile 名を確認する。worktree に適用した profile は前後の空白を除いて読み戻すので、空白は使えない。
+pub fn validate_profile(name: &str) -> Result<()> {
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        bail!("{}", t!("store.profile_invalid", profile = name));
+    }
+    Ok(())
+}
+
+/// manifest のどのエントリにも含まれない profile ならエラーにする（打ち間違いで何も配置されないのを防ぐ）。
+pub fn require_profile(entries: &[ManifestEntry], profile: &str) -> Result<()> {
+    if !entries
+        .iter()
+        .any(|e| e.profiles.iter().any(|p| p == profile))
+    {
+        bail!("{}", t!("store.profile_unknown", profile = profile));
+    }
+    Ok(())
+}
+
+/// store の base を、この worktree で最後にマージした base として記録する。
+pub fn save_merge_base(
+    store: &Path,
+    wt_root: &Path,
+    filepath: &str,
+    exec: &Executor,
+) -> Result<()> {
+    // dry-run の ws new では worktree がまだないので git ディレクトリを引けない
+    if exec.is_dry_run() && !wt_root.is_dir() {
+        return Ok(());
+    }
+    let path = merge_base_path(wt_root, filepath)?;
+    if let Some(parent) = path.parent() {
+        exec.create_dir_all(parent)?;
+    }
+    exec.copy(&store.join(filepath), &path)
+}
+
+fn read_json_object(path: &Path) -> Option<serde_json::Value> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| json::parse_object(&content))
+}
+
+/// worktree の merge-json ファイルを、store の base に worktree 側の変更分を重ねた内容で置き換える。
+///
+/// worktree 側の変更分は、前回マージした base との差分として取り出す。
+/// 記録がなければ現在の base を基準にする。
+pub fn merge_json_file(
+    store: &Path,
+    wt_root: &Path,
+    filepath: &str,
+    exec: &Executor,
+) -> Result<()> {
+    let target = worktree_path(wt_root, filepath)?;
+    let invalid = |path: &Path| {
+        anyhow::anyhow!(
+            "{}",
+            t!(
+                "store.merge_json_invalid",
+                file = path.display().to_string()
+            )
+        )
+    };
+    let store_file = store.join(filepath);
+    let base = read_json_object(&store_file).ok_or_else(|| invalid(&store_file))?;
+    let local = read_json_object(&target).ok_or_else(|| invalid(&target))?;
+    let previous = merge_base_path(wt_root, filepath)
+        .ok()
+        .and_then(|path| read_json_object(&path))
+        .unwrap_or_else(|| base.clone());
+
+    let merged = json::deep_merge(&base, &json::overlay(&previous, &local));
+    if merged != local {
+        exec.write(&target, json::to_pretty(&merged))?;
+    }
+    save_merge_base(store, wt_root, filepath, exec)
+}
+
+/// push 用に、worktree の merge-json ファイルの変更を `store_file` の内容に反映した JSON と、反映したキーの数を返す。
+///
+/// 前回マージした base にあったキーの変更だけを反映し、worktree で足したキー（overlay）は
+/// `include_overlay` のときだけ反映する。記録がなければ現在の base を基準にする。
+pub fn merge_json_push(
+    store_file: &Path,
+    wt_root: &Path,
+    filepath: &str,
+    include_overlay: bool,
+) -> Result<(String, usize)> {
+    let target = worktree_path(wt_root, filepath)?;
+    let invalid = |path: &Path| {
+        anyhow::anyhow!(
+            "{}",
+            t!(
+                "store.merge_json_invalid",
+                file = path.display().to_string()
+            )
+        )
+    };
+    let base = read_json_object(store_file).ok_or_else(|| invalid(store_file))?;
+    let local = read_json_object(&target).ok_or_else(|| invalid(&target))?;
+    let previous = merge_base_path(wt_root, filepath)
+        .ok()
+        .and_then(|path| read_json_object(&path))
+        .unwrap_or_else(|| base.clone());
+    let (pushed, changed) = json::apply_base_changes(&base, &previous, &local, include_overlay);
+    Ok((json::to_pretty(&pushed), changed))
+}
+
+/// `sh -c` でコマンドを実行し、標準出力を返す。標準エラーはそのまま端末に流す。
+fn run_command(command: &str, filepath: &str, root: &Path) -> Result<Vec<u8>> {
+    let output = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(root)
+        .env("WS_WORKTREE", root)
+        .env("WS_FILE", filepath)
+        .stdin(std::process::Stdio::null())
+        .stderr(std::process::Stdio::inherit())
+        .output()
+        .with_context(|| {
+            t!("store.command_failed", file = filepath, command = command).to_string()
+        })?;
+    if !output.status.success() {
+        bail!(
+            "{}",
+            t!("store.command_failed", file = filepath, command = command)
+        );
+    }
+    Ok(output.stdout)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileStatus {
+    Ok,
+    Missing,
+    MissingStore,
+    Modified,
+    NotLink,
+    WrongLink,
+    ModeDiff,
+    BaseChanged,
+    NotInProfile,
+    Error,
+    StoreOnly,
+    Extra,
+}
+
+impl std::fmt::Display for FileStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ok => write!(f, "OK"),
+            Self::Missing => write!(f, "MISSING"),
+            Self::MissingStore => write!(f, "MISSING(store)"),
+            Self::Modified => write!(f, "MODIFIED"),
+            Self::NotLink => write!(f, "NOT_LINK"),
+            Self::WrongLink => write!(f, "WRONG_LINK"),
+            Self::ModeDiff => write!(f, "MODE_DIFF"),
+            Self::BaseChanged => write!(f, "BASE_CHANGED"),
+            Self::NotInProfile => write!(f, "-(profile)"),
+            Self::Error => write!(f, "ERROR"),
+            Self::StoreOnly => write!(f, "(store only)"),
+            Self::Extra => write!(f, "EXTRA"),
+        }
+    }
+}
+
+pub fn file_status(
+    entry: &ManifestEntry,
+    store_file: &Path,
+    wt_root: &Option<PathBuf>,
+) -> FileStatus {
+    file_status_cached(entry, store_file, wt_root, &mut HashCache::default())
+}
+
+/// worktree の branch と profile を考慮した状態。`branch` に一致する variant があればそれと比較し、
+/// profile に含まれないエントリは、worktree になくても `MISSING` ではなく `NotInProfile` とする。
+pub fn worktree_status(
+    entry: &ManifestEntry,
+    store: &Path,
+    wt_root: &Option<PathBuf>,
+    branch: Option<&str>,
+    profile: Option<&str>,
+    cache: &mut HashCache,
+) -> FileStatus {
+    let variant = entry.for_branch(branch);
+    match file_status_cached(variant, &variant.store_path(store), wt_root, cache) {
+        FileStatus::Missing if !entry.in_profile(profile) => FileStatus::NotInProfile,
+        status => status,
+    }
+}
+
+/// `file_status` と同じだが、manifest にハッシュが記録されていれば worktree 側だけを
+/// `cache` 経由でハッシュして比較する。stat が変わっていないファイルは読み直さない。
+pub fn file_status_cached(
+    entry: &ManifestEntry,
+    store_file: &Path,
+    wt_root: &Option<PathBuf>,
+    cache: &mut HashCache,
+) -> FileStatus {
+    if !store_entry_exists(store_file) {
+        return FileStatus::MissingStore;
+    }
+
+    let Some(root) = wt_root else {
+        return FileStatus::StoreOnly;
+    };
+
+    let wt_file = root.join(&entry.filepath);
+
+    if !path_or_symlink_exists(&wt_file) {
+        return FileStatus::Missing;
+    }
+
+    // manifest に記録がない（旧形式）場合は store 側のパーミッションを基準にする
+    let expected_mode = entry.mode.or_else(|| file_mode(store_file));
+
+    match entry.strategy {
+        Strategy::Symlink => {
+            let is_link = wt_file
+                .symlink_metadata()
+                .map(|m| m.file_type().is_symlink())
+                .unwrap_or(false);
+
+            if !is_link {
+                return FileStatus::NotLink;
+            }
+
+            let link_target = match fs::read_link(&wt_file) {
+                Ok(t) => t,
+                Err(_) => return FileStatus::Error,
+            };
+            if link_target != *store_file {
+                FileStatus::WrongLink
+            } else if file_mode(store_file) != expected_mode {
+                FileStatus::ModeDiff
+            } else {
+                FileStatus::Ok
+            }
+        }
+        // seed は作成後に内容が変わるのが前提なので、存在すれば OK とする
+        Strategy::Seed => FileStatus::Ok,
+        Strategy::LinkTree => {
+            if !is_real_dir(&wt_file) {
+                return FileStatus::NotLink;
+            }
+            // worktree 固有のファイル（Extra）は正常な状態なので、エントリの状態には含めない
+            let issues = link_tree_status(store_file, &wt_file, &entry.filter);
+            [
+                FileStatus::NotLink,
+                FileStatus::WrongLink,
+                FileStatus::Missing,
+            ]
+            .into_iter()
+            .find(|status| issues.iter().any(|(_, s)| s == status))
+            .unwrap_or(FileStatus::Ok)
+        }
+        Strategy::Hardlink => {
+            // 内容が同じでも inode が別なら、エディタなどで置き換えられてリンクが切れている
+            if is_hardlinked(store_file, &wt_file) {
+                FileStatus::Ok
+            } else {
+                FileStatus::NotLink
+            }
+        }
+        // command はキャッシュした前回の出力と比較する
+        Strategy::Copy | Strategy::Clone | Strategy::Command => {
+            if let Some(ref hash) = entry.hash
+                && store_file.is_dir() == wt_file.is_dir()
+            {
+                if cache
+                    .hash_filtered(&wt_file, &entry.filepath, &entry.filter)
+                    .ok()
+                    .as_ref()
+                    != Some(hash)
+                {
+                    FileStatus::Modified
+                } else if file_mode(&wt_file) != expected_mode
+                    || (store_file.is_dir() && !dir_modes_equal_recursive(store_file, &wt_file))
+                {
+                    FileStatus::ModeDiff
+                } else {
+                    FileStatus::Ok
+                }
+            } else if store_file.is_dir() {
+                if !wt_file.is_dir()
+                    || !dirs_equal_recursive(store_file, &wt_file, &entry.filter, "")
+                {
+                    FileStatus::Modified
+                } else if file_mode(&wt_file) != expected_mode
+                    || !dir_modes_equal_recursive(store_file, &wt_file)
+                {
+                    FileStatus::ModeDiff
+                } else {
+                    FileStatus::Ok
+                }
+            } else {
+                let store_content = fs::read(store_file).ok();
+                let wt_content = fs::read(&wt_file).ok();
+                if store_content != wt_content {
+                    FileStatus::Modified
+                } else if file_mode(&wt_file) != expected_mode {
+                    FileStatus::ModeDiff
+                } else {
+                    FileStatus::Ok
+                }
+            }
+        }
+        Strategy::MergeJson => {
+            if read_json_object(&wt_file).is_none() {
+                return FileStatus::Error;
+            }
+            // 前回マージしたときから base が変わっていれば、pull で重ね直す必要がある
+            let base = read_json_object(store_file);
+            let merged_base = merge_base_path(root, &entry.filepath)
+                .ok()
+                .and_then(|path| read_json_object(&path));
+            if base.is_none() || merged_base != base {
+                FileStatus::BaseChanged
+            } else if file_mode(&wt_file) != expected_mode {
+                FileStatus::ModeDiff
+            } else {
+                FileStatus::Ok
+            }
+        }
+        Strategy::Dotenv => {
+            // キーと値の集合が同じなら、コメントや並び順の違いは変更とみなさない
+            let store_content = fs::read_to_string(store_file).unwrap_or_default();
+            let Ok(wt_content) = fs::read_to_string(&wt_file) else {
+                return FileStatus::Error;
+            };
+            if !dotenv::equivalent(&store_content, &wt_content) {
+                FileStatus::Modified
+            } else if file_mode(&wt_file) != expected_mode {
+                FileStatus::ModeDiff
+            } else {
+                FileStatus::Ok
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs as unix_fs;
+    use tempfile::TempDir;
+
+    /// store ディレクトリと manifest を構築するヘルパー
+    fn setup_store() -> (TempDir, PathBuf) {
+        let tmp = TempDir::new().unwrap();
+        let store = tmp.path().join("worktree-store");
+        fs::create_dir_all(&store).unwrap();
+        fs::write(manifest_path(&store), "version = 1\n").unwrap();
+        (tmp, store)
+    }
+
+    /// 旧形式の manifest だけがある store にする
+    fn write_legacy_manifest(store: &Path, content: impl AsRef<[u8]>) {
+        let _ = fs::remove_file(manifest_path(store));
+        fs::write(store.join("manifest"), content).unwrap();
+    }
+
+    // ---- read_manifest ----
+
+    #[test]
+    fn read_manifest_parses_entries() {
+        let (_tmp, store) = setup_store();
+        fs::write(
+            manifest_path(&store),
+            r#"version = 1
+
+[[files]]
+path = ".envrc"
+strategy = "symlink"
+
+[[files]]
+path = ".mcp.json"
+strategy = "copy"
+mode = "0600"
+profiles = ["ci"]
+"#,
+        )
+        .unwrap();
+
+        let entries = read_manifest(&store).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].strategy, Strategy::Symlink);
+        assert_eq!(entries[0].filepath, ".envrc");
+        assert_eq!(entries[1].strategy, Strategy::Copy);
+        assert_eq!(entries[1].filepath, ".mcp.json");
+        assert_eq!(entries[1].mode, Some(0o600));
+        assert_eq!(entries[1].profiles, vec!["ci".to_string()]);
+    }
+
+    #[test]
+    fn read_manifest_reports_syntax_and_schema_errors_with_line_number() {
+        let (_tmp, store) = setup_store();
+        for (content, line, detail) in [
+            ("version = 1\n[[files]]\npath = \".env\n", 3, ""),
+            (
+                "version = 1\n[[files]]\npath = \".env\"\nstrategy = \"copy\"\ncolor = \"red\"\n",
+                5,
+                "color",
+            ),
+            (
+                "version = 1\n[[files]]\npath = \".env\"\nstrategy = \"move\"\n",
+                4,
+                "symlink, copy",
+            ),
+            (
+                "version = 1\n[[files]]\npath = \".env\"\nstrategy = \"copy\"\nmode = \"rw\"\n",
+                3,
+                "rw",
+            ),
+            ("version = 2\n", 1, "2"),
+            (
+                "version = 1\n[[files]]\npath = \".env\"\nstrategy = \"copy\"\n[[files]]\npath = \".env\"\nstrategy = \"dotenv\"\n",
+                6,
+                ".env",
+            ),
+        ] {
+            fs::write(manifest_path(&store), content).unwrap();
+            let err = read_manifest(&store).err().unwrap().to_string();
+            assert!(err.contains(&format!("manifest.toml:{}:", line)), "{}", err);
+            assert!(err.contains(detail), "{}", err);
+        }
+    }
+
+    #[test]
+    fn read_manifest_rejects_unsafe_paths_with_line_number() {
+        let (_tmp, store) = setup_store();
+        for (record, detail) in [
+            (
+                "path = \"../../.ssh/config\"\nstrategy = \"copy\"",
+                ".ssh/config",
+            ),
+            ("path = \"/etc/passwd\"\nstrategy = \"copy\"", "/etc/passwd"),
+            ("path = \"a/./b\"\nstrategy = \"copy\"", "a/./b"),
+        ] {
+            fs::write(
+                manifest_path(&store),
+                format!("version = 1\n\n[[files]]\n{}\n", record),
+            )
+            .unwrap();
+            let err = read_manifest(&store).err().unwrap().to_string();
+            assert!(err.contains("manifest.toml:4:"), "{}", err);
+            assert!(err.contains(detail), "{}", err);
+        }
+
+        fs::write(
+            manifest_path(&store),
+            "version = 1\n[[files]]\npath = \".env\"\nstrategy = \"copy\"\n[[files.variants]]\nbranch = \"..\"\n",
+        )
+        .unwrap();
+        let err = read_manifest(&store).err().unwrap().to_string();
+        assert!(err.contains("manifest.toml:6:"), "{}", err);
+    }
+
+    #[test]
+    fn read_legacy_manifest_when_toml_is_missing() {
+        let (_tmp, store) = setup_store();
+        write_legacy_manifest(&store, "\nsymlink:.envrc\n\ncopy:.env\tmode=0600\n");
+        assert!(manifest_exists(&store));
+
+        let entries = read_manifest(&store).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].mode, Some(0o600));
+    }
+
+    #[test]
+    fn read_legacy_manifest_rejects_malformed_lines() {
+        let (_tmp, store) = setup_store();
+        for (content, line) in [
+            ("symlink:.envrc\n\nno_colon_here\n", 3),
+            ("symlink:.envrc\n:empty_strategy\n", 2),
+            ("copy:.env\tmode=rw\n", 1),
+            ("symlink:.envrc\ncopy:.env\tmtime=123\n", 2),
+            ("copy:.env\tnot-an-attribute\n", 1),
+        ] {
+            write_legacy_manifest(&store, content);
+            let err = read_manifest(&store).err().unwrap().to_string();
+            assert!(err.contains(&format!("manifest:{}:", line)), "{}", err);
+        }
+    }
+
+    #[test]
+    fn read_legacy_manifest_rejects_unsafe_paths_with_line_number() {
+        let (_tmp, store) = setup_store();
+        for (line, detail) in [
+            ("copy:../../.ssh/config", ".ssh/config"),
+            ("copy:/etc/passwd", "/etc/passwd"),
+            ("copy:.env\tbranch=..", ".."),
+        ] {
+            write_legacy_manifest(&store, format!("symlink:.envrc\n{}\n", line));
+            let err = read_manifest(&store).err().unwrap().to_string();
+            assert!(err.contains("manifest:2:"), "{}", err);
+            assert!(err.contains(detail), "{}", err);
+        }
+    }
+
+    #[test]
+    fn write_manifest_migrates_legacy_manifest() {
+        let (_tmp, store) = setup_store();
+        write_legacy_manifest(
+            &store,
+            "copy:.env\thash=sha256:abc\tprofiles=ci\ncopy:.env\tbranch=main\nsymlink:.envrc\n",
+        );
+
+        let entries = read_manifest(&store).unwrap();
+        write_manifest(&store, &entries).unwrap();
+        retire_legacy_manifest(&store, &Executor::new(false)).unwrap();
+
+        assert!(!store.join("manifest").exists());
+        assert!(store.join("manifest.legacy").is_file());
+        let migrated = read_manifest(&store).unwrap();
+        assert_eq!(migrated.len(), 2);
+        assert_eq!(migrated[0].hash.as_deref(), Some("sha256:abc"));
+        assert_eq!(migrated[0].profiles, vec!["ci".to_string()]);
+        assert_eq!(migrated[0].variants[0].branch.as_deref(), Some("main"));
+        assert_eq!(migrated[1].strategy, Strategy::Symlink);
+    }
+
+    #[test]
+    fn validate_entry_path_accepts_only_relative_paths_inside_worktree() {
+        assert!(validate_entry_path(".env").is_ok());
+        assert!(validate_entry_path(".claude/settings.local.json").is_ok());
+        assert!(validate_entry_path("a..b/c").is_ok());
+        // manifest は TOML なので、タブや改行を含む名前もそのまま記録できる
+        assert!(validate_entry_path("a\tb").is_ok());
+        assert!(validate_entry_path("a\nb").is_ok());
+        for path in ["", "/abs", "..", "a/../../b", "a//b", "./a", "a/"] {
+            assert!(validate_entry_path(path).is_err(), "{:?}", path);
+        }
+    }
+
+    #[test]
+    fn worktree_path_rejects_symlinked_parent() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path().join("wt");
+        let outside = tmp.path().join("outside");
+        fs::create_dir_all(root.join("real")).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        unix_fs::symlink(&outside, root.join("linked")).unwrap();
+
+        assert_eq!(
+            worktree_path(&root, "real/.env").unwrap(),
+            root.join("real/.env")
+        );
+        // 最後の要素自体は symlink でもよい（symlink strategy の配置先）
+        assert!(worktree_path(&root, "linked").is_ok());
+        let err = worktree_path(&root, "linked/.env").unwrap_err().to_string();
+        assert!(err.contains("linked"), "{}", err);
+    }
+
+    #[test]
+    fn apply_file_does_not_write_through_symlinked_parent() {
+        let tmp = TempDir::new().unwrap();
+        let store = tmp.path().join("store");
+        fs::create_dir_all(store.join("cfg")).unwrap();
+        fs::write(store.join("cfg/.env"), "A=1").unwrap();
+        let target_root = tmp.path().join("target");
+        let outside = tmp.path().join("outside");
+        fs::create_dir_all(&target_root).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        unix_fs::symlink(&outside, target_root.join("cfg")).unwrap();
+
+        let result = apply_file(
+            &ManifestEntry::new(Strategy::Copy, "cfg/.env"),
+            &store,
+            &target_root,
+            None,
+            &Executor::default(),
+        );
+        assert!(result.is_err());
+        assert!(!outside.join(".env").exists());
+    }
+
+    #[test]
+    fn read_manifest_filepath_with_colon() {
+        let (_tmp, store) = setup_store();
+        write_legacy_manifest(&store, "symlink:path:with:colon\n");
+
+        let entries = read_manifest(&store).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].strategy, Strategy::Symlink);
+        assert_eq!(entries[0].filepath, "path:with:colon");
+    }
+
+    #[test]
+    fn write_then_read_roundtrip() {
+        let (_tmp, store) = setup_store();
+        let original = vec![
+            ManifestEntry::new(Strategy::Symlink, ".envrc"),
+            ManifestEntry::new(Strategy::Copy, ".mcp.json"),
+        ];
+
+        write_manifest(&store, &original).unwrap();
+        let read_back = read_manifest(&store).unwrap();
+
+        assert_eq!(read_back.len(), 2);
+        assert_eq!(read_back[0].strategy, Strategy::Symlink);
+        assert_eq!(read_back[0].filepath, ".envrc");
+        assert_eq!(read_back[1].strategy, Strategy::Copy);
+        assert_eq!(read_back[1].filepath, ".mcp.json");
+    }
+
+    #[test]
+    fn read_manifest_parses_mode_attribute() {
+        let (_tmp, store) = setup_store();
+        write_legacy_manifest(&store, "copy:.env\tmode=0600\nsymlink:.envrc\n");
+
+        let entries = read_manifest(&store).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].filepath, ".env");
+        assert_eq!(entries[0].mode, Some(0o600));
+        assert_eq!(entries[1].filepath, ".envrc");
+        assert_eq!(entries[1].mode, None);
+    }
+
+    #[test]
+    fn write_manifest_records_mode() {
+        let (_tmp, store) = setup_store();
+        let mut entry = ManifestEntry::new(Strategy::Copy, ".env");
+        entry.mode = Some(0o640);
+
+        write_manifest(&store, &[entry]).unwrap();
+
+        let content = fs::read_to_string(manifest_path(&store)).unwrap();
+        assert!(content.contains("mode = \"0640\"\n"), "{}", content);
+        assert_eq!(read_manifest(&store).unwrap()[0].mode, Some(0o640));
+    }
+
+    // ---- size / binary ----
+
+    #[test]
+    fn path_size_sums_directory_contents() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("data");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a"), "12345").unwrap();
+        fs::write(dir.join("sub/b"), "678").unwrap();
+
+        assert_eq!(path_size(&dir), 8);
+        assert_eq!(path_size(&dir.join("a")), 5);
+        assert_eq!(path_size(&tmp.path().join("missing")), 0);
+    }
+
+    #[test]
+    fn find_binary_detects_nul_bytes() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("assets");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("readme.txt"), "text").unwrap();
+        assert_eq!(find_binary(&dir), None);
+
+        fs::write(dir.join("logo.png"), [0x89, b'P', b'N', b'G', 0x00, 0x01]).unwrap();
+        assert_eq!(find_binary(&dir), Some(dir.join("logo.png")));
+    }
+
+    #[test]
+    fn format_size_uses_binary_units() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(2 * 1024 * 1024 * 1024), "2.0 GB");
+    }
+
+    #[test]
+    fn cloned_copy_keeps_content_and_modes() {
+        let tmp = TempDir::new().unwrap();
+        let src = tmp.path().join("src");
+        fs::create_dir_all(src.join("sub")).unwrap();
+        fs::write(src.join("sub/run.sh"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(src.join("sub/run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+
+        let dst = tmp.path().join("dst");
+        copy_dir_with(&src, &dst, CopyMethod::Clone, &PathFilter::default(), "").unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dst.join("sub/run.sh")).unwrap(),
+            "#!/bin/sh\n"
+        );
+        assert_eq!(file_mode(&dst.join("sub/run.sh")), Some(0o755));
+        // 複製先は独立したファイル
+        assert_ne!(
+            fs::metadata(src.join("sub/run.sh")).unwrap().ino(),
+            fs::metadata(dst.join("sub/run.sh")).unwrap().ino()
+        );
+    }
+
+    #[test]
+    fn cloned_copy_reports_full_copies_when_reflink_is_unavailable() {
+        let tmp = TempDir::new().unwrap();
+        let src = tmp.path().join("src");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("a"), "data").unwrap();
+
+        let copied = copy_dir_with(
+            &src,
+            &tmp.path().join("dst"),
+            CopyMethod::Clone,
+            &PathFilter::default(),
+            "",
+        )
+        .unwrap();
+        // 同じファイルシステムで reflink が使えるかどうかと一致する
+        let reflinked = reflink::reflink(&src.join("a"), &tmp.path().join("probe")).is_ok();
+        assert_eq!(copied, !reflinked);
+        // Copy は常に丸ごとコピー
+        assert!(
+            copy_dir_with(
+                &src,
+                &tmp.path().join("plain"),
+                CopyMethod::Copy,
+                &PathFilter::default(),
+                ""
+            )
+            .unwrap()
+        );
+    }
+
+    // ---- file_status ----
+
+    #[test]
+    fn file_status_missing_store() {
+        let tmp = TempDir::new().unwrap();
+        let store_file = tmp.path().join("nonexistent");
+        let entry = ManifestEntry::new(Strategy::Symlink, "test");
+        assert_eq!(
+            file_status(&entry, &store_file, &None),
+            FileStatus::MissingStore
+        );
+    }
+
+    #[test]
+    fn file_status_store_only() {
+        let tmp = TempDir::new().unwrap();
+        let store_file = tmp.path().join("test_file");
+        fs::write(&store_file, "content").unwrap();
+
+        let entry = ManifestEntry::new(Strategy::Symlink, "test_file");
+        assert_eq!(
+            file_status(&entry, &store_file, &None),
+            FileStatus::StoreOnly
+        );
+    }
+
+    #[test]
+    fn file_status_missing_in_worktree() {
+        let tmp = TempDir::new().unwrap();
+        let store_file = tmp.path().join("store_copy");
+        fs::write(&store_file, "content").unwrap();
+
+        let wt_root = tmp.path().join("worktree");
+        fs::create_dir_all(&wt_root).unwrap();
+
+        let entry = ManifestEntry::new(Strategy::Symlink, "missing_file");
+        assert_eq!(
+            file_status(&entry, &store_file, &Some(wt_root)),
+            FileStatus::Missing
+        );
+    }
+
+    #[test]
+    fn file_status_symlink_not_link() {
+        let tmp = TempDir::new().unwrap();
+        let store_file = tmp.path().join("store_copy");
+        fs::write(&store_file, "content").unwrap();
+
+        let wt_root = tmp.path().join("worktree");
+        fs::create_dir_all(&wt_root).unwrap();
+        fs::write(wt_root.join(".envrc"), "regular file").unwrap();
+
+        let entry = ManifestEntry::new(Strategy::Symlink, ".envrc");
+        assert_eq!(
+            file_status(&entry, &store_file, &Some(wt_root)),
+            FileStatus::NotLink
+        );
+    }
+
+    #[test]
+    fn file_status_symlink_wrong_target() {
+        let tmp = TempDir::new().unwrap();
+        let store_file = tmp.path().join("store_copy");
+        fs::write(&store_file, "content").unwrap();
+
+        let wt_root = tmp.path().join("worktree");
+        fs::create_dir_all(&wt_root).unwrap();
+
+        let wrong_target = tmp.path().join("wrong_target");
+        fs::write(&wrong_target, "wrong").unwrap();
+        unix_fs::symlink(&wrong_target, wt_root.join(".envrc")).unwrap();
+
+        let entry = ManifestEntry::new(Strategy::Symlink, ".envrc");
+        assert_eq!(
+            file_status(&entry, &store_file, &Some(wt_root)),
+            FileStatus::WrongLink
+        );
+    }
+
+    #[test]
+    fn file_status_symlink_ok() {
+        let tmp = TempDir::new().unwrap();
+        let store_file = tmp.path().join("store_copy");
+        fs::write(&store_file, "content").unwrap();
+
+        let wt_root = tmp.path().join("worktree");
+        fs::create_dir_all(&wt_root).unwrap();
+        unix_fs::symlink(&store_file, wt_root.join(".envrc")).unwrap();
+
+        let entry = ManifestEntry::new(Strategy::Symlink, ".envrc");
+        assert_eq!(
+            file_status(&entry, &store_file, &Some(wt_root)),
+            FileStatus::Ok
+        );
+    }
+
+    #[test]
+    fn file_status_copy_modified() {
+        let tmp = TempDir::new().unwrap();
+        let store_file = tmp.path().join("store_copy");
+        fs::write(&store_file, "original").unwrap();
+
+        let wt_root = tmp.path().join("worktree");
+        fs::create_dir_all(&wt_root).unwrap();
+        fs::write(wt_root.join(".mcp.json"), "modified").unwrap();
+
+        let entry = ManifestEntry::new(Strategy::Copy, ".mcp.json");
+        assert_eq!(
+            file_status(&entry, &store_file, &Some(wt_root)),
+            FileStatus::Modified
+        );
+    }
+
+    #[test]
+    fn file_status_copy_ok() {
+        let tmp = TempDir::new().unwrap();
+        let store_file = tmp.path().join("store_copy");
+        fs::write(&store_file, "same content").unwrap();
+
+        let wt_root = tmp.path().join("worktree");
+        fs::create_dir_all(&wt_root).unwrap();
+        fs::write(wt_root.join(".mcp.json"), "same content").unwrap();
+
+        let entry = ManifestEntry::new(Strategy::Copy, ".mcp.json");
+        assert_eq!(
+            file_status(&entry, &store_file, &Some(wt_root)),
+            FileStatus::Ok
+        );
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::os::unix::fs as unix_fs;
-    use tempfile::TempDir;
+    #[test]
+    fn file_status_copy_uses_recorded_hash() {
+        let tmp = TempDir::new().unwrap();
+        let store_file = tmp.path().join("store_copy");
+        fs::write(&store_file, "same content").unwrap();
 
-    /// store ディレクトリと manifest を構築するヘルパー
-    fn setup_store() -> (TempDir, PathBuf) {
+        let wt_root = tmp.path().join("worktree");
+        fs::create_dir_all(&wt_root).unwrap();
+        fs::write(wt_root.join(".mcp.json"), "same content").unwrap();
+
+        let mut entry = ManifestEntry::new(Strategy::Copy, ".mcp.json");
+        entry.hash = Some(crate::hash::hash_bytes("same content"));
+        let mut cache = HashCache::default();
+        let wt_root = Some(wt_root);
+        assert_eq!(
+            file_status_cached(&entry, &store_file, &wt_root, &mut cache),
+            FileStatus::Ok
+        );
+
+        // worktree が記録と違えば MODIFIED（store の内容ではなくハッシュと比較する）
+        entry.hash = Some(crate::hash::hash_bytes("other content"));
+        assert_eq!(
+            file_status_cached(&entry, &store_file, &wt_root, &mut cache),
+            FileStatus::Modified
+        );
+    }
+
+    #[test]
+    fn file_status_hardlink_ok_and_not_link() {
         let tmp = TempDir::new().unwrap();
-        let store = tmp.path().join("worktree-store");
-        fs::create_dir_all(&store).unwrap();
-        fs::write(store.join("manifest"), "").unwrap();
-        (tmp, store)
+        let store_file = tmp.path().join("store_file");
+        fs::write(&store_file, "shared").unwrap();
+
+        let wt_root = tmp.path().join("worktree");
+        fs::create_dir_all(&wt_root).unwrap();
+        hardlink_entry(&store_file, &wt_root.join(".env"), &PathFilter::default()).unwrap();
+
+        let entry = ManifestEntry::new(Strategy::Hardlink, ".env");
+        assert_eq!(
+            file_status(&entry, &store_file, &Some(wt_root.clone())),
+            FileStatus::Ok
+        );
+
+        // 同じ内容でも別ファイルに置き換わっていればリンク切れ
+        fs::remove_file(wt_root.join(".env")).unwrap();
+        fs::write(wt_root.join(".env"), "shared").unwrap();
+        assert_eq!(
+            file_status(&entry, &store_file, &Some(wt_root)),
+            FileStatus::NotLink
+        );
     }
 
-    // ---- read_manifest ----
+    #[test]
+    fn manifest_roundtrip_keeps_hash() {
+        let (_tmp, store) = setup_store();
+        let mut entry = ManifestEntry::new(Strategy::Copy, ".env");
+        entry.hash = Some("sha256:abc".to_string());
+        write_manifest(&store, &[entry]).unwrap();
+
+        let content = fs::read_to_string(manifest_path(&store)).unwrap();
+        assert_eq!(
+            content,
+            "version = 1\n\n[[files]]\npath = \".env\"\nstrategy = \"copy\"\nhash = \"sha256:abc\"\n"
+        );
+        let entries = read_manifest(&store).unwrap();
+        assert_eq!(entries[0].hash.as_deref(), Some("sha256:abc"));
+    }
 
     #[test]
-    fn read_manifest_parses_entries() {
+    fn manifest_roundtrip_keeps_profiles() {
         let (_tmp, store) = setup_store();
-        fs::write(store.join("manifest"), "symlink:.envrc\ncopy:.mcp.json\n").unwrap();
+        let mut entry = ManifestEntry::new(Strategy::Copy, ".env");
+        entry.profiles = vec!["review".to_string(), "ci".to_string()];
+        write_manifest(&store, &[entry]).unwrap();
 
+        let content = fs::read_to_string(manifest_path(&store)).unwrap();
+        assert!(
+            content.contains("profiles = [\"review\", \"ci\"]\n"),
+            "{}",
+            content
+        );
         let entries = read_manifest(&store).unwrap();
-        assert_eq!(entries.len(), 2);
-        assert_eq!(entries[0].strategy, Strategy::Symlink);
-        assert_eq!(entries[0].filepath, ".envrc");
-        assert_eq!(entries[1].strategy, Strategy::Copy);
-        assert_eq!(entries[1].filepath, ".mcp.json");
+        assert!(entries[0].in_profile(Some("ci")));
+        assert!(!entries[0].in_profile(Some("dev")));
+        assert!(entries[0].in_profile(None));
     }
 
     #[test]
-    fn read_manifest_skips_empty_and_malformed() {
+    fn manifest_roundtrip_keeps_variants_after_their_entry() {
         let (_tmp, store) = setup_store();
-        fs::write(
-            store.join("manifest"),
-            "\nsymlink:.envrc\n\nno_colon_here\n:empty_strategy\n",
+        let mut entry = ManifestEntry::new(Strategy::Copy, ".env");
+        let mut variant = ManifestEntry::new(Strategy::Copy, ".env");
+        variant.branch = Some("release/*".to_string());
+        variant.hash = Some("sha256:abc".to_string());
+        entry.variants.push(variant);
+        write_manifest(
+            &store,
+            &[entry, ManifestEntry::new(Strategy::Symlink, ".envrc")],
         )
         .unwrap();
 
+        let content = fs::read_to_string(manifest_path(&store)).unwrap();
+        assert_eq!(
+            content,
+            "version = 1\n\n[[files]]\npath = \".env\"\nstrategy = \"copy\"\n\n[[files.variants]]\nbranch = \"release/*\"\nhash = \"sha256:abc\"\n\n[[files]]\npath = \".envrc\"\nstrategy = \"symlink\"\n"
+        );
         let entries = read_manifest(&store).unwrap();
-        assert_eq!(entries.len(), 1);
-        assert_eq!(entries[0].filepath, ".envrc");
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].variants.len(), 1);
+        assert_eq!(entries[0].variants[0].branch.as_deref(), Some("release/*"));
     }
 
     #[test]
-    fn read_manifest_filepath_with_colon() {
+    fn manifest_records_paths_relative_to_project() {
         let (_tmp, store) = setup_store();
-        fs::write(store.join("manifest"), "symlink:path:with:colon\n").unwrap();
-
+        let mut entry = ManifestEntry::new(Strategy::Dotenv, "apps/web/.env");
+        entry.project = Some("apps/web".to_string());
+        // サブプロジェクトの外に移動したエントリは root からの相対パスで記録する
+        let mut moved = ManifestEntry::new(Strategy::Copy, "shared/.npmrc");
+        moved.project = Some("apps/api".to_string());
+        write_manifest(&store, &[entry, moved]).unwrap();
+
+        let content = fs::read_to_string(manifest_path(&store)).unwrap();
+        assert!(
+            content.contains("path = \".env\"\nproject = \"apps/web\"\n"),
+            "{}",
+            content
+        );
         let entries = read_manifest(&store).unwrap();
-        assert_eq!(entries.len(), 1);
-        assert_eq!(entries[0].strategy, Strategy::Symlink);
-        assert_eq!(entries[0].filepath, "path:with:colon");
+        assert_eq!(entries[0].filepath, "apps/web/.env");
+        assert_eq!(entries[0].project.as_deref(), Some("apps/web"));
+        assert_eq!(entries[0].project_path(), ".env");
+        assert_eq!(entries[1].filepath, "shared/.npmrc");
+        assert_eq!(entries[1].project, None);
     }
 
     #[test]
-    fn write_then_read_roundtrip() {
+    fn manifest_roundtrip_keeps_filter_and_shares_it_with_variants() {
         let (_tmp, store) = setup_store();
-        let original = vec![
-            ManifestEntry {
-                strategy: Strategy::Symlink,
-                filepath: ".envrc".into(),
-            },
-            ManifestEntry {
-                strategy: Strategy::Copy,
-                filepath: ".mcp.json".into(),
-            },
-        ];
-
-        write_manifest(&store, &original).unwrap();
-        let read_back = read_manifest(&store).unwrap();
+        let mut entry = ManifestEntry::new(Strategy::Copy, ".idea");
+        entry.filter.exclude = vec!["workspace.xml".to_string()];
+        let mut variant = ManifestEntry::new(Strategy::Copy, ".idea");
+        variant.branch = Some("release/*".to_string());
+        entry.variants.push(variant);
+        write_manifest(&store, &[entry]).unwrap();
+
+        let content = fs::read_to_string(manifest_path(&store)).unwrap();
+        assert!(
+            content.contains("exclude = [\"workspace.xml\"]\n"),
+            "{}",
+            content
+        );
+        let entries = read_manifest(&store).unwrap();
+        assert_eq!(entries[0].filter.exclude, vec!["workspace.xml"]);
+        assert!(entries[0].filter.include.is_empty());
+        assert_eq!(entries[0].variants[0].filter, entries[0].filter);
+    }
 
-        assert_eq!(read_back.len(), 2);
-        assert_eq!(read_back[0].strategy, Strategy::Symlink);
-        assert_eq!(read_back[0].filepath, ".envrc");
-        assert_eq!(read_back[1].strategy, Strategy::Copy);
-        assert_eq!(read_back[1].filepath, ".mcp.json");
+    #[test]
+    fn parse_manifest_rejects_filter_for_unsupported_strategy() {
+        let content = "version = 1\n\n[[files]]\npath = \".idea\"\nstrategy = \"symlink\"\nexclude = [\"workspace.xml\"]\n";
+        let err = parse_manifest(Path::new("manifest.toml"), content)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("manifest.toml:4:"), "{}", err);
     }
 
-    // ---- file_status ----
+    #[test]
+    fn parse_manifest_rejects_project_outside_worktree() {
+        let content = "version = 1\n\n[[files]]\npath = \".env\"\nproject = \"../web\"\nstrategy = \"copy\"\n";
+        let err = parse_manifest(Path::new("manifest.toml"), content)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("manifest.toml:5:"), "{}", err);
+    }
 
     #[test]
-    fn file_status_missing_store() {
-        let tmp = TempDir::new().unwrap();
-        let store_file = tmp.path().join("nonexistent");
-        let entry = ManifestEntry {
-            strategy: Strategy::Symlink,
-            filepath: "test".into(),
-        };
+    fn project_file_resolves_relative_to_project() {
+        assert_eq!(project_file(None, "./.env"), ".env");
+        assert_eq!(project_file(Some("apps/web"), ".env"), "apps/web/.env");
         assert_eq!(
-            file_status(&entry, &store_file, &None),
-            FileStatus::MissingStore
+            project_file(Some("apps/web"), "../api/.env"),
+            "apps/api/.env"
         );
+        assert_eq!(project_file(Some("apps"), "../../.env"), "../.env");
+        assert_eq!(project_file(Some("apps"), "/etc/passwd"), "/etc/passwd");
     }
 
     #[test]
-    fn file_status_store_only() {
-        let tmp = TempDir::new().unwrap();
-        let store_file = tmp.path().join("test_file");
-        fs::write(&store_file, "content").unwrap();
+    fn in_sparse_keeps_root_entries_and_matching_projects() {
+        let root = ManifestEntry::new(Strategy::Copy, ".env");
+        let mut web = ManifestEntry::new(Strategy::Copy, "apps/web/.env");
+        web.project = Some("apps/web".to_string());
+        let mut webapp = ManifestEntry::new(Strategy::Copy, "apps/webapp/.env");
+        webapp.project = Some("apps/webapp".to_string());
+        let dirs = vec!["apps/web".to_string()];
+
+        assert!(root.in_sparse(&dirs));
+        assert!(web.in_sparse(&dirs));
+        assert!(web.in_sparse(&["apps".to_string()]));
+        assert!(!webapp.in_sparse(&dirs));
+    }
 
-        let entry = ManifestEntry {
-            strategy: Strategy::Symlink,
-            filepath: "test_file".into(),
-        };
+    #[test]
+    fn read_legacy_manifest_rejects_variant_without_entry() {
+        let (_tmp, store) = setup_store();
+        write_legacy_manifest(&store, "symlink:.envrc\ncopy:.env\tbranch=release/*\n");
+        let err = read_manifest(&store).err().unwrap().to_string();
+        assert!(err.contains("manifest:2:"), "{}", err);
+        assert!(err.contains(".env"), "{}", err);
+    }
+
+    #[test]
+    fn migrate_legacy_manifest_rewrites_once() {
+        let (_tmp, store) = setup_store();
+        write_legacy_manifest(&store, "copy:.env\tmode=0600\n");
+
+        // dry-run では書き換えない
+        migrate_legacy_manifest(&store, &Executor::new(true)).unwrap();
+        assert!(!manifest_path(&store).exists());
+        assert!(store.join(LEGACY_MANIFEST_FILE).is_file());
+
+        let exec = Executor::new(false);
+        migrate_legacy_manifest(&store, &exec).unwrap();
+        assert!(manifest_path(&store).is_file());
+        assert!(!store.join(LEGACY_MANIFEST_FILE).exists());
+        assert_eq!(read_manifest(&store).unwrap()[0].mode, Some(0o600));
+
+        // 移行済みなら何もしない
+        let before = fs::read_to_string(manifest_path(&store)).unwrap();
+        migrate_legacy_manifest(&store, &exec).unwrap();
+        assert_eq!(fs::read_to_string(manifest_path(&store)).unwrap(), before);
+    }
+
+    #[test]
+    fn for_branch_picks_most_specific_variant() {
+        let mut entry = ManifestEntry::new(Strategy::Copy, ".env");
+        for pattern in ["release/*", "release/1.*", "main"] {
+            let mut variant = ManifestEntry::new(Strategy::Copy, ".env");
+            variant.branch = Some(pattern.to_string());
+            entry.variants.push(variant);
+        }
+        let pick = |branch| entry.for_branch(branch).branch.clone();
+        assert_eq!(pick(Some("release/1.2")), Some("release/1.*".to_string()));
+        assert_eq!(pick(Some("release/2.0")), Some("release/*".to_string()));
+        assert_eq!(pick(Some("feature/main")), None);
+        assert_eq!(pick(None), None);
+    }
+
+    #[test]
+    fn variant_store_path_escapes_slashes() {
+        let mut entry = ManifestEntry::new(Strategy::Copy, "config/.env");
+        entry.branch = Some("release/*".to_string());
         assert_eq!(
-            file_status(&entry, &store_file, &None),
-            FileStatus::StoreOnly
+            entry.store_path(Path::new("/store")),
+            Path::new("/store/.variants/release%2F*/config/.env")
         );
+        assert!(validate_branch_pattern("release/*").is_ok());
+        assert!(validate_branch_pattern("../x").is_err());
+        assert!(validate_branch_pattern("a//b").is_err());
     }
 
     #[test]
-    fn file_status_missing_in_worktree() {
+    fn apply_file_uses_variant_for_matching_branch() {
         let tmp = TempDir::new().unwrap();
-        let store_file = tmp.path().join("store_copy");
-        fs::write(&store_file, "content").unwrap();
+        let store = tmp.path().join("store");
+        fs::create_dir_all(&store).unwrap();
+        fs::write(store.join(".env"), "ENV=dev\n").unwrap();
+        let mut entry = ManifestEntry::new(Strategy::Copy, ".env");
+        let mut variant = ManifestEntry::new(Strategy::Copy, ".env");
+        variant.branch = Some("release/*".to_string());
+        let variant_file = variant.store_path(&store);
+        fs::create_dir_all(variant_file.parent().unwrap()).unwrap();
+        fs::write(&variant_file, "ENV=prod\n").unwrap();
+        entry.variants.push(variant);
+
+        for (branch, expected) in [("release/1.0", "ENV=prod\n"), ("feature/x", "ENV=dev\n")] {
+            let target_root = tmp.path().join(branch.replace('/', "-"));
+            fs::create_dir_all(&target_root).unwrap();
+            apply_file(
+                &entry,
+                &store,
+                &target_root,
+                Some(branch),
+                &Executor::default(),
+            )
+            .unwrap();
+            assert_eq!(
+                fs::read_to_string(target_root.join(".env")).unwrap(),
+                expected
+            );
+        }
+    }
 
-        let wt_root = tmp.path().join("worktree");
-        fs::create_dir_all(&wt_root).unwrap();
+    #[test]
+    fn manifest_roundtrip_keeps_command() {
+        let tmp = TempDir::new().unwrap();
+        let mut entry = ManifestEntry::new(Strategy::Command, ".kube/config");
+        entry.command = Some("kubectl config view --raw".to_string());
+        entry.mode = Some(0o600);
+        write_manifest(tmp.path(), &[entry]).unwrap();
 
-        let entry = ManifestEntry {
-            strategy: Strategy::Symlink,
-            filepath: "missing_file".into(),
-        };
+        let read_back = read_manifest(tmp.path()).unwrap();
+        assert_eq!(read_back[0].strategy, Strategy::Command);
         assert_eq!(
-            file_status(&entry, &store_file, &Some(wt_root)),
-            FileStatus::Missing
+            read_back[0].command.as_deref(),
+            Some("kubectl config view --raw")
         );
+        assert_eq!(read_back[0].mode, Some(0o600));
     }
 
     #[test]
-    fn file_status_symlink_not_link() {
+    fn file_status_seed_ignores_content_changes() {
         let tmp = TempDir::new().unwrap();
-        let store_file = tmp.path().join("store_copy");
-        fs::write(&store_file, "content").unwrap();
+        let store_file = tmp.path().join("store_file");
+        fs::write(&store_file, "seed data").unwrap();
 
         let wt_root = tmp.path().join("worktree");
         fs::create_dir_all(&wt_root).unwrap();
-        fs::write(wt_root.join(".envrc"), "regular file").unwrap();
+        let entry = ManifestEntry::new(Strategy::Seed, "dev.db");
+        assert_eq!(
+            file_status(&entry, &store_file, &Some(wt_root.clone())),
+            FileStatus::Missing
+        );
 
-        let entry = ManifestEntry {
-            strategy: Strategy::Symlink,
-            filepath: ".envrc".into(),
-        };
+        fs::write(wt_root.join("dev.db"), "diverged").unwrap();
         assert_eq!(
             file_status(&entry, &store_file, &Some(wt_root)),
-            FileStatus::NotLink
+            FileStatus::Ok
         );
     }
 
     #[test]
-    fn file_status_symlink_wrong_target() {
+    fn hardlink_entry_links_every_file_in_directory() {
         let tmp = TempDir::new().unwrap();
-        let store_file = tmp.path().join("store_copy");
-        fs::write(&store_file, "content").unwrap();
+        let src = tmp.path().join("src");
+        fs::create_dir_all(src.join("sub")).unwrap();
+        fs::write(src.join("a"), "a").unwrap();
+        fs::write(src.join("sub/b"), "b").unwrap();
 
-        let wt_root = tmp.path().join("worktree");
-        fs::create_dir_all(&wt_root).unwrap();
+        let dst = tmp.path().join("dst");
+        hardlink_entry(&src, &dst, &PathFilter::default()).unwrap();
 
-        let wrong_target = tmp.path().join("wrong_target");
-        fs::write(&wrong_target, "wrong").unwrap();
-        unix_fs::symlink(&wrong_target, wt_root.join(".envrc")).unwrap();
+        assert!(is_hardlinked(&src, &dst));
+        fs::write(dst.join("sub/b"), "changed").unwrap();
+        assert_eq!(fs::read_to_string(src.join("sub/b")).unwrap(), "changed");
+    }
 
-        let entry = ManifestEntry {
-            strategy: Strategy::Symlink,
-            filepath: ".envrc".into(),
+    #[test]
+    fn adopt_and_link_tree_keep_local_files() {
+        let tmp = TempDir::new().unwrap();
+        let wt = tmp.path().join("wt/.idea");
+        let store = tmp.path().join("store/.idea");
+        fs::create_dir_all(wt.join("codeStyles")).unwrap();
+        fs::write(wt.join("modules.xml"), "m").unwrap();
+        fs::write(wt.join("codeStyles/Project.xml"), "p").unwrap();
+        fs::write(wt.join("workspace.xml"), "w").unwrap();
+        let filter = PathFilter {
+            include: Vec::new(),
+            exclude: vec!["workspace.xml".to_string()],
         };
+
+        adopt_tree(&wt, &store, &filter).unwrap();
         assert_eq!(
-            file_status(&entry, &store_file, &Some(wt_root)),
-            FileStatus::WrongLink
+            fs::read_link(wt.join("modules.xml")).unwrap(),
+            store.join("modules.xml")
+        );
+        assert_eq!(
+            fs::read_link(wt.join("codeStyles/Project.xml")).unwrap(),
+            store.join("codeStyles/Project.xml")
+        );
+        assert!(!wt.join("workspace.xml").is_symlink());
+        assert!(!store.join("workspace.xml").exists());
+        assert!(link_tree_status(&store, &wt, &filter).is_empty());
+
+        // 新しいファイルは worktree 固有、store のファイルは個別に状態を返す
+        fs::write(wt.join("local.xml"), "l").unwrap();
+        fs::remove_file(wt.join("modules.xml")).unwrap();
+        fs::remove_file(wt.join("codeStyles/Project.xml")).unwrap();
+        fs::write(wt.join("codeStyles/Project.xml"), "edited").unwrap();
+        assert_eq!(
+            link_tree_status(&store, &wt, &filter),
+            vec![
+                ("codeStyles/Project.xml".to_string(), FileStatus::NotLink),
+                ("local.xml".to_string(), FileStatus::Extra),
+                ("modules.xml".to_string(), FileStatus::Missing),
+            ]
         );
+        let mut entry = ManifestEntry::new(Strategy::LinkTree, ".idea");
+        entry.filter = filter.clone();
+        let wt_root = Some(tmp.path().join("wt"));
+        assert_eq!(file_status(&entry, &store, &wt_root), FileStatus::NotLink);
+
+        link_tree(&store, &wt, &filter).unwrap();
+        assert_eq!(file_status(&entry, &store, &wt_root), FileStatus::Ok);
+        assert_eq!(fs::read_to_string(wt.join("local.xml")).unwrap(), "l");
+
+        unlink_tree(&wt).unwrap();
+        assert!(!wt.join("modules.xml").is_symlink());
+        assert_eq!(fs::read_to_string(wt.join("modules.xml")).unwrap(), "m");
     }
 
     #[test]
-    fn file_status_symlink_ok() {
+    fn clone_entry_creates_independent_copy() {
+        let tmp = TempDir::new().unwrap();
+        let src = tmp.path().join("src");
+        fs::write(&src, "original").unwrap();
+        fs::set_permissions(&src, fs::Permissions::from_mode(0o600)).unwrap();
+
+        let dst = tmp.path().join("dst");
+        clone_entry(&src, &dst, &PathFilter::default()).unwrap();
+        assert_eq!(file_mode(&dst), Some(0o600));
+
+        fs::write(&dst, "changed").unwrap();
+        assert_eq!(fs::read_to_string(&src).unwrap(), "original");
+        assert!(!is_hardlinked(&src, &dst));
+    }
+
+    #[test]
+    fn file_status_copy_mode_diff() {
         let tmp = TempDir::new().unwrap();
         let store_file = tmp.path().join("store_copy");
-        fs::write(&store_file, "content").unwrap();
+        fs::write(&store_file, "same content").unwrap();
 
         let wt_root = tmp.path().join("worktree");
         fs::create_dir_all(&wt_root).unwrap();
-        unix_fs::symlink(&store_file, wt_root.join(".envrc")).unwrap();
+        let wt_file = wt_root.join(".env");
+        fs::write(&wt_file, "same content").unwrap();
+        fs::set_permissions(&wt_file, fs::Permissions::from_mode(0o644)).unwrap();
 
-        let entry = ManifestEntry {
-            strategy: Strategy::Symlink,
-            filepath: ".envrc".into(),
-        };
+        let mut entry = ManifestEntry::new(Strategy::Copy, ".env");
+        entry.mode = Some(0o600);
+        assert_eq!(
+            file_status(&entry, &store_file, &Some(wt_root.clone())),
+            FileStatus::ModeDiff
+        );
+
+        fs::set_permissions(&wt_file, fs::Permissions::from_mode(0o600)).unwrap();
         assert_eq!(
             file_status(&entry, &store_file, &Some(wt_root)),
             FileStatus::Ok
@@ -536,43 +3014,53 @@ mod tests {
     }
 
     #[test]
-    fn file_status_copy_modified() {
+    fn file_status_dir_copy_mode_diff_inside() {
         let tmp = TempDir::new().unwrap();
-        let store_file = tmp.path().join("store_copy");
-        fs::write(&store_file, "original").unwrap();
+        let store_dir = tmp.path().join("store_scripts");
+        fs::create_dir_all(&store_dir).unwrap();
+        fs::write(store_dir.join("run.sh"), "#!/bin/sh").unwrap();
+        fs::set_permissions(store_dir.join("run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
 
         let wt_root = tmp.path().join("worktree");
-        fs::create_dir_all(&wt_root).unwrap();
-        fs::write(wt_root.join(".mcp.json"), "modified").unwrap();
+        let wt_dir = wt_root.join("scripts");
+        fs::create_dir_all(&wt_dir).unwrap();
+        fs::set_permissions(&wt_dir, fs::metadata(&store_dir).unwrap().permissions()).unwrap();
+        fs::write(wt_dir.join("run.sh"), "#!/bin/sh").unwrap();
+        fs::set_permissions(wt_dir.join("run.sh"), fs::Permissions::from_mode(0o644)).unwrap();
 
-        let entry = ManifestEntry {
-            strategy: Strategy::Copy,
-            filepath: ".mcp.json".into(),
-        };
+        let entry = ManifestEntry::new(Strategy::Copy, "scripts");
         assert_eq!(
-            file_status(&entry, &store_file, &Some(wt_root)),
-            FileStatus::Modified
+            file_status(&entry, &store_dir, &Some(wt_root)),
+            FileStatus::ModeDiff
         );
     }
 
     #[test]
-    fn file_status_copy_ok() {
+    fn file_status_dotenv_ignores_comments_and_order() {
         let tmp = TempDir::new().unwrap();
-        let store_file = tmp.path().join("store_copy");
-        fs::write(&store_file, "same content").unwrap();
+        let store_file = tmp.path().join("store_env");
+        fs::write(&store_file, "A=1\nB=2\n").unwrap();
 
         let wt_root = tmp.path().join("worktree");
         fs::create_dir_all(&wt_root).unwrap();
-        fs::write(wt_root.join(".mcp.json"), "same content").unwrap();
+        fs::write(wt_root.join(".env"), "# local note\nB=2\nA=1\n").unwrap();
+        fs::set_permissions(
+            wt_root.join(".env"),
+            fs::metadata(&store_file).unwrap().permissions(),
+        )
+        .unwrap();
 
-        let entry = ManifestEntry {
-            strategy: Strategy::Copy,
-            filepath: ".mcp.json".into(),
-        };
+        let entry = ManifestEntry::new(Strategy::Dotenv, ".env");
         assert_eq!(
-            file_status(&entry, &store_file, &Some(wt_root)),
+            file_status(&entry, &store_file, &Some(wt_root.clone())),
             FileStatus::Ok
         );
+
+        fs::write(wt_root.join(".env"), "A=1\nB=3\n").unwrap();
+        assert_eq!(
+            file_status(&entry, &store_file, &Some(wt_root)),
+            FileStatus::Modified
+        );
     }
 
     // ---- apply_file ----
@@ -587,7 +3075,14 @@ mod tests {
         let target_root = tmp.path().join("target");
         fs::create_dir_all(&target_root).unwrap();
 
-        apply_file(&Strategy::Symlink, ".envrc", &store, &target_root).unwrap();
+        apply_file(
+            &ManifestEntry::new(Strategy::Symlink, ".envrc"),
+            &store,
+            &target_root,
+            None,
+            &Executor::default(),
+        )
+        .unwrap();
 
         let target = target_root.join(".envrc");
         assert!(target.symlink_metadata().unwrap().file_type().is_symlink());
@@ -603,13 +3098,38 @@ mod tests {
         let target_root = tmp.path().join("target");
         fs::create_dir_all(&target_root).unwrap();
 
-        apply_file(&Strategy::Copy, ".mcp.json", &store, &target_root).unwrap();
+        apply_file(
+            &ManifestEntry::new(Strategy::Copy, ".mcp.json"),
+            &store,
+            &target_root,
+            None,
+            &Executor::default(),
+        )
+        .unwrap();
 
         let target = target_root.join(".mcp.json");
         assert!(target.is_file());
         assert_eq!(fs::read_to_string(&target).unwrap(), "content");
     }
 
+    #[test]
+    fn apply_file_copy_applies_recorded_mode() {
+        let tmp = TempDir::new().unwrap();
+        let store = tmp.path().join("store");
+        fs::create_dir_all(&store).unwrap();
+        fs::write(store.join(".env"), "SECRET=1").unwrap();
+        fs::set_permissions(store.join(".env"), fs::Permissions::from_mode(0o644)).unwrap();
+
+        let target_root = tmp.path().join("target");
+        fs::create_dir_all(&target_root).unwrap();
+
+        let mut entry = ManifestEntry::new(Strategy::Copy, ".env");
+        entry.mode = Some(0o600);
+        apply_file(&entry, &store, &target_root, None, &Executor::default()).unwrap();
+
+        assert_eq!(file_mode(&target_root.join(".env")), Some(0o600));
+    }
+
     #[test]
     fn apply_file_skips_existing() {
         let tmp = TempDir::new().unwrap();
@@ -621,7 +3141,14 @@ mod tests {
         fs::create_dir_all(&target_root).unwrap();
         fs::write(target_root.join(".envrc"), "existing").unwrap();
 
-        apply_file(&Strategy::Symlink, ".envrc", &store, &target_root).unwrap();
+        apply_file(
+            &ManifestEntry::new(Strategy::Symlink, ".envrc"),
+            &store,
+            &target_root,
+            None,
+            &Executor::default(),
+        )
+        .unwrap();
 
         // 既存ファイルが変更されていないこと
         assert_eq!(
@@ -640,7 +3167,14 @@ mod tests {
         let target_root = tmp.path().join("target");
         fs::create_dir_all(&target_root).unwrap();
 
-        apply_file(&Strategy::Copy, "sub/dir/file", &store, &target_root).unwrap();
+        apply_file(
+            &ManifestEntry::new(Strategy::Copy, "sub/dir/file"),
+            &store,
+            &target_root,
+            None,
+            &Executor::default(),
+        )
+        .unwrap();
 
         assert!(target_root.join("sub/dir/file").is_file());
     }
@@ -658,7 +3192,14 @@ mod tests {
         let target_root = tmp.path().join("target");
         fs::create_dir_all(&target_root).unwrap();
 
-        apply_file(&Strategy::Symlink, "nix/secrets", &store, &target_root).unwrap();
+        apply_file(
+            &ManifestEntry::new(Strategy::Symlink, "nix/secrets"),
+            &store,
+            &target_root,
+            None,
+            &Executor::default(),
+        )
+        .unwrap();
 
         let target = target_root.join("nix/secrets");
         assert!(target.symlink_metadata().unwrap().file_type().is_symlink());
@@ -678,7 +3219,14 @@ mod tests {
         let target_root = tmp.path().join("target");
         fs::create_dir_all(&target_root).unwrap();
 
-        apply_file(&Strategy::Copy, "nix/secrets", &store, &target_root).unwrap();
+        apply_file(
+            &ManifestEntry::new(Strategy::Copy, "nix/secrets"),
+            &store,
+            &target_root,
+            None,
+            &Executor::default(),
+        )
+        .unwrap();
 
         let target = target_root.join("nix/secrets");
         assert!(target.is_dir());
@@ -699,10 +3247,7 @@ mod tests {
         fs::create_dir_all(&wt_root).unwrap();
         unix_fs::symlink(&store_dir, wt_root.join("secrets")).unwrap();
 
-        let entry = ManifestEntry {
-            strategy: Strategy::Symlink,
-            filepath: "secrets".into(),
-        };
+        let entry = ManifestEntry::new(Strategy::Symlink, "secrets");
         assert_eq!(
             file_status(&entry, &store_dir, &Some(wt_root)),
             FileStatus::Ok
@@ -721,10 +3266,7 @@ mod tests {
         fs::create_dir_all(&wt_dir).unwrap();
         fs::write(wt_dir.join("key"), "secret").unwrap();
 
-        let entry = ManifestEntry {
-            strategy: Strategy::Copy,
-            filepath: "secrets".into(),
-        };
+        let entry = ManifestEntry::new(Strategy::Copy, "secrets");
         assert_eq!(
             file_status(&entry, &store_dir, &Some(wt_root)),
             FileStatus::Ok
@@ -743,25 +3285,44 @@ mod tests {
         fs::create_dir_all(&wt_dir).unwrap();
         fs::write(wt_dir.join("key"), "modified_secret").unwrap();
 
-        let entry = ManifestEntry {
-            strategy: Strategy::Copy,
-            filepath: "secrets".into(),
-        };
+        let entry = ManifestEntry::new(Strategy::Copy, "secrets");
         assert_eq!(
             file_status(&entry, &store_dir, &Some(wt_root)),
             FileStatus::Modified
         );
     }
 
+    #[test]
+    fn file_status_dir_copy_ignores_excluded_paths() {
+        let tmp = TempDir::new().unwrap();
+        let store_dir = tmp.path().join("store_idea");
+        fs::create_dir_all(&store_dir).unwrap();
+        fs::write(store_dir.join("modules.xml"), "m").unwrap();
+
+        let wt_root = tmp.path().join("worktree");
+        let wt_dir = wt_root.join(".idea");
+        fs::create_dir_all(wt_dir.join("caches")).unwrap();
+        fs::write(wt_dir.join("modules.xml"), "m").unwrap();
+        fs::write(wt_dir.join("workspace.xml"), "local").unwrap();
+        fs::write(wt_dir.join("caches/blob"), "b").unwrap();
+
+        let mut entry = ManifestEntry::new(Strategy::Copy, ".idea");
+        entry.filter.exclude = vec!["workspace.xml".to_string(), "caches/".to_string()];
+        let wt = Some(wt_root.clone());
+        assert_eq!(file_status(&entry, &store_dir, &wt), FileStatus::Ok);
+        entry.hash = Some(crate::hash::hash_path_filtered(&store_dir, &entry.filter).unwrap());
+        assert_eq!(file_status(&entry, &store_dir, &wt), FileStatus::Ok);
+
+        fs::write(wt_dir.join("modules.xml"), "changed").unwrap();
+        assert_eq!(file_status(&entry, &store_dir, &wt), FileStatus::Modified);
+    }
+
     #[test]
     fn file_status_dir_missing_store() {
         let tmp = TempDir::new().unwrap();
         let store_dir = tmp.path().join("nonexistent_dir");
 
-        let entry = ManifestEntry {
-            strategy: Strategy::Copy,
-            filepath: "secrets".into(),
-        };
+        let entry = ManifestEntry::new(Strategy::Copy, "secrets");
         assert_eq!(
             file_status(&entry, &store_dir, &None),
             FileStatus::MissingStore
@@ -788,4 +3349,20 @@ mod tests {
             "content2"
         );
     }
+
+    #[test]
+    fn copy_dir_recursive_preserves_modes() {
+        let tmp = TempDir::new().unwrap();
+        let src = tmp.path().join("src");
+        fs::create_dir_all(src.join("bin")).unwrap();
+        fs::write(src.join("bin/run.sh"), "#!/bin/sh").unwrap();
+        fs::set_permissions(src.join("bin/run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+        fs::set_permissions(src.join("bin"), fs::Permissions::from_mode(0o700)).unwrap();
+
+        let dst = tmp.path().join("dst");
+        copy_dir_recursive(&src, &dst).unwrap();
+
+        assert_eq!(file_mode(&dst.join("bin/run.sh")), Some(0o755));
+        assert_eq!(file_mode(&dst.join("bin")), Some(0o700));
+    }
 }
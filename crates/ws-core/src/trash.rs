@@ -0,0 +1,268 @@
+use anyhow::{Context, Result};
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::exec::Executor;
+use crate::hash;
+use crate::oplog::{format_utc, now_secs};
+use crate::store::{ManifestEntry, Strategy, path_or_symlink_exists, worktree_path};
+
+/// store 内のごみ箱ディレクトリ。削除した worktree の変更済みファイルを退避する。
+pub const TRASH_DIR: &str = ".trash";
+/// ごみ箱のエントリの情報を記録するファイル。
+const META_FILE: &str = "meta.json";
+/// 退避したファイルを置くディレクトリ（worktree のルートからの相対パスで置く）。
+const FILES_DIR: &str = "files";
+
+/// ごみ箱の 1 エントリ（1 回の worktree 削除）の情報。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashMeta {
+    /// 退避した時刻（UTC の RFC 3339 形式）
+    pub time: String,
+    /// 退避した時刻（Unix 時刻）。保持期間の判定に使う
+    pub created: u64,
+    /// 削除した worktree の絶対パス
+    pub worktree: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// 退避したファイル（worktree のルートからの相対パス）
+    pub files: Vec<String>,
+}
+
+pub struct TrashEntry {
+    pub id: String,
+    pub meta: TrashMeta,
+    pub dir: PathBuf,
+}
+
+impl TrashEntry {
+    /// 退避したファイルの実体のパス。
+    pub fn file_path(&self, file: &str) -> PathBuf {
+        self.dir.join(FILES_DIR).join(file)
+    }
+}
+
+pub fn trash_dir(store: &Path) -> PathBuf {
+    store.join(TRASH_DIR)
+}
+
+/// worktree 固有の内容を持つ strategy か。symlink・hardlink・link-tree は store と内容を共有し、
+/// command は作り直せるので退避しない。seed と merge-json は store と内容が違うのが前提なので、
+/// 比べると毎回退避されてしまう。
+fn keeps_local_copy(strategy: &Strategy) -> bool {
+    !strategy.shares_store()
+        && !matches!(
+            strategy,
+            Strategy::Command | Strategy::Seed | Strategy::MergeJson
+        )
+}
+
+/// worktree で store と内容が異なるファイル（push していない変更）を返す。
+pub fn modified_copies(
+    store: &Path,
+    entries: &[ManifestEntry],
+    wt_root: &Path,
+    branch: Option<&str>,
+) -> Vec<String> {
+    entries
+        .iter()
+        .filter(|entry| keeps_local_copy(&entry.strategy))
+        .filter(|entry| {
+            let Ok(wt_file) = worktree_path(wt_root, &entry.filepath) else {
+                return false;
+            };
+            if !path_or_symlink_exists(&wt_file) {
+                return false;
+            }
+            let variant = entry.for_branch(branch);
+            let store_file = variant.store_path(store);
+            match (
+                hash::hash_path_filtered(&wt_file, &variant.filter),
+                hash::hash_path_filtered(&store_file, &variant.filter),
+            ) {
+                (Ok(wt), Ok(stored)) => wt != stored,
+                (Ok(_), Err(_)) => true,
+                _ => false,
+            }
+        })
+        .map(|entry| entry.filepath.clone())
+        .collect()
+}
+
+/// `files` を worktree からごみ箱に退避し、エントリの ID を返す。
+pub fn stash(
+    store: &Path,
+    wt_root: &Path,
+    branch: Option<&str>,
+    files: &[String],
+    exec: &Executor,
+) -> Result<String> {
+    let created = now_secs();
+    let name = wt_root
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "worktree".to_string());
+    let base = format!("{}-{}", created, name);
+    let mut id = base.clone();
+    let mut n = 2;
+    while trash_dir(store).join(&id).exists() {
+        id = format!("{}-{}", base, n);
+        n += 1;
+    }
+    let dir = trash_dir(store).join(&id);
+
+    for file in files {
+        let dst = dir.join(FILES_DIR).join(file);
+        if let Some(parent) = dst.parent() {
+            exec.create_dir_all(parent)?;
+        }
+        exec.copy(&worktree_path(wt_root, file)?, &dst)?;
+    }
+    let meta = TrashMeta {
+        time: format_utc(created),
+        created,
+        worktree: wt_root.display().to_string(),
+        branch: branch.map(str::to_string),
+        files: files.to_vec(),
+    };
+    exec.create_dir_all(&dir)?;
+    exec.write(&dir.join(META_FILE), serde_json::to_string_pretty(&meta)?)?;
+    Ok(id)
+}
+
+/// ごみ箱のエントリを新しい順に返す。情報を読めないディレクトリは無視する。
+pub fn list(store: &Path) -> Result<Vec<TrashEntry>> {
+    let root = trash_dir(store);
+    if !root.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut entries = Vec::new();
+    let dirs = fs::read_dir(&root).with_context(|| {
+        t!("store.trash_read_failed", path = root.display().to_string()).to_string()
+    })?;
+    for dir in dirs.flatten() {
+        let Ok(content) = fs::read_to_string(dir.path().join(META_FILE)) else {
+            continue;
+        };
+        let Ok(meta) = serde_json::from_str::<TrashMeta>(&content) else {
+            continue;
+        };
+        entries.push(TrashEntry {
+            id: dir.file_name().to_string_lossy().into_owned(),
+            meta,
+            dir: dir.path(),
+        });
+    }
+    entries.sort_by(|a, b| {
+        b.meta
+            .created
+            .cmp(&a.meta.created)
+            .then_with(|| b.id.cmp(&a.id))
+    });
+    Ok(entries)
+}
+
+/// 保持期間（日数）を過ぎたエントリを削除し、削除した数を返す。`0` なら削除しない。
+pub fn expire(store: &Path, retention_days: u64, exec: &Executor) -> Result<usize> {
+    if retention_days == 0 {
+        return Ok(0);
+    }
+    let cutoff = now_secs().saturating_sub(retention_days * 86_400);
+    let mut removed = 0;
+    for entry in list(store)? {
+        if entry.meta.created < cutoff {
+            exec.remove(&entry.dir)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup() -> (TempDir, PathBuf, PathBuf) {
+        let tmp = TempDir::new().unwrap();
+        let store = tmp.path().join("store");
+        let wt = tmp.path().join("feat");
+        fs::create_dir_all(&store).unwrap();
+        fs::create_dir_all(&wt).unwrap();
+        (tmp, store, wt)
+    }
+
+    #[test]
+    fn modified_copies_skips_unchanged_and_linked_entries() {
+        let (_tmp, store, wt) = setup();
+        for (file, stored, local) in [
+            (".env", "A=1\n", "A=2\n"),
+            (".mcp.json", "{}", "{}"),
+            (".envrc", "use flake", "use nix"),
+            ("seed.txt", "initial", "edited"),
+            ("settings.json", "{}", r#"{"local":true}"#),
+        ] {
+            fs::write(store.join(file), stored).unwrap();
+            fs::write(wt.join(file), local).unwrap();
+        }
+        fs::write(wt.join("new.txt"), "x").unwrap();
+        let entries = vec![
+            ManifestEntry::new(Strategy::Dotenv, ".env"),
+            ManifestEntry::new(Strategy::Copy, ".mcp.json"),
+            ManifestEntry::new(Strategy::Symlink, ".envrc"),
+            ManifestEntry::new(Strategy::Seed, "seed.txt"),
+            ManifestEntry::new(Strategy::MergeJson, "settings.json"),
+            ManifestEntry::new(Strategy::Copy, "new.txt"),
+            ManifestEntry::new(Strategy::Copy, "missing.txt"),
+        ];
+
+        assert_eq!(
+            modified_copies(&store, &entries, &wt, None),
+            vec![".env".to_string(), "new.txt".to_string()]
+        );
+    }
+
+    #[test]
+    fn stash_list_and_expire() {
+        let (_tmp, store, wt) = setup();
+        fs::create_dir_all(wt.join("config")).unwrap();
+        fs::write(wt.join("config/.env"), "A=2\n").unwrap();
+        let exec = Executor::new(false);
+
+        let id = stash(
+            &store,
+            &wt,
+            Some("feat"),
+            &["config/.env".to_string()],
+            &exec,
+        )
+        .unwrap();
+        let second = stash(&store, &wt, None, &["config/.env".to_string()], &exec).unwrap();
+        assert_ne!(id, second);
+
+        let entries = list(&store).unwrap();
+        assert_eq!(entries.len(), 2);
+        let entry = entries.iter().find(|e| e.id == id).unwrap();
+        assert_eq!(entry.meta.branch.as_deref(), Some("feat"));
+        assert_eq!(
+            fs::read_to_string(entry.file_path("config/.env")).unwrap(),
+            "A=2\n"
+        );
+
+        // 古いエントリだけを削除する
+        let mut meta = entry.meta.clone();
+        meta.created -= 40 * 86_400;
+        fs::write(
+            entry.dir.join(META_FILE),
+            serde_json::to_string(&meta).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(expire(&store, 0, &exec).unwrap(), 0);
+        assert_eq!(expire(&store, 30, &exec).unwrap(), 1);
+        let remaining = list(&store).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].id, second);
+    }
+}
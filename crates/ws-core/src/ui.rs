@@ -1,6 +1,6 @@
 use anstyle::{AnsiColor, Effects, Style};
 
-use crate::store::FileStatus;
+use crate::store::{FileStatus, ManifestEntry};
 
 // --- Style constants ---
 
@@ -49,8 +49,21 @@ pub fn status_style(status: &FileStatus) -> Style {
         FileStatus::Ok => STYLE_OK,
         FileStatus::Missing | FileStatus::MissingStore => STYLE_ERROR,
         FileStatus::Error => STYLE_ERROR_BOLD,
-        FileStatus::Modified | FileStatus::NotLink | FileStatus::WrongLink => STYLE_WARN,
-        FileStatus::StoreOnly => STYLE_DIM,
+        FileStatus::Modified
+        | FileStatus::NotLink
+        | FileStatus::WrongLink
+        | FileStatus::ModeDiff
+        | FileStatus::BaseChanged => STYLE_WARN,
+        FileStatus::NotInProfile | FileStatus::StoreOnly => STYLE_DIM,
+        FileStatus::Extra => STYLE_INFO,
+    }
+}
+
+/// Cell showing which branch variant of a store entry is in use (`-` for the default content).
+pub fn variant_cell(entry: &ManifestEntry) -> StyledCell {
+    match entry.branch {
+        Some(ref pattern) => StyledCell::new(pattern.as_str(), STYLE_INFO),
+        None => StyledCell::new("-", STYLE_DIM),
     }
 }
 